@@ -1,48 +1,244 @@
-use super::{dh_cpu::CPU, END_OF_RAM, KB, START_OF_RAM};
+use std::io;
 
+use super::{
+    cartridge::Cartrige, dh_cpu::CPU, dh_ppu::PPU, END_OF_RAM, KB, START_OF_RAM,
+};
+
+const RAM_MIRROR_MASK: u16 = 0x07FF; // 2KB internal ram
+const START_OF_PPU_REGS: u16 = 0x2000;
+const END_OF_PPU_REGS: u16 = 0x3FFF;
+const PPU_REG_MIRROR_MASK: u16 = 0x0007; // 8 registers
+const START_OF_APU_IO: u16 = 0x4000;
+const END_OF_APU_IO: u16 = 0x401F;
+const OAM_DMA: u16 = 0x4014;
+/// Default cpu cycles between flushes of the cartridge's save, about five
+/// seconds.
+pub const SAVE_FLUSH_CYCLES: u32 = 5 * 1_789_773;
+
+/// ```no_run
+/// CPU memory map
+/// $0000-$07FF  2KB internal ram
+/// $0800-$1FFF  mirrors of $0000-$07FF
+/// $2000-$2007  PPU registers
+/// $2008-$3FFF  mirrors of $2000-$2007 (every 8 bytes)
+/// $4000-$401F  APU and I/O registers
+/// $4020-$FFFF  cartridge space
+/// ```
 #[derive(Debug)]
 pub struct Bus {
-    pub cpu_ram: [u8; KB(64)],  // 2Kb of ram
+    pub cpu_ram: [u8; KB(2)],   // 2Kb of ram
+    pub ppu: PPU,               // picture processing unit
+    pub apu_io: [u8; 0x20],     // APU and I/O register latches
     pub sys_clock_counter: u32, // motherboards clock for busses
+    pub save_interval: u32,     // cpu cycles between save flushes
+    cart: Option<Cartrige>,     // game pak plugged into the console
+    save_timer: u32,            // cpu cycles until the next save flush
+    save_due: bool,             // save_interval elapsed since the last flush
+
+    // debugging aid: when set every address is plain ram
+    #[cfg(feature = "debug")]
+    flat_ram: Option<Box<[u8]>>,
 }
 impl Bus {
     /// Creates a new [`Bus`]. With 2Kb of MOS 6502 memory
     pub fn new() -> Self {
         Self {
-            cpu_ram: [0u8; KB(64)],
+            cpu_ram: [0u8; KB(2)],
+            ppu: PPU::new(),
+            apu_io: [0u8; 0x20],
             sys_clock_counter: 0,
+            save_interval: SAVE_FLUSH_CYCLES,
+            cart: None,
+            save_timer: SAVE_FLUSH_CYCLES,
+            save_due: false,
+            #[cfg(feature = "debug")]
+            flat_ram: None,
         }
     }
 
+    /// Creates a [`Bus`] that ignores the NES memory map and treats the full
+    /// 64Kb address space as ram. Handy for feeding the cpu hand written
+    /// programs.
+    #[cfg(feature = "debug")]
+    pub fn new_flat() -> Self {
+        Self {
+            flat_ram: Some(vec![0u8; KB(64)].into_boxed_slice()),
+            ..Self::new()
+        }
+    }
+
+    /// Plugs a cartridge into the console, replacing any previous one. The
+    /// cartridge answers cpu reads/writes in $4020-$FFFF and ppu accesses to
+    /// the pattern tables.
+    pub fn insert_cartridge(&mut self, cart: Cartrige) {
+        self.cart = Some(cart);
+    }
+
+    pub fn cartridge(&self) -> Option<&Cartrige> {
+        self.cart.as_ref()
+    }
+
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartrige> {
+        self.cart.as_mut()
+    }
+
     #[inline]
-    pub fn read(&self, addr: u16, _b_read_only: bool) -> u8 {
-        if addr >= START_OF_RAM && addr <= END_OF_RAM {
-            return self.cpu_ram[addr as usize];
+    pub fn read(&mut self, addr: u16, b_read_only: bool) -> u8 {
+        #[cfg(feature = "debug")]
+        if let Some(ram) = &self.flat_ram {
+            return ram[addr as usize];
+        }
+
+        match addr {
+            START_OF_RAM..=END_OF_RAM => {
+                self.cpu_ram[(addr & RAM_MIRROR_MASK) as usize]
+            }
+            START_OF_PPU_REGS..=END_OF_PPU_REGS => self.ppu.cpu_read(
+                self.cart.as_mut(),
+                addr & PPU_REG_MIRROR_MASK,
+                b_read_only,
+            ),
+            // APU is not emulated yet, hand back whatever was last latched
+            START_OF_APU_IO..=END_OF_APU_IO => {
+                self.apu_io[(addr - START_OF_APU_IO) as usize]
+            }
+            // cartridge space, the bus floats when nothing answers
+            _ => {
+                let mut data: u8 = 0x00;
+                if let Some(cart) = &mut self.cart {
+                    cart.cpu_read(addr, &mut data);
+                }
+                data
+            }
         }
-        println!("Memory accessed out of bound: {:?}", addr);
-        0x00
     }
 
     #[inline]
     pub fn write(&mut self, addr: u16, data: u8) {
-        assert!(
-            addr >= START_OF_RAM && addr <= END_OF_RAM,
-            "can't write to address that is out of memory bounds"
-        );
-        self.cpu_ram[addr as usize] = data;
+        #[cfg(feature = "debug")]
+        if let Some(ram) = &mut self.flat_ram {
+            ram[addr as usize] = data;
+            return;
+        }
+
+        match addr {
+            START_OF_RAM..=END_OF_RAM => {
+                self.cpu_ram[(addr & RAM_MIRROR_MASK) as usize] = data;
+            }
+            START_OF_PPU_REGS..=END_OF_PPU_REGS => {
+                self.ppu.cpu_write(
+                    self.cart.as_mut(),
+                    addr & PPU_REG_MIRROR_MASK,
+                    data,
+                );
+                if let Some(cart) = &mut self.cart {
+                    cart.ppu_register_write(
+                        START_OF_PPU_REGS | (addr & PPU_REG_MIRROR_MASK),
+                        data,
+                    );
+                }
+            }
+            OAM_DMA => {
+                // copy the whole cpu page into OAM, the cpu stall is not
+                // modeled yet
+                let mut page = [0u8; 256];
+                let base: u16 = (data as u16) << 8;
+                for (offset, byte) in page.iter_mut().enumerate() {
+                    *byte = self.read(base | offset as u16, false);
+                }
+                self.ppu.oam_dma(&page);
+            }
+            START_OF_APU_IO..=END_OF_APU_IO => {
+                self.apu_io[(addr - START_OF_APU_IO) as usize] = data;
+            }
+            // cartridge space
+            _ => {
+                if let Some(cart) = &mut self.cart {
+                    cart.cpu_write(addr, data);
+                }
+            }
+        }
     }
 
+    /// One master tick. The PPU runs every tick and the cpu at a third of
+    /// that rate, every cpu cycle is also delivered to the cartridge. The
+    /// cartridge's IRQ output is wired to the cpu's /IRQ input and the
+    /// PPU's vblank NMI to its /NMI input.
+    ///
+    /// Every `save_interval` cpu cycles the save is marked due, see
+    /// [`Bus::save_due`].
     #[inline]
     pub fn clock(&mut self, cpu: &mut CPU) {
+        self.ppu.clock(self.cart.as_mut());
         if self.sys_clock_counter % 3 == 0 {
-            CPU::reset(cpu, self);
+            CPU::clock(cpu, self);
+            if let Some(cart) = &mut self.cart {
+                cart.cpu_clock();
+            }
+            self.save_timer = self.save_timer.saturating_sub(1);
+            if self.save_timer == 0 {
+                self.save_timer = self.save_interval;
+                self.save_due = true;
+            }
         }
+        cpu.set_irq(self.irq());
+        cpu.set_nmi(self.ppu.nmi());
         self.sys_clock_counter += 1;
     }
 
+    /// Wired-or of every device that can pull the /IRQ line low.
+    pub fn irq(&self) -> bool {
+        match &self.cart {
+            Some(cart) => cart.irq_state(),
+            None => false,
+        }
+    }
+
+    /// Current audio output level. There is no APU yet, so this is only
+    /// the cartridge's expansion audio.
+    pub fn audio_sample(&self) -> f32 {
+        match &self.cart {
+            Some(cart) => cart.audio_output(),
+            None => 0.0,
+        }
+    }
+
+    /// `true` once `save_interval` cpu cycles passed since the last
+    /// [`Bus::flush_save`]. The frontend flushes between frames so a crash
+    /// loses at most a few seconds of progress, without file I/O in the
+    /// middle of emulation.
+    pub const fn save_due(&self) -> bool {
+        self.save_due
+    }
+
+    /// Writes the cartridge's battery backed memory to its .sav file, see
+    /// [`Cartrige::flush_save`].
+    pub fn flush_save(&mut self) -> io::Result<bool> {
+        let flushed: bool = match &mut self.cart {
+            Some(cart) => cart.flush_save()?,
+            None => false,
+        };
+        self.save_due = false;
+        Ok(flushed)
+    }
+
+    /// Saves everything the cartridge keeps between sessions, for when the
+    /// console is switched off.
+    pub fn shutdown(&mut self) -> io::Result<()> {
+        self.flush_save()?;
+        match &self.cart {
+            Some(cart) => cart.save_writes(),
+            None => Ok(()),
+        }
+    }
+
     pub fn reset(&mut self, cpu: &mut CPU) {
+        if let Some(cart) = &mut self.cart {
+            cart.reset();
+        }
         CPU::reset(cpu, self);
         self.sys_clock_counter = 0;
+        self.save_timer = self.save_interval;
     }
 
     #[cfg(feature = "debug")]
@@ -1,35 +1,1235 @@
-use super::mappers::mapper::*;
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
 
-#[derive(Debug)]
+use super::mappers::{
+    self,
+    m000::M000,
+    m020::{
+        FDS_HEADER_MAGIC, FDS_HEADER_SIZE, FDS_SIDE_MAGIC, FDS_SIDE_SIZE, M020,
+    },
+    mapper::{Mapped, Mapper, MapperData},
+};
+use super::patch::{self, PatchError};
+use super::romdb::{RomDatabase, RomEntry, RomHash, RomInfo};
+use super::unif::{self, UnifError, UnifImage};
+use super::zip::{self, ZipArchive, ZipEntry, ZipError};
+use super::KB;
+
+/// Every iNES image starts with these four bytes: "NES" followed by MS-DOS EOF
+pub const INES_MAGIC: [u8; 4] = [0x4E, 0x45, 0x53, 0x1A];
+pub const INES_HEADER_SIZE: usize = 16;
+pub const TRAINER_SIZE: usize = 512;
+pub const PRG_BANK_SIZE: usize = KB(16);
+pub const CHR_BANK_SIZE: usize = KB(8);
+/// The Disk System's BIOS ROM, mapped at $E000-$FFFF.
+pub const FDS_BIOS_SIZE: usize = KB(8);
+/// Patches picked up next to an image, in the order they are looked for.
+pub const PATCH_EXTENSIONS: [&str; 3] = ["ips", "bps", "ups"];
+/// Entries of a zip archive that count as ROMs.
+pub const ROM_EXTENSIONS: [&str; 4] = ["nes", "unf", "fds", "nsf"];
+/// NSF music rips start with "NESM" followed by MS-DOS EOF.
+pub const NSF_MAGIC: [u8; 5] = [0x4E, 0x45, 0x53, 0x4D, 0x1A];
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mirroring {
     HORIZONTAL,
     VERTICAL,
     ONESCREAN_LO,
     ONESCREAN_HI,
+    FOUR_SCREEN,
+}
+
+/// Where a PPU access routed through the cartridge ended up.
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PpuAccess {
+    /// The board answered on its own (CHR, mapper internal memory).
+    CARTRIDGE,
+    /// The board steered the access into the console's 2KB of nametable
+    /// ram (CIRAM) at this offset.
+    CIRAM(usize),
+    /// The board does not decode the address, the PPU's own memory answers
+    /// using the cartridge's mirroring.
+    CONSOLE,
+}
+
+/// Which header revision the image was described with.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderFormat {
+    INES,
+    NES2,
+}
+
+/// Console the image was built for (flags 7, bits 0-1).
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleType {
+    NES,
+    VS_SYSTEM {
+        ppu_type: u8,
+        hardware_type: u8,
+    },
+    PLAYCHOICE_10,
+    /// NES 2.0 extended console type from byte 13
+    EXTENDED(u8),
+}
+
+/// CPU/PPU timing the image expects (NES 2.0 byte 12).
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimingRegion {
+    NTSC,
+    PAL,
+    MULTI_REGION,
+    DENDY,
+}
+
+/// Reasons a ROM image can be rejected while building a [`Cartrige`].
+#[derive(Debug)]
+pub enum CartridgeError {
+    /// The image could not be read from disk.
+    Io(io::Error),
+    /// The image is shorter than the 16 byte header.
+    MissingHeader,
+    /// The first four bytes are not `NES<EOF>`.
+    BadMagic([u8; 4]),
+    /// The header promises more PRG/CHR/trainer data than the image holds.
+    Truncated { expected: usize, found: usize },
+    /// The header declares no PRG ROM, so there is nothing to execute.
+    NoPrgRom,
+    /// The board the image was dumped from is not emulated.
+    UnsupportedMapper(u16),
+    /// A disk image without a single complete side.
+    NoDiskSides,
+    /// The Disk System BIOS has to be exactly 8KB.
+    BadBios(usize),
+    /// The writes saved next to the image could not be applied.
+    Patch(PatchError),
+    /// The IPS/BPS/UPS patch at the path could not be applied.
+    RomPatch(PathBuf, PatchError),
+    /// A Disk System image was loaded without the BIOS to boot it.
+    MissingBios,
+    /// The image is an NSF music rip, which cannot be played.
+    NsfNotSupported,
+    /// The zip archive could not be read.
+    Zip(ZipError),
+    /// The zip archive holds no .nes, .unf, .fds or .nsf file.
+    NoRomInArchive,
+    /// The zip archive holds several ROMs and none was picked by name.
+    AmbiguousArchive(Vec<String>),
+    /// The zip archive has no entry with the requested name.
+    NotInArchive(String),
+    /// The UNIF image could not be read or names an unknown board.
+    Unif(UnifError),
+}
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "could not read rom image: {e}"),
+            Self::MissingHeader => {
+                write!(f, "rom image is smaller than the iNES header")
+            }
+            Self::BadMagic(magic) => {
+                write!(f, "not an iNES image (magic bytes {magic:02X?})")
+            }
+            Self::Truncated { expected, found } => write!(
+                f,
+                "rom image is truncated: header expects {expected} bytes, \
+                 found {found}"
+            ),
+            Self::NoPrgRom => write!(f, "rom image declares no PRG ROM"),
+            Self::UnsupportedMapper(id) => {
+                write!(f, "mapper {id} is not supported")
+            }
+            Self::NoDiskSides => {
+                write!(f, "disk image does not hold a complete side")
+            }
+            Self::BadBios(size) => write!(
+                f,
+                "disk system BIOS must be {FDS_BIOS_SIZE} bytes, found {size}"
+            ),
+            Self::Patch(e) => write!(f, "could not apply saved writes: {e}"),
+            Self::RomPatch(path, e) => {
+                write!(f, "could not apply patch {}: {e}", path.display())
+            }
+            Self::MissingBios => {
+                write!(f, "disk system images need the BIOS to boot")
+            }
+            Self::NsfNotSupported => {
+                write!(f, "NSF music files cannot be played yet")
+            }
+            Self::Zip(e) => write!(f, "could not read zip archive: {e}"),
+            Self::NoRomInArchive => {
+                write!(f, "zip archive holds no .nes, .unf, .fds or .nsf file")
+            }
+            Self::AmbiguousArchive(names) => write!(
+                f,
+                "zip archive holds several roms, pick one of: {}",
+                names.join(", ")
+            ),
+            Self::NotInArchive(name) => {
+                write!(f, "zip archive has no entry called {name}")
+            }
+            Self::Unif(e) => write!(f, "{e}"),
+        }
+    }
+}
+impl std::error::Error for CartridgeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Patch(e) | Self::RomPatch(_, e) => Some(e),
+            Self::Zip(e) => Some(e),
+            Self::Unif(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<io::Error> for CartridgeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+impl From<PatchError> for CartridgeError {
+    fn from(e: PatchError) -> Self {
+        Self::Patch(e)
+    }
+}
+impl From<ZipError> for CartridgeError {
+    fn from(e: ZipError) -> Self {
+        Self::Zip(e)
+    }
+}
+impl From<UnifError> for CartridgeError {
+    fn from(e: UnifError) -> Self {
+        Self::Unif(e)
+    }
+}
+
+/// Everything besides the image that [`Cartrige::open`] may need.
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    /// Disk System BIOS, required for .fds images.
+    pub bios: Option<PathBuf>,
+    /// Patch to apply instead of the one found next to the image.
+    pub patch: Option<PathBuf>,
+    /// Entry to load from a zip archive holding several ROMs.
+    pub entry: Option<String>,
 }
 
 #[derive(Debug)]
 pub struct Cartrige {
     pub image_valid: bool,
     pub mirror: Mirroring,
-    mapper_id: u8,
-    prg_banks: u8,
-    chr_banks: u8,
+    format: HeaderFormat,
+    mapper_id: u16,
+    submapper: u8,
+    prg_banks: u16,
+    chr_banks: u16,
+    prg_ram_size: usize,
+    prg_nvram_size: usize,
+    chr_ram_size: usize,
+    chr_nvram_size: usize,
+    console: ConsoleType,
+    timing: TimingRegion,
+    expansion_device: u8,
+    battery: bool,
+    trainer: Option<Vec<u8>>,
     prg_mem: Vec<u8>,
+    prg_ram: Vec<u8>,
     chr_mem: Vec<u8>,
-    mapper: MapperData,
+    mapper: Box<dyn Mapper>,
+    path: Option<PathBuf>, // where the image was loaded from
+    original: Option<OriginalImage>,
+    saved: Option<Vec<u8>>, // battery data as last read from or written to disk
+    hash: RomHash,
+    title: Option<String>, // canonical title from the rom database
+    corrected: bool,       // the rom database overrode the header
+}
+
+/// The image as it was read, before the saved writes went on top. Disks and
+/// flash boards write to their own media, what changed is saved as a diff
+/// against this.
+#[derive(Debug, Clone)]
+struct OriginalImage {
+    image: Vec<u8>,
+    offset: usize, // start of the writable part (disk sides or PRG)
 }
 impl Cartrige {
     pub fn new() -> Self {
         Self {
             image_valid: false,
             mirror: Mirroring::HORIZONTAL,
-            mapper_id: 0u8,
-            prg_banks: 0u8,
-            chr_banks: 0u8,
+            format: HeaderFormat::INES,
+            mapper_id: 0u16,
+            submapper: 0u8,
+            prg_banks: 0u16,
+            chr_banks: 0u16,
+            prg_ram_size: 0,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            console: ConsoleType::NES,
+            timing: TimingRegion::NTSC,
+            expansion_device: 0u8,
+            battery: false,
+            trainer: None,
             prg_mem: vec![],
+            prg_ram: vec![],
             chr_mem: vec![],
-            mapper: MapperData::default(),
+            mapper: Box::new(M000::new(MapperData::default())),
+            path: None,
+            original: None,
+            saved: None,
+            hash: RomHash::of(&[]),
+            title: None,
+            corrected: false,
+        }
+    }
+
+    /// Loads whatever `path` holds: an iNES or UNIF image, a Disk System
+    /// image (booted with `options.bios`) or a zip archive with one inside.
+    /// The image is patched before it is parsed, by `options.patch` or the
+    /// patch found next to it ([`sibling_patch`]). Battery backed memory is
+    /// restored from the .sav file, disks and boards with PRG flash get
+    /// back what [`Cartrige::save_writes`] saved.
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        options: &LoadOptions,
+    ) -> Result<Self, CartridgeError> {
+        let path: &Path = path.as_ref();
+        let image: Vec<u8> = read_image(path, options)?;
+        if is_fds_image(&image) {
+            let bios_path: &Path =
+                options.bios.as_deref().ok_or(CartridgeError::MissingBios)?;
+            let bios: Vec<u8> = fs::read(bios_path)?;
+            Self::fds_at(path, image, &bios)
+        } else if unif::is_unif(&image) {
+            let unif: UnifImage = UnifImage::parse(&image)?;
+            let mut cart: Self = Self::ines_at(path, unif.to_nes2()?)?;
+            cart.title = cart.title.or(unif.name);
+            Ok(cart)
+        } else {
+            Self::ines_at(path, image)
+        }
+    }
+
+    /// Reads an iNES image from disk and builds a [`Cartrige`] out of it,
+    /// see [`Cartrige::open`].
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, CartridgeError> {
+        Self::open(path, &LoadOptions::default())
+    }
+
+    /// Reads a Famicom Disk System image and the BIOS to boot it with, see
+    /// [`Cartrige::open`].
+    pub fn from_fds_file<P: AsRef<Path>, B: AsRef<Path>>(
+        path: P,
+        bios_path: B,
+    ) -> Result<Self, CartridgeError> {
+        let options = LoadOptions {
+            bios: Some(bios_path.as_ref().to_path_buf()),
+            ..LoadOptions::default()
+        };
+        Self::open(path, &options)
+    }
+
+    /// Builds the cartridge for an iNES image read from `path`.
+    fn ines_at(path: &Path, image: Vec<u8>) -> Result<Self, CartridgeError> {
+        let mut cart: Self = Self::from_bytes(&image)?;
+        if cart.mapper.prg_flash() {
+            let offset: usize = INES_HEADER_SIZE
+                + cart.trainer.as_ref().map_or(0, |trainer| trainer.len());
+            let end: usize = offset + cart.prg_mem.len();
+            let written: Vec<u8> = read_saved_writes(path, &image)?;
+            if let Some(prg) = written.get(offset..end) {
+                cart.prg_mem.copy_from_slice(prg);
+            }
+            cart.original = Some(OriginalImage { image, offset });
+        }
+        cart.path = Some(path.to_path_buf());
+        cart.load_save()?;
+        Ok(cart)
+    }
+
+    /// Builds the cartridge for a Disk System image read from `path`.
+    fn fds_at(
+        path: &Path,
+        image: Vec<u8>,
+        bios: &[u8],
+    ) -> Result<Self, CartridgeError> {
+        let written: Vec<u8> = read_saved_writes(path, &image)?;
+        let mut cart: Self = Self::from_fds(&written, bios)?;
+        if let Some(original) = &mut cart.original {
+            original.image = image;
+        }
+        cart.path = Some(path.to_path_buf());
+        Ok(cart)
+    }
+
+    /// Builds the cartridge for an in memory UNIF image, through the NES 2.0
+    /// image its board translates to. Titles from the rom database win over
+    /// the image's NAME chunk.
+    pub fn from_unif(image: &[u8]) -> Result<Self, CartridgeError> {
+        let unif: UnifImage = UnifImage::parse(image)?;
+        let mut cart: Self = Self::from_bytes(&unif.to_nes2()?)?;
+        cart.title = cart.title.or(unif.name);
+        Ok(cart)
+    }
+
+    /// Builds the Disk System RAM adapter (mapper 20) around an in memory
+    /// .fds image, with or without its header.
+    ///
+    /// ```no_run
+    /// Header (16 bytes, optional)
+    /// 0-3   "FDS" 0x1A
+    /// 4     number of disk sides
+    /// 5-15  padding
+    /// Disk sides (65500 bytes each), starting with "\x01*NINTENDO-HVC*"
+    /// ```
+    pub fn from_fds(image: &[u8], bios: &[u8]) -> Result<Self, CartridgeError> {
+        if bios.len() != FDS_BIOS_SIZE {
+            return Err(CartridgeError::BadBios(bios.len()));
+        }
+        let (offset, count): (usize, usize) =
+            match image.starts_with(&FDS_HEADER_MAGIC) {
+                true if image.len() >= FDS_HEADER_SIZE => (
+                    FDS_HEADER_SIZE,
+                    (image[4] as usize)
+                        .min((image.len() - FDS_HEADER_SIZE) / FDS_SIDE_SIZE),
+                ),
+                true => return Err(CartridgeError::MissingHeader),
+                false => (0, image.len() / FDS_SIDE_SIZE),
+            };
+        let sides: Vec<&[u8]> = image[offset..]
+            .chunks_exact(FDS_SIDE_SIZE)
+            .take(count)
+            .collect();
+        if sides.is_empty() || !sides[0].starts_with(FDS_SIDE_MAGIC) {
+            return Err(CartridgeError::NoDiskSides);
+        }
+
+        let mapper_data = MapperData {
+            prg_bank: 0,
+            chr_bank: 0,
+            prg_ram_size: KB(32),
+            ..MapperData::default()
+        };
+        Ok(Self {
+            image_valid: true,
+            mapper_id: 20,
+            prg_ram_size: KB(32),
+            chr_ram_size: CHR_BANK_SIZE,
+            prg_mem: bios.to_vec(),
+            prg_ram: vec![0u8; KB(32)],
+            chr_mem: vec![0u8; CHR_BANK_SIZE],
+            mapper: Box::new(M020::new(mapper_data, &sides)),
+            original: Some(OriginalImage {
+                image: image.to_vec(),
+                offset,
+            }),
+            hash: RomHash::of(&image[offset..]),
+            ..Self::new()
+        })
+    }
+
+    /// Parses an in memory iNES or NES 2.0 image.
+    ///
+    /// ```no_run
+    /// Header (16 bytes)
+    /// 0-3   "NES" 0x1A
+    /// 4     PRG ROM size LSB in 16KB units
+    /// 5     CHR ROM size LSB in 8KB units (0 means the board uses CHR RAM)
+    /// 6     flags 6: mapper D0..D3, four screen, trainer, battery, mirroring
+    /// 7     flags 7: mapper D4..D7, NES 2.0 identifier, console type
+    /// iNES:
+    /// 8     PRG RAM size in 8KB units (0 infers 8KB)
+    /// 9     bit 0: TV system (0 NTSC, 1 PAL)
+    /// 10-15 padding
+    /// NES 2.0:
+    /// 8     mapper D8..D11, submapper
+    /// 9     PRG/CHR ROM size MSB nibbles
+    /// 10    PRG NVRAM / PRG RAM shift counts (64 << n bytes)
+    /// 11    CHR NVRAM / CHR RAM shift counts (64 << n bytes)
+    /// 12    CPU/PPU timing
+    /// 13    Vs. System PPU/hardware type or extended console type
+    /// 14    miscellaneous ROM count
+    /// 15    default expansion device
+    /// Trainer, if present (512 bytes)
+    /// PRG ROM data
+    /// CHR ROM data, if present
+    /// ```
+    ///
+    /// The PRG and CHR data are looked up in the embedded
+    /// [`RomDatabase`], known dumps get their mapper, submapper, mirroring,
+    /// battery and region from there instead of the header.
+    pub fn from_bytes(image: &[u8]) -> Result<Self, CartridgeError> {
+        Self::from_bytes_with_database(image, RomDatabase::embedded())
+    }
+
+    /// [`Cartrige::from_bytes`] with the header corrections taken from
+    /// `database`.
+    pub fn from_bytes_with_database(
+        image: &[u8],
+        database: &RomDatabase,
+    ) -> Result<Self, CartridgeError> {
+        if image.len() < INES_HEADER_SIZE {
+            return Err(CartridgeError::MissingHeader);
+        }
+        let mut header: [u8; INES_HEADER_SIZE] = [0; INES_HEADER_SIZE];
+        header.copy_from_slice(&image[..INES_HEADER_SIZE]);
+        if header.starts_with(&NSF_MAGIC) {
+            return Err(CartridgeError::NsfNotSupported);
+        }
+        if header[0..4] != INES_MAGIC {
+            let mut magic = [0u8; 4];
+            magic.copy_from_slice(&header[0..4]);
+            return Err(CartridgeError::BadMagic(magic));
+        }
+
+        let format: HeaderFormat = if header[7] & 0x0C == 0x08 {
+            HeaderFormat::NES2
+        } else {
+            HeaderFormat::INES
+        };
+
+        // Old dumping tools ("DiskDude!") scribbled text over bytes 7-15,
+        // which turns into garbage mapper numbers, RAM sizes and timing. If
+        // the padding is dirty on a plain iNES header only trust bytes 0-6.
+        if format == HeaderFormat::INES
+            && header[12..16].iter().any(|b| *b != 0)
+        {
+            header[7..16].fill(0);
+        }
+        let flags6: u8 = header[6];
+        let flags7: u8 = header[7];
+
+        let mut mirror: Mirroring = if flags6 & 0x08 != 0 {
+            Mirroring::FOUR_SCREEN
+        } else if flags6 & 0x01 != 0 {
+            Mirroring::VERTICAL
+        } else {
+            Mirroring::HORIZONTAL
+        };
+        let mut battery: bool = flags6 & 0x02 != 0;
+        let has_trainer: bool = flags6 & 0x04 != 0;
+        let mut mapper_id: u16 = ((flags7 & 0xF0) | (flags6 >> 4)) as u16;
+
+        let console: ConsoleType = match flags7 & 0x03 {
+            0 => ConsoleType::NES,
+            1 => ConsoleType::VS_SYSTEM {
+                ppu_type: header[13] & 0x0F,
+                hardware_type: header[13] >> 4,
+            },
+            2 => ConsoleType::PLAYCHOICE_10,
+            _ if format == HeaderFormat::NES2 => {
+                ConsoleType::EXTENDED(header[13] & 0x0F)
+            }
+            _ => ConsoleType::NES,
+        };
+
+        let (prg_size, chr_size): (usize, usize) = match format {
+            HeaderFormat::NES2 => {
+                mapper_id |= ((header[8] & 0x0F) as u16) << 8;
+                (
+                    nes2_rom_size(header[4], header[9] & 0x0F, PRG_BANK_SIZE),
+                    nes2_rom_size(header[5], header[9] >> 4, CHR_BANK_SIZE),
+                )
+            }
+            HeaderFormat::INES => (
+                header[4] as usize * PRG_BANK_SIZE,
+                header[5] as usize * CHR_BANK_SIZE,
+            ),
+        };
+        if prg_size == 0 {
+            return Err(CartridgeError::NoPrgRom);
+        }
+
+        let trainer_size: usize = if has_trainer { TRAINER_SIZE } else { 0 };
+        let expected: usize = (INES_HEADER_SIZE + trainer_size)
+            .saturating_add(prg_size)
+            .saturating_add(chr_size);
+        if image.len() < expected {
+            return Err(CartridgeError::Truncated {
+                expected,
+                found: image.len(),
+            });
+        }
+
+        let hash: RomHash =
+            RomHash::of(&image[INES_HEADER_SIZE + trainer_size..expected]);
+        let entry: Option<&RomEntry> = database.find(&hash);
+        let mut submapper: u8 = match format {
+            HeaderFormat::NES2 => header[8] >> 4,
+            HeaderFormat::INES => 0,
+        };
+        let mut corrected: bool = false;
+        let mut battery_corrected: bool = false;
+        if let Some(entry) = entry {
+            corrected |= correct(&mut mapper_id, entry.mapper);
+            corrected |= correct(&mut submapper, entry.submapper);
+            battery_corrected = correct(&mut battery, entry.battery);
+            corrected |= battery_corrected;
+        }
+
+        let prg_ram_size: usize;
+        let prg_nvram_size: usize;
+        let chr_ram_size: usize;
+        let chr_nvram_size: usize;
+        let mut timing: TimingRegion;
+        let expansion_device: u8;
+        match format {
+            HeaderFormat::NES2 => {
+                // NES 2.0 sizes the NVRAM separately, a battery bit the
+                // database corrected moves the work RAM between the pools
+                let work_ram: usize = shift_size(header[10] & 0x0F);
+                let nvram: usize = shift_size(header[10] >> 4);
+                (prg_ram_size, prg_nvram_size) =
+                    match (battery_corrected, battery) {
+                        (false, _) => (work_ram, nvram),
+                        (true, true) => (0, work_ram + nvram),
+                        (true, false) => (work_ram + nvram, 0),
+                    };
+                chr_ram_size = shift_size(header[11] & 0x0F);
+                chr_nvram_size = shift_size(header[11] >> 4);
+                timing = match header[12] & 0x03 {
+                    0 => TimingRegion::NTSC,
+                    1 => TimingRegion::PAL,
+                    2 => TimingRegion::MULTI_REGION,
+                    _ => TimingRegion::DENDY,
+                };
+                expansion_device = header[15] & 0x3F;
+            }
+            HeaderFormat::INES => {
+                // iNES only knows about one pool of work RAM, the battery bit
+                // decides whether it survives a power cycle
+                let work_ram: usize = match mapper_id {
+                    30 => 0, // UNROM 512's battery bit is about its flash
+                    _ => KB(8) * (header[8].max(1) as usize),
+                };
+                (prg_ram_size, prg_nvram_size) = match battery {
+                    true => (0, work_ram),
+                    false => (work_ram, 0),
+                };
+                chr_ram_size = match (chr_size, mapper_id) {
+                    (0, 30) => KB(32), // UNROM 512 always carries 32KB
+                    (0, _) => CHR_BANK_SIZE,
+                    _ => 0,
+                };
+                chr_nvram_size = 0;
+                timing = match header[9] & 0x01 {
+                    0 => TimingRegion::NTSC,
+                    _ => TimingRegion::PAL,
+                };
+                expansion_device = 0;
+            }
+        }
+        // UNROM 512 reads the four screen bit without the mirroring bit as
+        // one-screen mirroring picked by the mapper
+        if mapper_id == 30 && flags6 & 0x09 == 0x08 {
+            mirror = Mirroring::ONESCREAN_LO;
+        }
+        if let Some(entry) = entry {
+            corrected |= correct(&mut mirror, entry.mirror);
+            corrected |= correct(&mut timing, entry.timing);
+        }
+
+        let mut offset: usize = INES_HEADER_SIZE;
+        let trainer: Option<Vec<u8>> = if has_trainer {
+            offset += TRAINER_SIZE;
+            Some(image[INES_HEADER_SIZE..offset].to_vec())
+        } else {
+            None
+        };
+
+        let prg_mem: Vec<u8> = image[offset..offset + prg_size].to_vec();
+        offset += prg_size;
+
+        // no CHR ROM means the board carries CHR RAM instead
+        let chr_mem: Vec<u8> = if chr_size == 0 {
+            vec![0u8; chr_ram_size + chr_nvram_size]
+        } else {
+            image[offset..offset + chr_size].to_vec()
+        };
+
+        let prg_banks: u16 = prg_size.div_ceil(PRG_BANK_SIZE) as u16;
+        let chr_banks: u16 = chr_size.div_ceil(CHR_BANK_SIZE) as u16;
+        let prg_ram: Vec<u8> = vec![0u8; prg_ram_size + prg_nvram_size];
+
+        let mapper_data = MapperData {
+            prg_bank: prg_banks,
+            chr_bank: chr_banks,
+            submapper,
+            mirror,
+            prg_ram_size: prg_ram.len(),
+            battery,
+        };
+        let mapper: Box<dyn Mapper> =
+            mappers::new_mapper(mapper_id, mapper_data)
+                .ok_or(CartridgeError::UnsupportedMapper(mapper_id))?;
+
+        Ok(Self {
+            image_valid: true,
+            mirror,
+            format,
+            mapper_id,
+            submapper,
+            prg_banks,
+            chr_banks,
+            prg_ram_size,
+            prg_nvram_size,
+            chr_ram_size,
+            chr_nvram_size,
+            console,
+            timing,
+            expansion_device,
+            battery,
+            trainer,
+            prg_mem,
+            prg_ram,
+            chr_mem,
+            mapper,
+            path: None,
+            original: None,
+            saved: None,
+            hash,
+            title: entry.map(|entry| entry.title.clone()),
+            corrected,
+        })
+    }
+
+    pub const fn format(&self) -> HeaderFormat {
+        self.format
+    }
+
+    pub const fn mapper_id(&self) -> u16 {
+        self.mapper_id
+    }
+
+    pub const fn submapper(&self) -> u8 {
+        self.submapper
+    }
+
+    pub const fn prg_banks(&self) -> u16 {
+        self.prg_banks
+    }
+
+    pub const fn chr_banks(&self) -> u16 {
+        self.chr_banks
+    }
+
+    /// Volatile PRG RAM in bytes, usually mapped at $6000-$7FFF
+    pub const fn prg_ram_size(&self) -> usize {
+        self.prg_ram_size
+    }
+
+    /// Battery backed PRG RAM (or EEPROM) in bytes
+    pub const fn prg_nvram_size(&self) -> usize {
+        self.prg_nvram_size
+    }
+
+    /// Volatile CHR RAM in bytes
+    pub const fn chr_ram_size(&self) -> usize {
+        self.chr_ram_size
+    }
+
+    /// Battery backed CHR RAM in bytes
+    pub const fn chr_nvram_size(&self) -> usize {
+        self.chr_nvram_size
+    }
+
+    pub const fn console(&self) -> ConsoleType {
+        self.console
+    }
+
+    pub const fn timing(&self) -> TimingRegion {
+        self.timing
+    }
+
+    /// NES 2.0 default expansion device, 0 when unspecified
+    pub const fn expansion_device(&self) -> u8 {
+        self.expansion_device
+    }
+
+    pub const fn battery(&self) -> bool {
+        self.battery
+    }
+
+    pub fn trainer(&self) -> Option<&[u8]> {
+        self.trainer.as_deref()
+    }
+
+    pub fn prg_mem(&self) -> &[u8] {
+        &self.prg_mem
+    }
+
+    pub fn chr_mem(&self) -> &[u8] {
+        &self.chr_mem
+    }
+
+    /// Mirroring currently in effect, mappers with a mirroring register
+    /// override what the header says.
+    pub fn mirror(&self) -> Mirroring {
+        self.mapper.mirroring().unwrap_or(self.mirror)
+    }
+
+    /// CRC-32 and SHA-1 of the PRG and CHR ROM (of the disk sides for Disk
+    /// System images).
+    pub const fn hash(&self) -> RomHash {
+        self.hash
+    }
+
+    /// Canonical title from the rom database, `None` for unknown dumps.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Everything known about the loaded image, ready to be printed.
+    pub fn rom_info(&self) -> RomInfo {
+        RomInfo {
+            title: self.title.clone(),
+            hash: self.hash,
+            format: self.format,
+            mapper: self.mapper_id,
+            submapper: self.submapper,
+            mirror: self.mirror,
+            battery: self.battery,
+            timing: self.timing,
+            prg_rom_size: self.prg_banks as usize * PRG_BANK_SIZE,
+            chr_rom_size: self.chr_banks as usize * CHR_BANK_SIZE,
+            corrected: self.corrected,
+        }
+    }
+
+    pub fn prg_ram(&self) -> &[u8] {
+        &self.prg_ram
+    }
+
+    /// File the image was loaded from, if it came from disk.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Number of disk sides, 0 for anything but a Disk System image.
+    pub fn disk_sides(&self) -> usize {
+        self.mapper.disk_sides()
+    }
+
+    /// Side currently in the drive, `None` while it is empty.
+    pub fn disk_side(&self) -> Option<usize> {
+        self.mapper.disk_side()
+    }
+
+    /// Ejects the disk and, unless `side` is `None`, puts the given side in
+    /// the drive. The drive reads as empty for a moment so the BIOS notices
+    /// the swap.
+    pub fn insert_disk(&mut self, side: Option<usize>) {
+        self.mapper.insert_disk(side);
+    }
+
+    /// The disk image including everything written to it.
+    pub fn disk_image(&self) -> Option<Vec<u8>> {
+        let sides: Vec<u8> = self.mapper.disk_image()?;
+        self.written_image(&sides)
+    }
+
+    /// The original image with its writable part replaced.
+    fn written_image(&self, contents: &[u8]) -> Option<Vec<u8>> {
+        let original: &OriginalImage = self.original.as_ref()?;
+        let end: usize = original.offset + contents.len();
+        let mut image: Vec<u8> = original.image.clone();
+        image.resize(image.len().max(end), 0x00);
+        image[original.offset..end].copy_from_slice(contents);
+        Some(image)
+    }
+
+    /// Saves what the game wrote to its disk or PRG flash as an IPS patch
+    /// next to the image ("game.fds.ips", "game.nes.ips"), the image itself
+    /// is never touched. Does nothing for other cartridges or images that
+    /// were not loaded from a file.
+    pub fn save_writes(&self) -> io::Result<()> {
+        let image: Option<Vec<u8>> = match self.mapper.prg_flash() {
+            true => self.written_image(&self.prg_mem),
+            false => self.disk_image(),
+        };
+        let (Some(path), Some(original), Some(image)) =
+            (&self.path, &self.original, image)
+        else {
+            return Ok(());
+        };
+        let save: PathBuf = write_save_path(path);
+        if image == original.image && !save.exists() {
+            return Ok(());
+        }
+        write_atomic(&save, &patch::create_ips(&original.image, &image))
+    }
+
+    /// The .sav file next to the image, "game.nes" saves to "game.sav".
+    pub fn save_path(&self) -> Option<PathBuf> {
+        self.path.as_ref().map(|path| path.with_extension("sav"))
+    }
+
+    /// Restores the battery backed memory from the .sav file, `false` when
+    /// there is nothing to restore.
+    pub fn load_save(&mut self) -> io::Result<bool> {
+        let Some(path) = self.save_path() else {
+            return Ok(false);
+        };
+        if self.battery_data().is_none() {
+            return Ok(false);
+        }
+        match fs::read(path) {
+            Ok(data) => {
+                self.load_battery_data(&data);
+                self.saved = self.battery_data();
+                Ok(true)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the battery backed memory to the .sav file if it changed
+    /// since it was loaded or last flushed, `true` when it did.
+    pub fn flush_save(&mut self) -> io::Result<bool> {
+        let (Some(path), Some(data)) = (self.save_path(), self.battery_data())
+        else {
+            return Ok(false);
+        };
+        if self.saved.as_ref() == Some(&data) {
+            return Ok(false);
+        }
+        write_atomic(&path, &data)?;
+        self.saved = Some(data);
+        Ok(true)
+    }
+
+    /// Everything the battery keeps alive, the PRG NVRAM followed by the
+    /// mapper's own battery backed memory. `None` for boards without one.
+    pub fn battery_data(&self) -> Option<Vec<u8>> {
+        let internal: Option<&[u8]> = self.mapper.battery_ram();
+        if self.prg_nvram_size == 0 && internal.is_none() {
+            return None;
+        }
+        let mut data: Vec<u8> = vec![];
+        if self.prg_nvram_size > 0 {
+            data.extend_from_slice(&self.prg_ram);
+        }
+        data.extend_from_slice(internal.unwrap_or_default());
+        Some(data)
+    }
+
+    /// Restores memory saved from [`Cartrige::battery_data`], short data
+    /// only fills what it covers.
+    pub fn load_battery_data(&mut self, data: &[u8]) {
+        let mut data: &[u8] = data;
+        if self.prg_nvram_size > 0 {
+            let len: usize = data.len().min(self.prg_ram.len());
+            self.prg_ram[..len].copy_from_slice(&data[..len]);
+            data = &data[len..];
+        }
+        if !data.is_empty() {
+            self.mapper.load_battery_ram(data);
+        }
+    }
+
+    /// Reads from cartridge space on the CPU bus. Returns `false` when the
+    /// mapper does not claim the address so the bus can fall through.
+    pub fn cpu_read(&mut self, addr: u16, data: &mut u8) -> bool {
+        match self.mapper.cpu_read(addr) {
+            Mapped::Prg(offset) => *data = wrapped(&self.prg_mem, offset),
+            Mapped::PrgRam(offset) => *data = wrapped(&self.prg_ram, offset),
+            Mapped::Chr(offset) => *data = wrapped(&self.chr_mem, offset),
+            Mapped::Data(value) => *data = value,
+            Mapped::Ciram(_) | Mapped::Unmapped => return false,
+        }
+        true
+    }
+
+    pub fn cpu_write(&mut self, addr: u16, data: u8) -> bool {
+        let mut data: u8 = data;
+        if self.mapper.bus_conflicts() {
+            if let Mapped::Prg(offset) = self.mapper.cpu_read(addr) {
+                data &= wrapped(&self.prg_mem, offset);
+            }
+        }
+        match self.mapper.cpu_write(addr, data) {
+            // programming flash can only clear bits
+            Mapped::Prg(offset) if self.mapper.prg_flash() => {
+                let data: u8 = data & wrapped(&self.prg_mem, offset);
+                wrapped_write(&mut self.prg_mem, offset, data)
+            }
+            Mapped::Prg(offset) => {
+                wrapped_write(&mut self.prg_mem, offset, data)
+            }
+            Mapped::PrgRam(offset) => {
+                wrapped_write(&mut self.prg_ram, offset, data)
+            }
+            Mapped::Chr(offset) => {
+                wrapped_write(&mut self.chr_mem, offset, data)
+            }
+            Mapped::Data(_) => (),
+            Mapped::Ciram(_) | Mapped::Unmapped => return false,
+        }
+        if let Some(erased) = self.mapper.prg_erased() {
+            let end: usize = erased.end.min(self.prg_mem.len());
+            self.prg_mem[erased.start.min(end)..end].fill(0xFF);
+        }
+        true
+    }
+
+    /// Reads from the PPU bus. Anything but [`PpuAccess::CARTRIDGE`] leaves
+    /// `data` alone and tells the PPU which of its own memories answers.
+    pub fn ppu_read(&mut self, addr: u16, data: &mut u8) -> PpuAccess {
+        match self.mapper.ppu_read(addr) {
+            Mapped::Chr(offset) => *data = wrapped(&self.chr_mem, offset),
+            Mapped::Prg(offset) => *data = wrapped(&self.prg_mem, offset),
+            Mapped::PrgRam(offset) => *data = wrapped(&self.prg_ram, offset),
+            Mapped::Data(value) => *data = value,
+            Mapped::Ciram(offset) => return PpuAccess::CIRAM(offset),
+            Mapped::Unmapped => return PpuAccess::CONSOLE,
+        }
+        PpuAccess::CARTRIDGE
+    }
+
+    pub fn ppu_write(&mut self, addr: u16, data: u8) -> PpuAccess {
+        match self.mapper.ppu_write(addr, data) {
+            Mapped::Chr(offset) => {
+                wrapped_write(&mut self.chr_mem, offset, data)
+            }
+            Mapped::Prg(offset) => {
+                wrapped_write(&mut self.prg_mem, offset, data)
+            }
+            Mapped::PrgRam(offset) => {
+                wrapped_write(&mut self.prg_ram, offset, data)
+            }
+            Mapped::Data(_) => (),
+            Mapped::Ciram(offset) => return PpuAccess::CIRAM(offset),
+            Mapped::Unmapped => return PpuAccess::CONSOLE,
+        }
+        PpuAccess::CARTRIDGE
+    }
+
+    /// Level of the cartridge IRQ line.
+    pub fn irq_state(&self) -> bool {
+        self.mapper.irq_state()
+    }
+
+    pub fn cpu_clock(&mut self) {
+        self.mapper.cpu_clock();
+    }
+
+    pub fn scanline(&mut self) {
+        self.mapper.scanline();
+    }
+
+    pub fn ppu_address(&mut self, addr: u16) {
+        self.mapper.ppu_address(addr);
+    }
+
+    pub fn ppu_register_write(&mut self, addr: u16, data: u8) {
+        self.mapper.ppu_register_write(addr, data);
+    }
+
+    /// Expansion audio produced by the board, see [`Mapper::audio_output`].
+    pub fn audio_output(&self) -> f32 {
+        self.mapper.audio_output()
+    }
+
+    pub fn reset(&mut self) {
+        self.mapper.reset();
+    }
+}
+
+/// Whether an image looks like a Disk System image rather than an iNES
+/// one, either headered or starting straight with the first side.
+pub fn is_fds_image(image: &[u8]) -> bool {
+    image.starts_with(&FDS_HEADER_MAGIC) || image.starts_with(FDS_SIDE_MAGIC)
+}
+
+/// Where the writes to a disk or flash image are kept, the image name with
+/// ".ips" tacked on.
+pub fn write_save_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".ips");
+    PathBuf::from(name)
+}
+
+/// The patch that goes with the image at `path`: the same name with an
+/// .ips, .bps or .ups extension (game.nes plays with game.bps). The first
+/// one found wins.
+pub fn sibling_patch(path: &Path) -> Option<PathBuf> {
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| path.with_extension(extension))
+        // an image without an extension keeps its saved writes there
+        .filter(|candidate| *candidate != write_save_path(path))
+        .find(|candidate| candidate.is_file())
+}
+
+/// Applies an IPS, BPS or UPS patch to a rom image. BPS and UPS patches
+/// made for the rom without its iNES header are noticed by their source
+/// checksum and applied to the data behind the header.
+pub fn apply_patch(image: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    match patch::apply(image, patch) {
+        Err(e @ PatchError::Checksum { which, .. })
+            if which == patch::Checksum::Source
+                && image.len() >= INES_HEADER_SIZE
+                && image.starts_with(&INES_MAGIC) =>
+        {
+            let (header, rom) = image.split_at(INES_HEADER_SIZE);
+            match patch::apply(rom, patch) {
+                Ok(patched) => Ok([header, &patched].concat()),
+                Err(_) => Err(e),
+            }
+        }
+        result => result,
+    }
+}
+
+/// Picks the ROM out of a zip archive: the entry called `name` (with or
+/// without its directories), otherwise the only .nes, .unf, .fds or .nsf
+/// inside.
+pub fn rom_from_zip(
+    archive: &[u8],
+    name: Option<&str>,
+) -> Result<Vec<u8>, CartridgeError> {
+    let archive: ZipArchive = ZipArchive::new(archive)?;
+    let is_rom = |entry: &&ZipEntry| {
+        let extension: &str = entry.name.rsplit('.').next().unwrap_or("");
+        !entry.is_dir()
+            && entry.name.contains('.')
+            && ROM_EXTENSIONS
+                .iter()
+                .any(|rom| rom.eq_ignore_ascii_case(extension))
+    };
+    let entry: &ZipEntry = match name {
+        Some(name) => archive
+            .entries()
+            .iter()
+            .find(|entry| entry.name == name || entry.file_name() == name)
+            .ok_or_else(|| CartridgeError::NotInArchive(name.to_string()))?,
+        None => {
+            let roms: Vec<&ZipEntry> =
+                archive.entries().iter().filter(is_rom).collect();
+            match roms[..] {
+                [] => return Err(CartridgeError::NoRomInArchive),
+                [entry] => entry,
+                _ => {
+                    return Err(CartridgeError::AmbiguousArchive(
+                        roms.iter().map(|entry| entry.name.clone()).collect(),
+                    ))
+                }
+            }
+        }
+    };
+    Ok(archive.read(entry)?)
+}
+
+/// Reads the image at `path`, out of the archive if it is a zip, with the
+/// patch from `options` applied, or the patch found next to the image when
+/// none is given.
+fn read_image(
+    path: &Path,
+    options: &LoadOptions,
+) -> Result<Vec<u8>, CartridgeError> {
+    let mut image: Vec<u8> = fs::read(path)?;
+    if zip::is_zip(&image) {
+        image = rom_from_zip(&image, options.entry.as_deref())?;
+    }
+    let Some(patch_path) =
+        options.patch.clone().or_else(|| sibling_patch(path))
+    else {
+        return Ok(image);
+    };
+    let patch: Vec<u8> = fs::read(&patch_path)?;
+    apply_patch(&image, &patch)
+        .map_err(|e| CartridgeError::RomPatch(patch_path, e))
+}
+
+/// Replaces `path` with `data` without ever leaving a partial file behind:
+/// the data goes to a temporary file first, which then takes its place.
+fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(".tmp");
+    let temp: PathBuf = PathBuf::from(temp);
+    let mut file: fs::File = fs::File::create(&temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    fs::rename(&temp, path)
+}
+
+/// The image with the writes saved next to it applied, a copy of it when
+/// nothing was saved yet.
+fn read_saved_writes(
+    path: &Path,
+    image: &[u8],
+) -> Result<Vec<u8>, CartridgeError> {
+    match fs::read(write_save_path(path)) {
+        Ok(diff) => Ok(patch::apply_ips(image, &diff)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(image.to_vec()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads a mapped offset, offsets past the end wrap around the memory the
+/// same way the unconnected high address lines would on a real board.
+#[inline]
+fn wrapped(mem: &[u8], offset: usize) -> u8 {
+    match mem.len() {
+        0 => 0x00,
+        len => mem[offset % len],
+    }
+}
+
+#[inline]
+fn wrapped_write(mem: &mut [u8], offset: usize, data: u8) {
+    let len: usize = mem.len();
+    if len != 0 {
+        mem[offset % len] = data;
+    }
+}
+
+/// NES 2.0 RAM sizes are stored as shift counts: 0 means none, otherwise
+/// the size is `64 << n` bytes.
+#[inline]
+const fn shift_size(count: u8) -> usize {
+    match count {
+        0 => 0,
+        n => 64usize << n,
+    }
+}
+
+/// NES 2.0 ROM sizes combine the iNES size byte with a MSB nibble from
+/// byte 9. A MSB nibble of $F switches to exponent-multiplier notation
+/// where the LSB byte is `EEEEEEMM` and the size is `2^E * (MM * 2 + 1)`.
+#[inline]
+fn nes2_rom_size(lsb: u8, msb: u8, unit: usize) -> usize {
+    if msb == 0x0F {
+        let exponent: u32 = (lsb >> 2) as u32;
+        let multiplier: usize = (lsb & 0x03) as usize * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .and_then(|size| size.checked_mul(multiplier))
+            .unwrap_or(usize::MAX)
+    } else {
+        (((msb as usize) << 8) | lsb as usize) * unit
+    }
+}
+
+/// Replaces a header field with the rom database's value, `true` when the
+/// two disagreed.
+#[inline]
+fn correct<T: Copy + PartialEq>(field: &mut T, known: Option<T>) -> bool {
+    match known {
+        Some(value) if value != *field => {
+            *field = value;
+            true
         }
+        _ => false,
     }
 }
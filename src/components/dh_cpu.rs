@@ -7,6 +7,26 @@ use super::bus::Bus;
 use super::types::{AddrModeMneumonic, CpuFlags, M6502AddrModes, M6502Opcodes};
 use super::{HIGH_BYTE, LOOKUP_TABLE, LOW_BYTE, TOP_BIT_THRESH};
 
+const STACK_PAGE: u16 = 0x0100;
+const NMI_VECTOR: u16 = 0xFFFA;
+const RESET_VECTOR: u16 = 0xFFFC;
+const IRQ_VECTOR: u16 = 0xFFFE; // shared with BRK
+/// Pushing PC and status and fetching the vector.
+const INTERRUPT_CYCLES: u8 = 7;
+
+/// The unstable XAA and LAX #imm OR the accumulator with a constant that
+/// depends on the chip and its temperature before the AND. $EE is what
+/// most chips settle on.
+const UNSTABLE_MAGIC: u8 = 0xEE;
+
+/// An interrupt the cpu has committed to taking once the current
+/// instruction is done.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Interrupt {
+    Nmi,
+    Irq,
+}
+
 /// # Mos 6502AD
 /// ## Fields
 /// cpu Core registers, exposed as public here for ease of access from external examinors
@@ -25,6 +45,13 @@ use super::{HIGH_BYTE, LOOKUP_TABLE, LOW_BYTE, TOP_BIT_THRESH};
 /// * `opcode` - Is the instruction byte
 /// * `cycles` - Counts how many cycles the instruction has remaining
 /// * `clock_count` - A global accumulation of the number of clocks
+///
+/// ## Interrupt inputs
+/// * `irq_line` - Level of the /IRQ pin, wired-or of the cartridge and APU
+/// * `nmi_line` - Level of the /NMI pin, driven by the PPU
+/// * `nmi_pending` - A falling edge was seen on /NMI and not serviced yet
+/// * `irq_inhibit` - The I flag as the interrupt poll sees it
+/// * `poll` - Interrupt to take after the current instruction
 #[derive(Debug)]
 pub struct CPU {
     // cpu Core registers, exposed as public here for ease of access from external
@@ -44,6 +71,13 @@ pub struct CPU {
     opcode: u8,  // Is the instruction byte
     cycles: u8,  // Counts how many cycles the instruction has remaining
     _clock_count: u32, // A global accumulation of the number of clocks
+
+    // Interrupt inputs
+    irq_line: bool,          // /IRQ is asserted (level triggered)
+    nmi_line: bool,          // /NMI is asserted
+    nmi_pending: bool,       // /NMI went active (edge triggered)
+    irq_inhibit: bool,       // I flag seen by the interrupt poll
+    poll: Option<Interrupt>, // result of the last interrupt poll
 }
 
 impl CPU {
@@ -63,6 +97,11 @@ impl CPU {
             opcode: 0x00,
             cycles: 0,
             _clock_count: 0,
+            irq_line: false,
+            nmi_line: false,
+            nmi_pending: false,
+            irq_inhibit: false,
+            poll: None,
         }
     }
 
@@ -106,7 +145,7 @@ impl CPU {
     /// assert_eq!(cpu.fetched, 0xAB);
     /// ```
     #[inline]
-    pub fn fetch(&mut self, bus: &Bus) -> u8 {
+    pub fn fetch(&mut self, bus: &mut Bus) -> u8 {
         let instruction: &CpuInstruction = &LOOKUP_TABLE[self.opcode as usize];
         match instruction.mneumonic.am_name == AddrModeMneumonic::IMP {
             true => (),
@@ -149,7 +188,7 @@ impl CPU {
         if conditional_set {
             self.status |= f as u8;
         } else {
-            self.status |= !(f as u8) // flip da bits
+            self.status &= !(f as u8) // flip da bits
         }
     }
 
@@ -188,24 +227,204 @@ impl CPU {
     /// assert_eq!(cpu.cycles, 8);
     /// ```
     ///
-    pub fn reset(cpu: &mut CPU, bus: &Bus) {
-        cpu.abs = 0xFFFC; // FFF 1110
-        let low: u16 = bus.read(cpu.abs + 0, false) as u16;
-        let high: u16 = bus.read(cpu.abs + 1, false) as u16;
-
-        cpu.pc = (high << 8) << low;
+    pub fn reset(cpu: &mut CPU, bus: &mut Bus) {
+        cpu.abs = RESET_VECTOR;
+        cpu.pc = read_vector(bus, cpu.abs);
 
         cpu.a = 0;
         cpu.x = 0;
         cpu.y = 0;
         cpu.sp = 0xFD;
-        cpu.status = 0x00 | CpuFlags::U as u8;
+        cpu.status = CpuFlags::U as u8 | CpuFlags::I as u8;
 
         cpu.rel = 0x0000;
         cpu.abs = 0x0000;
         cpu.fetched = 0x00;
 
         cpu.cycles = 8; // resets take a long time
+        cpu.nmi_pending = false;
+        cpu.irq_inhibit = true;
+        cpu.poll = None;
+    }
+
+    /// Interrupt request. Ignored while the I flag is set, otherwise the
+    /// cpu pushes PC and status and continues at the vector in $FFFE.
+    ///
+    /// `clock` takes interrupts on its own from the /IRQ line, see
+    /// [`CPU::set_irq`], this is for driving the cpu by hand.
+    pub fn irq(cpu: &mut CPU, bus: &mut Bus) {
+        if cpu.get_flag(CpuFlags::I) == 0 {
+            CPU::interrupt(cpu, bus, IRQ_VECTOR, false);
+        }
+    }
+
+    /// Non maskable interrupt, taken regardless of the I flag. The cpu
+    /// pushes PC and status and continues at the vector in $FFFA.
+    pub fn nmi(cpu: &mut CPU, bus: &mut Bus) {
+        CPU::interrupt(cpu, bus, NMI_VECTOR, false);
+    }
+
+    /// The sequence shared by IRQ, NMI and BRK.
+    ///
+    /// ```no_run
+    /// push PCH, push PCL, push P (B set only for BRK, U always set)
+    /// set I, fetch the vector, 7 cycles
+    /// ```
+    ///
+    /// An NMI that is pending while an IRQ or BRK pushes its state hijacks
+    /// the vector fetch, the NMI handler runs with the B flag of the other
+    /// interrupt on the stack.
+    fn interrupt(cpu: &mut CPU, bus: &mut Bus, vector: u16, brk: bool) {
+        cpu.push(bus, (cpu.pc >> 8) as u8);
+        cpu.push(bus, (cpu.pc & LOW_BYTE) as u8);
+        let status: u8 = cpu.status & !(CpuFlags::B as u8);
+        let b: u8 = match brk {
+            true => CpuFlags::B as u8,
+            false => 0x00,
+        };
+        cpu.push(bus, status | b | CpuFlags::U as u8);
+        cpu.set_flag(CpuFlags::I, true);
+        cpu.irq_inhibit = true;
+
+        let vector: u16 = match cpu.nmi_pending || vector == NMI_VECTOR {
+            true => NMI_VECTOR,
+            false => vector,
+        };
+        cpu.nmi_pending = false;
+        cpu.abs = vector;
+        cpu.pc = read_vector(bus, vector);
+        cpu.cycles = INTERRUPT_CYCLES;
+    }
+
+    /// Decides which interrupt, if any, follows the current instruction.
+    ///
+    /// The 6502 polls its interrupt lines during the last cycle of each
+    /// instruction, looking at their state at the end of the cycle before.
+    /// NMI wins over IRQ, IRQ is masked by the I flag as it was when the
+    /// poll happened.
+    #[inline]
+    fn poll_interrupts(&self) -> Option<Interrupt> {
+        if self.nmi_pending {
+            Some(Interrupt::Nmi)
+        } else if self.irq_line && !self.irq_inhibit {
+            Some(Interrupt::Irq)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, bus: &mut Bus, data: u8) {
+        bus.write(STACK_PAGE + self.sp as u16, data);
+        self.sp = self.sp.wrapping_sub(1);
+    }
+
+    #[inline]
+    fn pull(&mut self, bus: &mut Bus) -> u8 {
+        self.sp = self.sp.wrapping_add(1);
+        bus.read(STACK_PAGE + self.sp as u16, false)
+    }
+
+    /// Reads the two byte little endian operand at PC.
+    #[inline]
+    fn read_operand_word(&mut self, bus: &mut Bus) -> u16 {
+        let lo: u16 = bus.read(self.pc, false) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        let hi: u16 = bus.read(self.pc, false) as u16;
+        self.pc = self.pc.wrapping_add(1);
+        (hi << 8) | lo
+    }
+
+    #[inline]
+    fn set_zn(&mut self, value: u8) {
+        self.set_flag(CpuFlags::Z, value == 0x00);
+        self.set_flag(CpuFlags::N, value & TOP_BIT_THRESH as u8 != 0x00);
+    }
+
+    /// A + value + C, shared by ADC, SBC and the unofficial RRA/ISC.
+    #[inline]
+    fn add(&mut self, value: u8) {
+        let sum: u16 =
+            self.a as u16 + value as u16 + self.get_flag(CpuFlags::C) as u16;
+        self.set_flag(CpuFlags::C, sum > 0x00FF);
+        // signed overflow: both inputs share a sign the result does not
+        self.set_flag(
+            CpuFlags::V,
+            !(self.a as u16 ^ value as u16) & (self.a as u16 ^ sum) & 0x0080
+                != 0,
+        );
+        self.a = (sum & LOW_BYTE) as u8;
+        self.set_zn(self.a);
+    }
+
+    /// CMP, CPX, CPY and the unofficial DCP.
+    #[inline]
+    fn compare(&mut self, register: u8, value: u8) {
+        self.set_flag(CpuFlags::C, register >= value);
+        self.set_zn(register.wrapping_sub(value));
+    }
+
+    /// Result of a read-modify-write instruction, to the accumulator for
+    /// the implied forms and back to memory otherwise. Like the real chip
+    /// the unmodified value is written first, mappers such as MMC1 see
+    /// both writes on back to back cycles.
+    #[inline]
+    fn write_back(&mut self, bus: &mut Bus, value: u8) {
+        if LOOKUP_TABLE[self.opcode as usize].mneumonic.am_name
+            == AddrModeMneumonic::IMP
+        {
+            self.a = value;
+        } else {
+            bus.write(self.abs, self.fetched);
+            bus.write(self.abs, value);
+        }
+    }
+
+    #[inline]
+    fn shift_left(&mut self, value: u8, carry_in: bool) -> u8 {
+        self.set_flag(CpuFlags::C, value & 0x80 != 0);
+        let result: u8 = (value << 1) | carry_in as u8;
+        self.set_zn(result);
+        result
+    }
+
+    #[inline]
+    fn shift_right(&mut self, value: u8, carry_in: bool) -> u8 {
+        self.set_flag(CpuFlags::C, value & 0x01 != 0);
+        let result: u8 = (value >> 1) | ((carry_in as u8) << 7);
+        self.set_zn(result);
+        result
+    }
+
+    /// The unstable SHA, SHX, SHY and TAS stores. The value is ANDed with
+    /// the high byte of the unindexed address plus one, when indexing
+    /// crossed a page the same value also replaces the high byte of the
+    /// address written to.
+    #[inline]
+    fn store_high_and(&mut self, bus: &mut Bus, value: u8) {
+        let index: u8 = match self.opcode {
+            0x9C => self.x,
+            _ => self.y,
+        };
+        let base: u16 = self.abs.wrapping_sub(index as u16);
+        let value: u8 = value & ((base >> 8) as u8).wrapping_add(1);
+        if self.abs & HIGH_BYTE != base & HIGH_BYTE {
+            self.abs = ((value as u16) << 8) | (self.abs & LOW_BYTE);
+        }
+        bus.write(self.abs, value);
+    }
+
+    /// Taken branches cost a cycle, two when they land on another page.
+    #[inline]
+    fn branch(&mut self, taken: bool) {
+        if taken {
+            self.cycles += 1;
+            self.abs = self.pc.wrapping_add(self.rel);
+            if self.abs & HIGH_BYTE != self.pc & HIGH_BYTE {
+                self.cycles += 1;
+            }
+            self.pc = self.abs;
+        }
     }
 
     // Simulates a clock cycle of the 6502 CPU.
@@ -215,6 +434,9 @@ impl CPU {
     /// It then looks up the number of cycles required to execute the instruction from a lookup table, adds any additional cycles required,
     /// and updates the CPU's cycle count accordingly.
     ///
+    /// Between instructions the cpu checks for an interrupt it decided to take during the last instruction, see
+    /// [`CPU::set_irq`] and [`CPU::set_nmi`], and runs the 7 cycle interrupt sequence instead of the next instruction.
+    ///
     /// # Arguments
     ///
     /// * `cpu` - A mutable reference to the [`M6502`] struct representing the 6502 CPU being simulated.
@@ -229,22 +451,48 @@ impl CPU {
     /// ```
     pub fn clock(cpu: &mut CPU, bus: &mut Bus) {
         if cpu.complete() {
-            cpu.opcode = bus.read(cpu.pc, true);
-            cpu.set_flag(CpuFlags::U, true);
-            cpu.pc += 1;
+            match cpu.poll.take() {
+                Some(Interrupt::Nmi) => {
+                    CPU::interrupt(cpu, bus, NMI_VECTOR, false)
+                }
+                Some(Interrupt::Irq) => {
+                    CPU::interrupt(cpu, bus, IRQ_VECTOR, false)
+                }
+                None => CPU::execute(cpu, bus),
+            }
+        }
+        cpu._clock_count = cpu._clock_count.wrapping_add(1);
+        cpu.cycles -= 1;
+
+        if cpu.cycles == 1 {
+            cpu.poll = cpu.poll_interrupts();
+        }
+    }
 
-            let instruction: &CpuInstruction =
-                &LOOKUP_TABLE[cpu.opcode as usize];
-            cpu.cycles = instruction.cycles;
+    /// Fetches, decodes and runs the instruction at PC in one go, the
+    /// remaining cycles are then only counted down.
+    fn execute(cpu: &mut CPU, bus: &mut Bus) {
+        cpu.opcode = bus.read(cpu.pc, true);
+        cpu.set_flag(CpuFlags::U, true);
+        cpu.pc = cpu.pc.wrapping_add(1);
 
-            let added_cycle1: u8 = (instruction.op_code)(cpu, bus);
-            let added_cycle2: u8 = (instruction.addr_mode)(cpu, bus);
+        let instruction: &CpuInstruction = &LOOKUP_TABLE[cpu.opcode as usize];
+        cpu.cycles = instruction.cycles;
 
-            cpu.cycles += added_cycle1 & added_cycle2;
-            cpu.set_flag(CpuFlags::U, true);
-        }
-        cpu._clock_count += 1;
-        cpu.cycles -= 1;
+        // CLI, SEI and PLP change I after the poll already happened, the
+        // instruction that follows them still sees the old value
+        let inhibit: bool = cpu.get_flag(CpuFlags::I) == 1;
+
+        // the addressing mode resolves the operand the opcode works on
+        let added_cycle1: u8 = (instruction.addr_mode)(cpu, bus);
+        let added_cycle2: u8 = (instruction.op_code)(cpu, bus);
+
+        cpu.cycles += added_cycle1 & added_cycle2;
+        cpu.set_flag(CpuFlags::U, true);
+        cpu.irq_inhibit = match cpu.opcode {
+            0x28 | 0x58 | 0x78 => inhibit,
+            _ => cpu.get_flag(CpuFlags::I) == 1,
+        };
     }
 
     /// Returns the value of a specific flag in the status register.
@@ -377,7 +625,7 @@ impl CPU {
                     address += 1;
                     _high = 0x00;
                     // let string_rep = format!("#${} {{imm}}", helpers::to_hex(low as u32, 2));
-                    let string_rep: String = format!("#${:x} {{imm}}", low);
+                    let string_rep: String = format!("#${:x} {{imm}}", _value);
                     instruction_address.push_str(&string_rep);
                 }
 
@@ -495,7 +743,7 @@ impl CPU {
                     let string_rep: String = format!(
                         "${:x} [${:x}] {{rel}}",
                         _value,
-                        address + _value as u32
+                        (address as u16).wrapping_add(_value as i8 as u16)
                     );
 
                     // Append the string representation to the existing instruction address string
@@ -625,6 +873,34 @@ impl CPU {
     pub fn set_clock_count(&mut self, clock_count: u32) {
         self._clock_count = clock_count;
     }
+
+    pub const fn irq_line(&self) -> bool {
+        self.irq_line
+    }
+
+    /// Drives the /IRQ input. The line is level triggered, whoever asserts
+    /// it keeps it asserted until the interrupt source is acknowledged.
+    pub fn set_irq(&mut self, asserted: bool) {
+        self.irq_line = asserted;
+    }
+
+    pub const fn nmi_line(&self) -> bool {
+        self.nmi_line
+    }
+
+    /// Drives the /NMI input. Only the transition to asserted matters, an
+    /// NMI is latched on that edge and serviced even if the line is
+    /// released before the cpu gets to it.
+    pub fn set_nmi(&mut self, asserted: bool) {
+        if asserted && !self.nmi_line {
+            self.nmi_pending = true;
+        }
+        self.nmi_line = asserted;
+    }
+
+    pub const fn nmi_pending(&self) -> bool {
+        self.nmi_pending
+    }
 }
 
 impl M6502Opcodes for CPU {
@@ -681,33 +957,8 @@ impl M6502Opcodes for CPU {
     /// assert_eq!(cpu.get_flag(M6502::M6502Flags::N), false);
     /// ```
     fn ADC(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        // Grab the data that we are adding to the accumulator
-        // Add is performed in 16-bit domain for emulation to capture any
-        // carry bit, which will exist in bit 8 of the 16-bit word
-        cpu.temp = (cpu.a + cpu.fetch(bus) + cpu.get_flag(CpuFlags::C)).into();
-
-        // The carry flag out exists in the high byte bit 0
-        cpu.set_flag(CpuFlags::C, cpu.temp > 255);
-
-        // The Zero flag is set if the result is 0
-        cpu.set_flag(CpuFlags::Z, (cpu.temp & LOW_BYTE) == 0);
-
-        // The signed Overflow flag is set based on all that up there! :D
-        cpu.set_flag(
-            CpuFlags::V,
-            !(cpu.a as u16 ^ cpu.fetched as u16)
-                & (cpu.a as u16 ^ cpu.temp)
-                & 0x0080
-                != 0,
-        );
-
-        // The negative flag is set to the most significant bit of the result
-        cpu.set_flag(CpuFlags::N, (cpu.temp & TOP_BIT_THRESH) != 0);
-
-        // Load the result into the accumulator (it's 8-bit dont forget!)
-        cpu.a = ((cpu.temp as u16) & LOW_BYTE) as u8;
-
-        // This instruction has the potential to require an additional clock cycle
+        let value: u8 = cpu.fetch(bus);
+        cpu.add(value);
         1u8
     }
 
@@ -827,17 +1078,9 @@ impl M6502Opcodes for CPU {
     /// ```
     #[inline]
     fn ASL(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp = (cpu.fetch(bus) << 1).into();
-        cpu.set_flag(CpuFlags::C, (cpu.temp & HIGH_BYTE) > 0);
-        cpu.set_flag(CpuFlags::Z, (cpu.temp & LOW_BYTE) == 0);
-        cpu.set_flag(CpuFlags::N, (cpu.temp & TOP_BIT_THRESH) != 0);
-        if LOOKUP_TABLE[cpu.opcode as usize].addr_mode as usize
-            == CPU::IMP as usize
-        {
-            cpu.a = (cpu.temp & LOW_BYTE) as u8;
-        } else {
-            bus.write(cpu.abs, (cpu.temp & LOW_BYTE) as u8);
-        }
+        let value: u8 = cpu.fetch(bus);
+        let result: u8 = cpu.shift_left(value, false);
+        cpu.write_back(bus, result);
         0u8
     }
 
@@ -860,16 +1103,8 @@ impl M6502Opcodes for CPU {
     /// The number of cycles that the instruction has consumed, which is always 0.
     #[inline]
     fn BCC(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        if cpu.get_flag(CpuFlags::C) == 0_u8 {
-            cpu.cycles += 1_u8;
-            cpu.abs = cpu.pc + cpu.rel;
-
-            if cpu.abs & HIGH_BYTE != cpu.pc & HIGH_BYTE {
-                cpu.cycles += 1_u8;
-            }
-            cpu.pc = cpu.abs;
-        }
-        0_u8
+        cpu.branch(cpu.get_flag(CpuFlags::C) == 0);
+        0u8
     }
 
     /// Branch on carry set
@@ -928,16 +1163,8 @@ impl M6502Opcodes for CPU {
     /// ```
     #[inline]
     fn BCS(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        if cpu.get_flag(CpuFlags::C) == 1_u8 {
-            cpu.cycles += 1_u8;
-            cpu.abs = cpu.pc + cpu.rel;
-
-            if cpu.abs & HIGH_BYTE != cpu.pc & HIGH_BYTE {
-                cpu.cycles += 1_u8;
-            }
-            cpu.pc = cpu.abs;
-        }
-        1u8
+        cpu.branch(cpu.get_flag(CpuFlags::C) == 1);
+        0u8
     }
 
     /// Branch on equal (zero set)
@@ -996,16 +1223,8 @@ impl M6502Opcodes for CPU {
     /// ```
     #[inline]
     fn BEQ(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        if cpu.get_flag(CpuFlags::Z) == 1_u8 {
-            cpu.cycles += 1_u8;
-            cpu.abs = cpu.pc + cpu.rel;
-
-            if cpu.abs & HIGH_BYTE != cpu.pc & HIGH_BYTE {
-                cpu.cycles += 1_u8;
-            }
-            cpu.pc = cpu.abs;
-        }
-        0_u8
+        cpu.branch(cpu.get_flag(CpuFlags::Z) == 1);
+        0u8
     }
 
     /// Bit test
@@ -1123,16 +1342,8 @@ impl M6502Opcodes for CPU {
     /// ```
     #[inline]
     fn BMI(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        if cpu.get_flag(CpuFlags::N) == 1_u8 {
-            cpu.cycles += 1_u8;
-            cpu.abs = cpu.pc + cpu.rel;
-
-            if cpu.abs & HIGH_BYTE != cpu.pc & HIGH_BYTE {
-                cpu.cycles += 1_u8;
-            }
-            cpu.pc = cpu.abs;
-        }
-        0_u8
+        cpu.branch(cpu.get_flag(CpuFlags::N) == 1);
+        0u8
     }
 
     /// Branch on result not equal
@@ -1184,16 +1395,8 @@ impl M6502Opcodes for CPU {
     /// ```
     #[inline]
     fn BNE(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        if cpu.get_flag(CpuFlags::Z) == 0_u8 {
-            cpu.cycles += 1_u8;
-            cpu.abs = cpu.pc + cpu.rel;
-
-            if cpu.abs & HIGH_BYTE != cpu.pc & HIGH_BYTE {
-                cpu.cycles += 1_u8;
-            }
-            cpu.pc = cpu.abs;
-        }
-        0_u8
+        cpu.branch(cpu.get_flag(CpuFlags::Z) == 0);
+        0u8
     }
 
     /// Branch on result plus
@@ -1252,16 +1455,8 @@ impl M6502Opcodes for CPU {
     /// ```
     #[inline]
     fn BPL(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        if cpu.get_flag(CpuFlags::N) == 0 {
-            cpu.cycles += 1;
-            cpu.abs = cpu.pc + cpu.rel;
-
-            if cpu.abs & HIGH_BYTE != cpu.pc & HIGH_BYTE {
-                cpu.cycles += 1;
-            }
-            cpu.pc = cpu.abs;
-        }
-        0x0u8
+        cpu.branch(cpu.get_flag(CpuFlags::N) == 0);
+        0u8
     }
 
     /// Executes the BRK instruction of the [`M6502`] CPU.
@@ -1278,9 +1473,10 @@ impl M6502Opcodes for CPU {
     /// # Description
     ///
     /// The BRK instruction causes a software interrupt. It sets the interrupt flag to disable further
-    /// interrupts, pushes the program counter (plus one) and status register onto the stack, and loads
-    /// the program counter with the address stored at locations 0xFFFE and 0xFFFF. The BRK instruction
-    /// can be used for implementing subroutines and interrupt handlers.
+    /// interrupts, pushes the program counter (plus two, past the padding byte) and status register
+    /// with the B flag set onto the stack, and loads the program counter with the address stored at
+    /// locations 0xFFFE and 0xFFFF, the same vector IRQs use. The B flag on the stack is the only way
+    /// a handler can tell the two apart.
     ///
     /// # Example
     ///
@@ -1302,57 +1498,21 @@ impl M6502Opcodes for CPU {
     ///
     #[inline]
     fn BRK(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.pc += 1;
-
-        cpu.set_flag(CpuFlags::I, true);
-        bus.write(
-            (0x0100_u16 + cpu.sp as u16).into(),
-            (cpu.pc >> 8 & LOW_BYTE) as u8,
-        );
-        cpu.sp -= 1;
-        bus.write(
-            (0x0100_u16 + cpu.sp as u16).into(),
-            (cpu.pc & LOW_BYTE) as u8,
-        );
-        cpu.sp -= 1;
-
-        cpu.set_flag(CpuFlags::B, true);
-        bus.write((0x0100_u16 + cpu.sp as u16).into(), cpu.status);
-        cpu.sp -= 1;
-        cpu.set_flag(CpuFlags::B, true);
-
-        cpu.pc = ((bus.read(0xFFFE, false) != 0x0u8)
-            | (bus.read(0xFFFF, false) != 0x0u8))
-            .into();
+        // IMM already stepped over the padding byte, RTI returns past it
+        CPU::interrupt(cpu, bus, IRQ_VECTOR, true);
         0x0u8
     }
 
     #[inline]
     fn BVC(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        if cpu.get_flag(CpuFlags::V) == 0u8 {
-            cpu.cycles += 1;
-            cpu.abs = cpu.pc + cpu.rel;
-
-            if cpu.abs & HIGH_BYTE != cpu.pc & HIGH_BYTE {
-                cpu.cycles += 1;
-            }
-            cpu.pc = cpu.abs;
-        }
-        0x0u8
+        cpu.branch(cpu.get_flag(CpuFlags::V) == 0);
+        0u8
     }
 
     #[inline]
     fn BVS(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        if cpu.get_flag(CpuFlags::V) == 1u8 {
-            cpu.cycles += 1;
-            cpu.abs = cpu.pc + cpu.rel;
-
-            if cpu.abs & HIGH_BYTE != cpu.pc & HIGH_BYTE {
-                cpu.cycles += 1;
-            }
-            cpu.pc = cpu.abs;
-        }
-        0x0u8
+        cpu.branch(cpu.get_flag(CpuFlags::V) == 1);
+        0u8
     }
 
     #[inline]
@@ -1381,10 +1541,8 @@ impl M6502Opcodes for CPU {
 
     #[inline]
     fn CMP(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp = (cpu.a - cpu.fetch(bus)).into();
-        cpu.set_flag(CpuFlags::C, cpu.a >= cpu.fetched);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & LOW_BYTE == 0x0000);
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH != 0x0000);
+        let value: u8 = cpu.fetch(bus);
+        cpu.compare(cpu.a, value);
         1u8
     }
 
@@ -1403,77 +1561,66 @@ impl M6502Opcodes for CPU {
     /// The result of the operation, which is always 0.
     #[inline]
     fn CPX(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp = (cpu.x - cpu.fetch(bus)).into();
-        cpu.set_flag(CpuFlags::C, cpu.x >= cpu.fetched);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & LOW_BYTE == 0x0000);
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH != 0x0000);
+        let value: u8 = cpu.fetch(bus);
+        cpu.compare(cpu.x, value);
         0u8
     }
 
     #[inline]
     fn CPY(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp = (cpu.y - cpu.fetch(bus)).into();
-        cpu.set_flag(CpuFlags::C, cpu.y >= cpu.fetched);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & LOW_BYTE == 0x0000);
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH != 0x0000);
+        let value: u8 = cpu.fetch(bus);
+        cpu.compare(cpu.y, value);
         0u8
     }
 
     #[inline]
     fn DEC(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp = cpu.fetch(bus) as u16 - 1;
-        bus.write(cpu.abs, (cpu.temp & LOW_BYTE) as u8);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & LOW_BYTE == 0x0000);
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH != 0x0000);
+        let result: u8 = cpu.fetch(bus).wrapping_sub(1);
+        cpu.write_back(bus, result);
+        cpu.set_zn(result);
         0u8
     }
 
     #[inline]
     fn DEX(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        cpu.x -= 1;
-        cpu.set_flag(CpuFlags::Z, cpu.x == 0x00);
-        cpu.set_flag(CpuFlags::N, cpu.x & TOP_BIT_THRESH as u8 != 0x0000);
+        cpu.x = cpu.x.wrapping_sub(1);
+        cpu.set_zn(cpu.x);
         0u8
     }
 
     #[inline]
     fn DEY(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        cpu.y -= 1;
-        cpu.set_flag(CpuFlags::Z, cpu.y == 0x00);
-        cpu.set_flag(CpuFlags::N, cpu.y & TOP_BIT_THRESH as u8 != 0x0000);
+        cpu.y = cpu.y.wrapping_sub(1);
+        cpu.set_zn(cpu.y);
         0u8
     }
 
     #[inline]
     fn EOR(cpu: &mut CPU, bus: &mut Bus) -> u8 {
         cpu.a ^= cpu.fetch(bus);
-        cpu.set_flag(CpuFlags::Z, cpu.y == 0x00);
-        cpu.set_flag(CpuFlags::N, cpu.y & TOP_BIT_THRESH as u8 != 0x0000);
+        cpu.set_zn(cpu.a);
         1u8
     }
 
     #[inline]
     fn INC(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp = cpu.fetch(bus) as u16 + 1;
-        bus.write(cpu.abs, (cpu.temp & LOW_BYTE) as u8);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & LOW_BYTE == 0x0000);
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH != 0x0000);
+        let result: u8 = cpu.fetch(bus).wrapping_add(1);
+        cpu.write_back(bus, result);
+        cpu.set_zn(result);
         0u8
     }
 
     #[inline]
     fn INX(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        cpu.x += 1;
-        cpu.set_flag(CpuFlags::Z, cpu.x == 0x00);
-        cpu.set_flag(CpuFlags::N, cpu.x & TOP_BIT_THRESH as u8 != 0x0000);
+        cpu.x = cpu.x.wrapping_add(1);
+        cpu.set_zn(cpu.x);
         0u8
     }
 
     #[inline]
     fn INY(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        cpu.y += 1;
-        cpu.set_flag(CpuFlags::Z, cpu.y == 0x00);
-        cpu.set_flag(CpuFlags::N, cpu.y & TOP_BIT_THRESH as u8 != 0x0000);
+        cpu.y = cpu.y.wrapping_add(1);
+        cpu.set_zn(cpu.y);
         0u8
     }
 
@@ -1485,12 +1632,10 @@ impl M6502Opcodes for CPU {
 
     #[inline]
     fn JSR(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.pc -= 1;
+        cpu.pc = cpu.pc.wrapping_sub(1);
 
-        bus.write(0x0100 + cpu.sp as u16, (cpu.pc << 8 & LOW_BYTE) as u8);
-        cpu.sp -= 1;
-        bus.write(0x0100 + cpu.sp as u16, (cpu.pc & LOW_BYTE) as u8);
-        cpu.sp -= 1;
+        cpu.push(bus, (cpu.pc >> 8) as u8);
+        cpu.push(bus, (cpu.pc & LOW_BYTE) as u8);
 
         cpu.pc = cpu.abs;
         0u8
@@ -1565,26 +1710,19 @@ impl M6502Opcodes for CPU {
 
     #[inline]
     fn LSR(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp = (cpu.fetch(bus) >> 1) as u16;
-        cpu.set_flag(CpuFlags::C, cpu.fetched & 0x0001 != 0x0000);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & LOW_BYTE == 0x0000);
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH != 0x0000);
-        if LOOKUP_TABLE[cpu.opcode as usize].addr_mode as usize
-            == CPU::IMP as usize
-        {
-            cpu.a = cpu.temp as u8 & LOW_BYTE as u8;
-        } else {
-            bus.write(cpu.abs, (cpu.temp & LOW_BYTE) as u8);
-        }
+        let value: u8 = cpu.fetch(bus);
+        let result: u8 = cpu.shift_right(value, false);
+        cpu.write_back(bus, result);
         0u8
     }
 
     #[inline]
     fn NOP(cpu: &mut CPU, _: &mut Bus) -> u8 {
-        return match cpu.opcode {
+        // only the absolute,X forms take the page crossing cycle
+        match cpu.opcode {
             0x1C | 0x3C | 0x5C | 0x7C | 0xDC | 0xFC => 1u8,
             _ => 0u8,
-        };
+        }
     }
 
     #[inline]
@@ -1597,111 +1735,76 @@ impl M6502Opcodes for CPU {
 
     #[inline]
     fn PHA(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        bus.write(0x0100 + cpu.sp as u16, cpu.a);
-        cpu.sp -= 1;
+        cpu.push(bus, cpu.a);
         0u8
     }
 
     #[inline]
     fn PHP(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        bus.write(
-            0x0100 + cpu.sp as u16,
-            cpu.status | CpuFlags::B as u8 | CpuFlags::U as u8,
-        );
-        cpu.set_flag(CpuFlags::B, false);
-        cpu.set_flag(CpuFlags::U, false);
-        cpu.sp -= 1;
+        // B only exists on the stack, pushed set by PHP and BRK
+        cpu.push(bus, cpu.status | CpuFlags::B as u8 | CpuFlags::U as u8);
         0u8
     }
 
     #[inline]
     fn PLA(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.sp += 1;
-        cpu.status = bus.read(0x0100 + cpu.sp as u16, false);
+        cpu.a = cpu.pull(bus);
         cpu.set_flag(CpuFlags::Z, cpu.a == 0x00);
-        cpu.set_flag(CpuFlags::N, cpu.a & TOP_BIT_THRESH as u8 == 0x00);
+        cpu.set_flag(CpuFlags::N, cpu.a & TOP_BIT_THRESH as u8 != 0x00);
         0u8
     }
 
     #[inline]
     fn PLP(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.sp += 1;
-        cpu.status = bus.read(0x0100 + cpu.sp as u16, false);
+        cpu.status = cpu.pull(bus);
+        cpu.set_flag(CpuFlags::B, false);
         cpu.set_flag(CpuFlags::U, true);
         0u8
     }
 
     #[inline]
     fn ROL(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp = (cpu.fetch(bus) << 1 | cpu.get_flag(CpuFlags::C)).into();
-        cpu.set_flag(CpuFlags::C, cpu.temp & HIGH_BYTE != 0x0000);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & LOW_BYTE == 0x0000);
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH != 0x0000);
-        if LOOKUP_TABLE[cpu.opcode as usize].addr_mode as usize
-            == CPU::IMP as usize
-        {
-            cpu.a = (cpu.temp & LOW_BYTE) as u8;
-        } else {
-            bus.write(cpu.abs, (cpu.temp & LOW_BYTE) as u8);
-        }
+        let value: u8 = cpu.fetch(bus);
+        let carry: bool = cpu.get_flag(CpuFlags::C) == 1;
+        let result: u8 = cpu.shift_left(value, carry);
+        cpu.write_back(bus, result);
         0u8
     }
 
     #[inline]
     fn ROR(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.temp =
-            (cpu.get_flag(CpuFlags::C) << 7 | cpu.fetch(bus) >> 1).into();
-        cpu.set_flag(CpuFlags::C, cpu.fetched & 0x01 == 0x00);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & LOW_BYTE == 0x00);
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH != 0x00);
-        if LOOKUP_TABLE[cpu.opcode as usize].addr_mode as usize
-            == CPU::IMP as usize
-        {
-            cpu.a = (cpu.temp & LOW_BYTE) as u8;
-        } else {
-            bus.write(cpu.abs, (cpu.temp & LOW_BYTE) as u8);
-        }
+        let value: u8 = cpu.fetch(bus);
+        let carry: bool = cpu.get_flag(CpuFlags::C) == 1;
+        let result: u8 = cpu.shift_right(value, carry);
+        cpu.write_back(bus, result);
         0u8
     }
 
     #[inline]
     fn RTI(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.sp += 1;
-        cpu.status = bus.read(0x0100 + cpu.sp as u16, false);
-        cpu.status &= !(CpuFlags::B as u8);
-        cpu.status &= !(CpuFlags::U as u8);
-
-        cpu.sp += 1;
-        cpu.pc = bus.read(0x0100 + cpu.sp as u16, false).into();
-        cpu.sp += 1;
-        cpu.pc |= (bus.read(0x0100 + cpu.sp as u16, false) as u16) << 8;
+        cpu.status = cpu.pull(bus);
+        cpu.set_flag(CpuFlags::B, false);
+        cpu.set_flag(CpuFlags::U, true);
+
+        cpu.pc = cpu.pull(bus).into();
+        cpu.pc |= (cpu.pull(bus) as u16) << 8;
         0u8
     }
 
     #[inline]
     fn RTS(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.sp += 1;
-        cpu.pc = bus.read(0x0100 + cpu.sp as u16, false).into();
-        cpu.sp += 1;
-        cpu.pc |= (bus.read(0x0100 + cpu.sp as u16, false) as u16) << 8;
+        cpu.pc = cpu.pull(bus).into();
+        cpu.pc |= (cpu.pull(bus) as u16) << 8;
 
-        cpu.pc += 1;
+        cpu.pc = cpu.pc.wrapping_add(1);
         0u8
     }
 
     #[inline]
     fn SBC(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        let value: u16 = cpu.fetch(bus) as u16 ^ LOW_BYTE;
-        cpu.temp = cpu.a as u16 + value + cpu.get_flag(CpuFlags::C) as u16;
-        cpu.set_flag(CpuFlags::C, cpu.temp & HIGH_BYTE != 0x0000);
-        cpu.set_flag(CpuFlags::Z, cpu.temp & HIGH_BYTE == 0x0000);
-        cpu.set_flag(
-            CpuFlags::V,
-            (cpu.temp ^ cpu.a as u16) & (cpu.temp ^ value) & TOP_BIT_THRESH
-                != 0x0000,
-        );
-        cpu.set_flag(CpuFlags::N, cpu.temp & TOP_BIT_THRESH == 0x0000);
-        cpu.a = cpu.temp as u8 & LOW_BYTE as u8;
+        // A - M - (1 - C) is A + !M + C
+        let value: u8 = cpu.fetch(bus);
+        cpu.add(!value);
         1u8
     }
 
@@ -1787,6 +1890,186 @@ impl M6502Opcodes for CPU {
         0u8
     }
 
+    // unofficial opcodes, the side effects of the decoder enabling
+    // several official operations at once
+
+    /// AND #imm, then LSR A.
+    #[inline]
+    fn ALR(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let value: u8 = cpu.a & cpu.fetch(bus);
+        cpu.a = cpu.shift_right(value, false);
+        0u8
+    }
+
+    /// AND #imm, with bit 7 of the result copied into C.
+    #[inline]
+    fn ANC(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        cpu.a &= cpu.fetch(bus);
+        cpu.set_zn(cpu.a);
+        cpu.set_flag(CpuFlags::C, cpu.a & 0x80 != 0);
+        0u8
+    }
+
+    /// AND #imm, then ROR A. C is bit 6 of the result and V is bit 6
+    /// xor bit 5, the adder is involved even though nothing is added.
+    #[inline]
+    fn ARR(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let value: u8 = cpu.a & cpu.fetch(bus);
+        cpu.a = (value >> 1) | (cpu.get_flag(CpuFlags::C) << 7);
+        cpu.set_zn(cpu.a);
+        cpu.set_flag(CpuFlags::C, cpu.a & 0x40 != 0);
+        cpu.set_flag(CpuFlags::V, ((cpu.a >> 6) ^ (cpu.a >> 5)) & 0x01 != 0);
+        0u8
+    }
+
+    /// X = (A & X) - #imm, flags as CMP and without borrow in.
+    #[inline]
+    fn AXS(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let value: u8 = cpu.fetch(bus);
+        let ax: u8 = cpu.a & cpu.x;
+        cpu.compare(ax, value);
+        cpu.x = ax.wrapping_sub(value);
+        0u8
+    }
+
+    /// DEC, then CMP with the result.
+    #[inline]
+    fn DCP(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let result: u8 = cpu.fetch(bus).wrapping_sub(1);
+        cpu.write_back(bus, result);
+        cpu.compare(cpu.a, result);
+        0u8
+    }
+
+    /// INC, then SBC with the result.
+    #[inline]
+    fn ISC(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let result: u8 = cpu.fetch(bus).wrapping_add(1);
+        cpu.write_back(bus, result);
+        cpu.add(!result);
+        0u8
+    }
+
+    /// A, X and SP all become memory & SP.
+    #[inline]
+    fn LAS(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let value: u8 = cpu.fetch(bus) & cpu.sp;
+        cpu.a = value;
+        cpu.x = value;
+        cpu.sp = value;
+        cpu.set_zn(value);
+        1u8
+    }
+
+    /// LDA and LDX at once.
+    #[inline]
+    fn LAX(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        cpu.a = cpu.fetch(bus);
+        cpu.x = cpu.a;
+        cpu.set_zn(cpu.a);
+        1u8
+    }
+
+    /// Unstable LAX #imm, A and X become (A | magic) & #imm. See
+    /// [`UNSTABLE_MAGIC`].
+    #[inline]
+    fn LXA(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        cpu.a = (cpu.a | UNSTABLE_MAGIC) & cpu.fetch(bus);
+        cpu.x = cpu.a;
+        cpu.set_zn(cpu.a);
+        0u8
+    }
+
+    /// ROL, then AND with the result.
+    #[inline]
+    fn RLA(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let value: u8 = cpu.fetch(bus);
+        let carry: bool = cpu.get_flag(CpuFlags::C) == 1;
+        let result: u8 = cpu.shift_left(value, carry);
+        cpu.write_back(bus, result);
+        cpu.a &= result;
+        cpu.set_zn(cpu.a);
+        0u8
+    }
+
+    /// ROR, then ADC with the result, the carry out of ROR goes into the
+    /// addition.
+    #[inline]
+    fn RRA(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let value: u8 = cpu.fetch(bus);
+        let carry: bool = cpu.get_flag(CpuFlags::C) == 1;
+        let result: u8 = cpu.shift_right(value, carry);
+        cpu.write_back(bus, result);
+        cpu.add(result);
+        0u8
+    }
+
+    /// Stores A & X, no flags.
+    #[inline]
+    fn SAX(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        bus.write(cpu.abs, cpu.a & cpu.x);
+        0u8
+    }
+
+    /// Unstable store of A & X & (high byte of the address + 1).
+    #[inline]
+    fn SHA(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        cpu.store_high_and(bus, cpu.a & cpu.x);
+        0u8
+    }
+
+    /// Unstable store of X & (high byte of the address + 1).
+    #[inline]
+    fn SHX(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        cpu.store_high_and(bus, cpu.x);
+        0u8
+    }
+
+    /// Unstable store of Y & (high byte of the address + 1).
+    #[inline]
+    fn SHY(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        cpu.store_high_and(bus, cpu.y);
+        0u8
+    }
+
+    /// ASL, then ORA with the result.
+    #[inline]
+    fn SLO(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let value: u8 = cpu.fetch(bus);
+        let result: u8 = cpu.shift_left(value, false);
+        cpu.write_back(bus, result);
+        cpu.a |= result;
+        cpu.set_zn(cpu.a);
+        0u8
+    }
+
+    /// LSR, then EOR with the result.
+    #[inline]
+    fn SRE(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        let value: u8 = cpu.fetch(bus);
+        let result: u8 = cpu.shift_right(value, false);
+        cpu.write_back(bus, result);
+        cpu.a ^= result;
+        cpu.set_zn(cpu.a);
+        0u8
+    }
+
+    /// Unstable, SP = A & X, then stored like SHA.
+    #[inline]
+    fn TAS(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        cpu.sp = cpu.a & cpu.x;
+        cpu.store_high_and(bus, cpu.sp);
+        0u8
+    }
+
+    /// Unstable, A = (A | magic) & X & #imm. See [`UNSTABLE_MAGIC`].
+    #[inline]
+    fn XAA(cpu: &mut CPU, bus: &mut Bus) -> u8 {
+        cpu.a = (cpu.a | UNSTABLE_MAGIC) & cpu.x & cpu.fetch(bus);
+        cpu.set_zn(cpu.a);
+        0u8
+    }
+
     #[inline(always)]
     fn XXX(_: &mut CPU, _: &mut Bus) -> u8 {
         0u8
@@ -1860,6 +2143,7 @@ impl M6502AddrModes for CPU {
     /// ```
     fn IMM(cpu: &mut CPU, _bus: &mut Bus) -> u8 {
         cpu.abs = cpu.pc;
+        cpu.pc = cpu.pc.wrapping_add(1);
         0x00
     }
 
@@ -1897,7 +2181,7 @@ impl M6502AddrModes for CPU {
     /// ```
     fn ZP0(cpu: &mut CPU, bus: &mut Bus) -> u8 {
         cpu.abs = bus.read(cpu.pc, false) as u16;
-        cpu.pc += 1;
+        cpu.pc = cpu.pc.wrapping_add(1);
         cpu.abs &= LOW_BYTE; // checking if high bit is on a new page
         0x00
     }
@@ -1937,9 +2221,9 @@ impl M6502AddrModes for CPU {
     /// // The `abs` register in the `cpu` will now hold the value 0x42 from the zero page.
     /// ```
     fn ZPX(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.abs = bus.read(cpu.pc + cpu.x as u16, false) as u16;
-        cpu.pc += 1;
-        cpu.abs &= LOW_BYTE;
+        // the index wraps around inside the zero page
+        cpu.abs = bus.read(cpu.pc, false).wrapping_add(cpu.x) as u16;
+        cpu.pc = cpu.pc.wrapping_add(1);
         0x00
     }
 
@@ -1978,9 +2262,9 @@ impl M6502AddrModes for CPU {
     /// // The `abs` register in the `cpu` will now hold the value 0x42 from the zero page.
     /// ```
     fn ZPY(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        cpu.abs = bus.read(cpu.pc + cpu.y as u16, false) as u16;
-        cpu.pc += 1;
-        cpu.abs &= LOW_BYTE;
+        // the index wraps around inside the zero page
+        cpu.abs = bus.read(cpu.pc, false).wrapping_add(cpu.y) as u16;
+        cpu.pc = cpu.pc.wrapping_add(1);
         0x00
     }
 
@@ -2019,11 +2303,7 @@ impl M6502AddrModes for CPU {
     /// // The `abs` register in the `cpu` will now hold the value 0x3412 (little-endian).
     /// ```
     fn ABS(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        let lo: u32 = bus.read(cpu.pc as u16, false).into();
-        cpu.pc += 1;
-        let hi: u32 = bus.read(cpu.pc as u16, false).into();
-        cpu.pc += 1;
-        cpu.abs = ((hi << 8) | lo) as u16;
+        cpu.abs = cpu.read_operand_word(bus);
         0x00
     }
 
@@ -2064,18 +2344,11 @@ impl M6502AddrModes for CPU {
     /// // since X was added to the absolute address.
     /// ```
     fn ABX(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        let lo: u32 = bus.read(cpu.pc as u16, false).into();
-        cpu.pc += 1;
-        let hi: u32 = bus.read(cpu.pc as u16, false).into();
-        cpu.pc += 1;
-        cpu.abs = ((hi << 8) | lo) as u16;
-        cpu.abs += cpu.x as u16;
-
-        return if (cpu.abs & LOW_BYTE) != (hi << 8) as u16 {
-            0x01
-        } else {
-            0x00
-        };
+        let base: u16 = cpu.read_operand_word(bus);
+        cpu.abs = base.wrapping_add(cpu.x as u16);
+
+        // crossing into the next page costs a cycle
+        (cpu.abs & HIGH_BYTE != base & HIGH_BYTE) as u8
     }
 
     /// Absolute Indexed with Y Register Addressing (ABY)
@@ -2115,18 +2388,11 @@ impl M6502AddrModes for CPU {
     /// // since Y was added to the absolute address.
     /// ```
     fn ABY(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        let lo: u16 = bus.read(cpu.pc as u16, false).into();
-        cpu.pc += 1;
-        let hi: u16 = bus.read(cpu.pc as u16, false).into();
-        cpu.pc += 1;
-        cpu.abs = ((hi << 8) | lo) as u16;
-        cpu.abs += cpu.y as u16;
-
-        return if (cpu.abs & LOW_BYTE) != (hi << 8) as u16 {
-            0x01
-        } else {
-            0x00
-        };
+        let base: u16 = cpu.read_operand_word(bus);
+        cpu.abs = base.wrapping_add(cpu.y as u16);
+
+        // crossing into the next page costs a cycle
+        (cpu.abs & HIGH_BYTE != base & HIGH_BYTE) as u8
     }
 
     /// Relative Addressing (REL)
@@ -2164,9 +2430,10 @@ impl M6502AddrModes for CPU {
     /// ```
     fn REL(cpu: &mut CPU, bus: &mut Bus) -> u8 {
         cpu.rel = bus.read(cpu.pc, false) as u16;
-        cpu.pc += 1;
-        if (cpu.rel & 0x08) != 0 {
-            cpu.abs |= LOW_BYTE;
+        cpu.pc = cpu.pc.wrapping_add(1);
+        // sign extend the offset
+        if (cpu.rel & 0x80) != 0 {
+            cpu.rel |= HIGH_BYTE;
         }
         0x00
     }
@@ -2199,24 +2466,14 @@ impl M6502AddrModes for CPU {
     /// assert_eq!(result, 0x00);
     ///```
     fn IND(cpu: &mut CPU, bus: &mut Bus) -> u8 {
-        let pointer_lo = bus.read(cpu.pc, false) as u16;
-        cpu.pc += 1;
-        let pointer_hi = bus.read(cpu.pc as u16, false) as u16;
-        cpu.pc += 1;
-
-        let ptr: u16 = (pointer_hi << 8u16) | pointer_lo;
-
-        let lo: u32;
-        let hi: u32;
-        if pointer_lo == LOW_BYTE {
-            lo = (bus.read(ptr & LOW_BYTE, false) as u32) << 8;
-            hi = bus.read(ptr + 0, false).into();
-            cpu.abs = (lo | hi) as u16;
-        } else {
-            lo = (bus.read(ptr + 1, false) as u32) << 8;
-            hi = bus.read(ptr + 0, false).into();
-            cpu.abs = (lo | hi) as u16;
-        }
+        let ptr: u16 = cpu.read_operand_word(bus);
+
+        // hardware bug: the pointer's high byte is never carried into, a
+        // pointer at $xxFF reads its high byte from $xx00
+        let hi_ptr: u16 = (ptr & HIGH_BYTE) | (ptr.wrapping_add(1) & LOW_BYTE);
+        let lo: u16 = bus.read(ptr, false) as u16;
+        let hi: u16 = bus.read(hi_ptr, false) as u16;
+        cpu.abs = (hi << 8) | lo;
         0x00
     }
 
@@ -2264,12 +2521,14 @@ impl M6502AddrModes for CPU {
     /// ```
     fn IZX(cpu: &mut CPU, bus: &mut Bus) -> u8 {
         let t: u8 = bus.read(cpu.pc, false);
-        cpu.pc += 1;
+        cpu.pc = cpu.pc.wrapping_add(1);
 
-        let lo: u32 = bus.read((t + cpu.x) as u16 & LOW_BYTE, false).into();
-        let hi: u32 = bus.read((t + cpu.x + 1) as u16 & LOW_BYTE, false).into();
+        // the pointer lives in the zero page and wraps around it
+        let ptr: u8 = t.wrapping_add(cpu.x);
+        let lo: u16 = bus.read(ptr as u16, false) as u16;
+        let hi: u16 = bus.read(ptr.wrapping_add(1) as u16, false) as u16;
 
-        cpu.abs = ((hi << 8u8) | lo << 8u8) as u16 >> 8u16;
+        cpu.abs = (hi << 8) | lo;
         0x00
     }
 
@@ -2289,18 +2548,23 @@ impl M6502AddrModes for CPU {
     /// the operation resulted in a page boundary crossing.
     fn IZY(cpu: &mut CPU, bus: &mut Bus) -> u8 {
         let t: u8 = bus.read(cpu.pc, false);
-        cpu.pc += 1;
-
-        let lo: u8 = bus.read((t + cpu.y) as u16 & LOW_BYTE, false);
-        let hi: u8 = bus.read((t + cpu.y + 1) as u16 & LOW_BYTE, false);
+        cpu.pc = cpu.pc.wrapping_add(1);
 
-        cpu.abs = (((hi as u16) << 8u16) | (lo as u16) << 8u16) as u16;
-        cpu.abs += cpu.y as u16;
+        let lo: u16 = bus.read(t as u16, false) as u16;
+        let hi: u16 = bus.read(t.wrapping_add(1) as u16, false) as u16;
+        let base: u16 = (hi << 8) | lo;
+        cpu.abs = base.wrapping_add(cpu.y as u16);
 
-        return if (cpu.abs & HIGH_BYTE) != ((hi as u16) << 8u8) as u16 {
-            0x01
-        } else {
-            0x00
-        };
+        // crossing into the next page costs a cycle
+        (cpu.abs & HIGH_BYTE != base & HIGH_BYTE) as u8
     }
 }
+
+/// Reads a little endian address from one of the vectors at the top of
+/// memory.
+#[inline]
+fn read_vector(bus: &mut Bus, vector: u16) -> u16 {
+    let low: u16 = bus.read(vector, false) as u16;
+    let high: u16 = bus.read(vector.wrapping_add(1), false) as u16;
+    (high << 8) | low
+}
@@ -1,5 +1,24 @@
+use super::cartridge::{Cartrige, Mirroring, PpuAccess};
 use super::KB;
 
+// PPUCTRL ($2000)
+const CTRL_INCREMENT_MODE: u8 = 1 << 2;
+const CTRL_SPRITE_TABLE: u8 = 1 << 3;
+const CTRL_BACKGROUND_TABLE: u8 = 1 << 4;
+const CTRL_SPRITE_SIZE: u8 = 1 << 5;
+const CTRL_NMI_ENABLE: u8 = 1 << 7;
+// PPUMASK ($2001)
+const MASK_SHOW_BACKGROUND: u8 = 1 << 3;
+const MASK_SHOW_SPRITES: u8 = 1 << 4;
+// PPUSTATUS ($2002)
+const STATUS_SPRITE_OVERFLOW: u8 = 1 << 5;
+const STATUS_SPRITE_ZERO_HIT: u8 = 1 << 6;
+const STATUS_VERTICAL_BLANK: u8 = 1 << 7;
+
+const LAST_CYCLE: u16 = 340;
+const VBLANK_SCANLINE: u16 = 241;
+const PRE_RENDER_SCANLINE: u16 = 261;
+
 // this is a big boy struct
 #[allow(non_snake_case)]
 #[derive(Debug)]
@@ -9,4 +28,536 @@ pub struct PPU {
     table_palette: [u8; 32],
     scan_line: u16,
     cycle: u16,
+
+    // cpu facing registers
+    ctrl: u8,   // $2000 PPUCTRL
+    mask: u8,   // $2001 PPUMASK
+    status: u8, // $2002 PPUSTATUS
+    oam_addr: u8,
+    oam: [u8; 256],
+
+    // internal registers
+    address_latch: bool, // w: first or second write of $2005/$2006
+    data_buffer: u8,     // delayed $2007 read
+    vram_addr: u16,      // v: current vram address
+    tram_addr: u16,      // t: temporary vram address
+    fine_x: u8,          // x: fine x scroll
+
+    // rendering state
+    odd_frame: bool,
+    bg_next_tile_id: u8,
+    sprite_tiles: [(u8, u8); 8], // (tile, row) for next line's sprites
+    pub frame_complete: bool,
+}
+impl PPU {
+    pub fn new() -> Self {
+        Self {
+            table_name: [[0u8; KB(1)]; 2],
+            table_pattern: [[0u8; KB(4)]; 2],
+            table_palette: [0u8; 32],
+            scan_line: 0,
+            cycle: 0,
+            ctrl: 0x00,
+            mask: 0x00,
+            status: 0x00,
+            oam_addr: 0x00,
+            oam: [0u8; 256],
+            address_latch: false,
+            data_buffer: 0x00,
+            vram_addr: 0x0000,
+            tram_addr: 0x0000,
+            fine_x: 0x00,
+            odd_frame: false,
+            bg_next_tile_id: 0x00,
+            sprite_tiles: [(0xFF, 0); 8],
+            frame_complete: false,
+        }
+    }
+
+    /// Advances the PPU by one dot.
+    ///
+    /// Only the memory side of rendering is emulated: while the background
+    /// or sprites are enabled the PPU walks its bus with the same nametable,
+    /// attribute and pattern fetches as the real chip, so mappers watching
+    /// the address lines (MMC3's A12) see the right activity.
+    ///
+    /// ```no_run
+    /// scanlines 0-239  visible
+    /// scanline  240    post-render
+    /// scanlines 241-260 vertical blank
+    /// scanline  261    pre-render
+    /// dots      1-256  background fetches, 8 dots per tile
+    /// dots    257-320  sprite pattern fetches for the next line
+    /// dots    321-336  first two background tiles of the next line
+    /// dots    337-340  two unused nametable fetches
+    /// ```
+    pub fn clock(&mut self, mut cart: Option<&mut Cartrige>) {
+        let rendering: bool =
+            self.mask & (MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES) != 0;
+        let pre_render: bool = self.scan_line == PRE_RENDER_SCANLINE;
+        let fetching: bool = self.scan_line < 240 || pre_render;
+
+        if pre_render && self.cycle == 1 {
+            self.status &= !(STATUS_VERTICAL_BLANK
+                | STATUS_SPRITE_ZERO_HIT
+                | STATUS_SPRITE_OVERFLOW);
+        }
+
+        if fetching && rendering {
+            match self.cycle {
+                1..=256 | 321..=336 => {
+                    self.fetch_background(cart.as_deref_mut());
+                }
+                257..=320 => {
+                    if self.cycle == 257 {
+                        self.transfer_address_x();
+                        self.evaluate_sprites();
+                    }
+                    self.fetch_sprite(cart.as_deref_mut());
+                }
+                337 | 339 => {
+                    self.bg_next_tile_id = self.ppu_read(
+                        cart.as_deref_mut(),
+                        0x2000 | (self.vram_addr & 0x0FFF),
+                    );
+                }
+                _ => (),
+            }
+            if self.cycle == 256 {
+                self.increment_scroll_y();
+            }
+            if pre_render && (280..=304).contains(&self.cycle) {
+                self.transfer_address_y();
+            }
+            if self.cycle == 260 {
+                if let Some(cart) = &mut cart {
+                    cart.scanline();
+                }
+            }
+        }
+
+        if self.scan_line == VBLANK_SCANLINE && self.cycle == 1 {
+            self.status |= STATUS_VERTICAL_BLANK;
+        }
+
+        // the pre-render line is one dot shorter on odd frames
+        if pre_render && self.cycle == 339 && rendering && self.odd_frame {
+            self.cycle = LAST_CYCLE;
+        }
+
+        self.cycle += 1;
+        if self.cycle > LAST_CYCLE {
+            self.cycle = 0;
+            self.scan_line += 1;
+            if self.scan_line > PRE_RENDER_SCANLINE {
+                self.scan_line = 0;
+                self.odd_frame = !self.odd_frame;
+                self.frame_complete = true;
+            }
+        }
+    }
+
+    /// One of the four background fetches that make up each 8 dot tile
+    /// slot, followed by the coarse x increment on the last dot.
+    fn fetch_background(&mut self, cart: Option<&mut Cartrige>) {
+        let v: u16 = self.vram_addr;
+        match (self.cycle - 1) % 8 {
+            0 => {
+                self.bg_next_tile_id =
+                    self.ppu_read(cart, 0x2000 | (v & 0x0FFF));
+            }
+            2 => {
+                self.ppu_read(
+                    cart,
+                    0x23C0
+                        | (v & 0x0C00)
+                        | ((v >> 4) & 0x38)
+                        | ((v >> 2) & 0x07),
+                );
+            }
+            4 | 6 => {
+                let table: u16 = match self.ctrl & CTRL_BACKGROUND_TABLE {
+                    0 => 0x0000,
+                    _ => 0x1000,
+                };
+                let plane: u16 = if (self.cycle - 1) % 8 == 6 { 8 } else { 0 };
+                self.ppu_read(
+                    cart,
+                    table
+                        | ((self.bg_next_tile_id as u16) << 4)
+                        | ((v >> 12) & 0x07)
+                        | plane,
+                );
+            }
+            7 => self.increment_scroll_x(),
+            _ => (),
+        }
+    }
+
+    /// Sprite slots fetch two garbage nametable bytes and then the two
+    /// pattern planes of the sprite found during evaluation.
+    fn fetch_sprite(&mut self, cart: Option<&mut Cartrige>) {
+        let slot: usize = ((self.cycle - 257) / 8) as usize;
+        match (self.cycle - 257) % 8 {
+            0 | 2 => {
+                self.ppu_read(cart, 0x2000 | (self.vram_addr & 0x0FFF));
+            }
+            4 | 6 => {
+                let (tile, row) = self.sprite_tiles[slot];
+                let plane: u16 =
+                    if (self.cycle - 257) % 8 == 6 { 8 } else { 0 };
+                let addr: u16 = if self.ctrl & CTRL_SPRITE_SIZE == 0 {
+                    let table: u16 = match self.ctrl & CTRL_SPRITE_TABLE {
+                        0 => 0x0000,
+                        _ => 0x1000,
+                    };
+                    table | ((tile as u16) << 4) | (row & 0x07) as u16
+                } else {
+                    // 8x16 sprites pick their table with bit 0 of the tile
+                    let table: u16 = ((tile & 0x01) as u16) << 12;
+                    let tile: u16 = ((tile & 0xFE) | (row >> 3)) as u16;
+                    table | (tile << 4) | (row & 0x07) as u16
+                };
+                self.ppu_read(cart, addr | plane);
+            }
+            _ => (),
+        }
+    }
+
+    /// Finds the first eight sprites on the next scanline. Unused slots
+    /// fetch tile $FF like the hardware does.
+    fn evaluate_sprites(&mut self) {
+        let next_line: i16 = match self.scan_line {
+            PRE_RENDER_SCANLINE => 0,
+            line => line as i16 + 1,
+        };
+        let height: i16 = match self.ctrl & CTRL_SPRITE_SIZE {
+            0 => 8,
+            _ => 16,
+        };
+        self.sprite_tiles = [(0xFF, 0); 8];
+        let mut found: usize = 0;
+        for sprite in self.oam.chunks_exact(4) {
+            let row: i16 = next_line - sprite[0] as i16 - 1;
+            if (0..height).contains(&row) {
+                if found == 8 {
+                    self.status |= STATUS_SPRITE_OVERFLOW;
+                    break;
+                }
+                // vertical flip
+                let row: i16 = match sprite[2] & 0x80 {
+                    0 => row,
+                    _ => height - 1 - row,
+                };
+                self.sprite_tiles[found] = (sprite[1], row as u8);
+                found += 1;
+            }
+        }
+    }
+
+    #[inline]
+    fn increment_scroll_x(&mut self) {
+        if self.vram_addr & 0x001F == 31 {
+            self.vram_addr &= !0x001F;
+            self.vram_addr ^= 0x0400;
+        } else {
+            self.vram_addr += 1;
+        }
+    }
+
+    #[inline]
+    fn increment_scroll_y(&mut self) {
+        if self.vram_addr & 0x7000 != 0x7000 {
+            self.vram_addr += 0x1000;
+            return;
+        }
+        self.vram_addr &= !0x7000;
+        let mut coarse_y: u16 = (self.vram_addr & 0x03E0) >> 5;
+        match coarse_y {
+            29 => {
+                coarse_y = 0;
+                self.vram_addr ^= 0x0800;
+            }
+            31 => coarse_y = 0,
+            _ => coarse_y += 1,
+        }
+        self.vram_addr = (self.vram_addr & !0x03E0) | (coarse_y << 5);
+    }
+
+    #[inline]
+    fn transfer_address_x(&mut self) {
+        self.vram_addr = (self.vram_addr & !0x041F) | (self.tram_addr & 0x041F);
+    }
+
+    #[inline]
+    fn transfer_address_y(&mut self) {
+        self.vram_addr = (self.vram_addr & !0x7BE0) | (self.tram_addr & 0x7BE0);
+    }
+
+    /// Reads one of the eight PPU registers, `addr` is already reduced to
+    /// 0..=7 by the bus. Reading PPUSTATUS and PPUDATA have side effects
+    /// which are skipped when `read_only` is set so debuggers can peek.
+    pub fn cpu_read(
+        &mut self,
+        mut cart: Option<&mut Cartrige>,
+        addr: u16,
+        read_only: bool,
+    ) -> u8 {
+        match addr {
+            // PPUSTATUS: top three bits are real, the rest is stale bus
+            0x0002 => {
+                let data: u8 = (self.status & 0xE0) | (self.data_buffer & 0x1F);
+                if !read_only {
+                    self.status &= !STATUS_VERTICAL_BLANK;
+                    self.address_latch = false;
+                }
+                data
+            }
+            // OAMDATA
+            0x0004 => self.oam[self.oam_addr as usize],
+            // PPUDATA: reads below the palette are delayed by one read
+            0x0007 if read_only => self.data_buffer,
+            0x0007 => {
+                let mut data: u8 = self.data_buffer;
+                self.data_buffer =
+                    self.ppu_read(cart.as_deref_mut(), self.vram_addr);
+                if self.vram_addr & 0x3FFF >= 0x3F00 {
+                    data = self.data_buffer;
+                }
+                self.increment_vram_addr(cart);
+                data
+            }
+            // PPUCTRL, PPUMASK, OAMADDR, PPUSCROLL and PPUADDR are write only
+            _ => 0x00,
+        }
+    }
+
+    /// Writes one of the eight PPU registers, `addr` is already reduced to
+    /// 0..=7 by the bus.
+    pub fn cpu_write(
+        &mut self,
+        mut cart: Option<&mut Cartrige>,
+        addr: u16,
+        data: u8,
+    ) {
+        match addr {
+            // PPUCTRL
+            0x0000 => {
+                self.ctrl = data;
+                // nametable select lives in t as well
+                self.tram_addr =
+                    (self.tram_addr & !0x0C00) | (((data & 0x03) as u16) << 10);
+            }
+            // PPUMASK
+            0x0001 => self.mask = data,
+            // PPUSTATUS is read only
+            0x0002 => (),
+            // OAMADDR
+            0x0003 => self.oam_addr = data,
+            // OAMDATA
+            0x0004 => {
+                self.oam[self.oam_addr as usize] = data;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            // PPUSCROLL: x then y
+            0x0005 => {
+                if !self.address_latch {
+                    self.fine_x = data & 0x07;
+                    self.tram_addr =
+                        (self.tram_addr & !0x001F) | (data >> 3) as u16;
+                } else {
+                    self.tram_addr = (self.tram_addr & !0x73E0)
+                        | (((data & 0x07) as u16) << 12)
+                        | (((data >> 3) as u16) << 5);
+                }
+                self.address_latch = !self.address_latch;
+            }
+            // PPUADDR: high byte then low byte
+            0x0006 => {
+                if !self.address_latch {
+                    self.tram_addr = (self.tram_addr & 0x00FF)
+                        | (((data & 0x3F) as u16) << 8);
+                } else {
+                    self.tram_addr = (self.tram_addr & 0xFF00) | data as u16;
+                    self.vram_addr = self.tram_addr;
+                    if let Some(cart) = cart {
+                        cart.ppu_address(self.vram_addr & 0x3FFF);
+                    }
+                }
+                self.address_latch = !self.address_latch;
+            }
+            // PPUDATA
+            0x0007 => {
+                self.ppu_write(cart.as_deref_mut(), self.vram_addr, data);
+                self.increment_vram_addr(cart);
+            }
+            _ => (),
+        }
+    }
+
+    /// Copies a full page into OAM, used by the $4014 OAMDMA port.
+    pub fn oam_dma(&mut self, page: &[u8; 256]) {
+        for byte in page.iter() {
+            self.oam[self.oam_addr as usize] = *byte;
+            self.oam_addr = self.oam_addr.wrapping_add(1);
+        }
+    }
+
+    /// Reads the PPU's own 14-bit address space. The cartridge gets the
+    /// first say on every address, whatever it leaves alone is served from
+    /// the console's own memory.
+    ///
+    /// ```no_run
+    /// $0000-$1FFF  pattern tables
+    /// $2000-$2FFF  nametables ($3000-$3EFF mirrors them)
+    /// $3F00-$3F1F  palette ($3F20-$3FFF mirrors it)
+    /// ```
+    pub fn ppu_read(&self, mut cart: Option<&mut Cartrige>, addr: u16) -> u8 {
+        let addr: u16 = addr & 0x3FFF;
+        let mut data: u8 = 0x00;
+        if let Some(cart) = cart.as_deref_mut() {
+            match cart.ppu_read(addr, &mut data) {
+                PpuAccess::CARTRIDGE => return data,
+                PpuAccess::CIRAM(offset) => {
+                    let (table, offset) = ciram_index(offset);
+                    return self.table_name[table][offset];
+                }
+                PpuAccess::CONSOLE => (),
+            }
+        }
+        match addr {
+            0x0000..=0x1FFF => {
+                self.table_pattern[((addr & 0x1000) >> 12) as usize]
+                    [(addr & 0x0FFF) as usize]
+            }
+            0x2000..=0x3EFF => {
+                let (table, offset) = nametable_index(mirroring(&cart), addr);
+                self.table_name[table][offset]
+            }
+            _ => self.table_palette[palette_index(addr)],
+        }
+    }
+
+    pub fn ppu_write(
+        &mut self,
+        mut cart: Option<&mut Cartrige>,
+        addr: u16,
+        data: u8,
+    ) {
+        let addr: u16 = addr & 0x3FFF;
+        if let Some(cart) = cart.as_deref_mut() {
+            match cart.ppu_write(addr, data) {
+                PpuAccess::CARTRIDGE => return,
+                PpuAccess::CIRAM(offset) => {
+                    let (table, offset) = ciram_index(offset);
+                    self.table_name[table][offset] = data;
+                    return;
+                }
+                PpuAccess::CONSOLE => (),
+            }
+        }
+        match addr {
+            0x0000..=0x1FFF => {
+                self.table_pattern[((addr & 0x1000) >> 12) as usize]
+                    [(addr & 0x0FFF) as usize] = data;
+            }
+            0x2000..=0x3EFF => {
+                let (table, offset) = nametable_index(mirroring(&cart), addr);
+                self.table_name[table][offset] = data;
+            }
+            _ => self.table_palette[palette_index(addr)] = data,
+        }
+    }
+
+    /// Steps v after a $2007 access, the new address shows up on the PPU
+    /// bus so the mapper gets to see it.
+    #[inline]
+    fn increment_vram_addr(&mut self, cart: Option<&mut Cartrige>) {
+        let step: u16 = if self.ctrl & CTRL_INCREMENT_MODE != 0 {
+            32
+        } else {
+            1
+        };
+        self.vram_addr = self.vram_addr.wrapping_add(step) & 0x7FFF;
+        if let Some(cart) = cart {
+            cart.ppu_address(self.vram_addr & 0x3FFF);
+        }
+    }
+
+    /// Level of the /NMI output: asserted while the vertical blank flag
+    /// is up and PPUCTRL has NMIs enabled. Enabling NMIs in the middle of
+    /// vblank therefore produces another edge, and another NMI.
+    pub const fn nmi(&self) -> bool {
+        self.status & STATUS_VERTICAL_BLANK != 0
+            && self.ctrl & CTRL_NMI_ENABLE != 0
+    }
+
+    pub const fn ctrl(&self) -> u8 {
+        self.ctrl
+    }
+
+    pub const fn mask(&self) -> u8 {
+        self.mask
+    }
+
+    pub const fn status(&self) -> u8 {
+        self.status
+    }
+
+    pub const fn vram_addr(&self) -> u16 {
+        self.vram_addr
+    }
+
+    pub const fn scan_line(&self) -> u16 {
+        self.scan_line
+    }
+
+    pub const fn cycle(&self) -> u16 {
+        self.cycle
+    }
+}
+
+/// Mirroring is owned by the cartridge, with nothing plugged in the PPU
+/// defaults to horizontal arrangement.
+#[inline]
+fn mirroring(cart: &Option<&mut Cartrige>) -> Mirroring {
+    match cart {
+        Some(cart) => cart.mirror(),
+        None => Mirroring::HORIZONTAL,
+    }
+}
+
+/// Folds the four logical nametables onto the 2KB of CIRAM the console
+/// actually has, according to the cartridge's mirroring.
+#[inline]
+const fn nametable_index(mirror: Mirroring, addr: u16) -> (usize, usize) {
+    let addr: u16 = addr & 0x0FFF;
+    let offset: usize = (addr & 0x03FF) as usize;
+    let table: usize = match mirror {
+        Mirroring::VERTICAL => ((addr >> 10) & 0x01) as usize,
+        Mirroring::HORIZONTAL => ((addr >> 11) & 0x01) as usize,
+        Mirroring::ONESCREAN_LO => 0,
+        Mirroring::ONESCREAN_HI => 1,
+        // four screen boards bring their own VRAM, without it the best we
+        // can do is fall back to vertical arrangement
+        Mirroring::FOUR_SCREEN => ((addr >> 10) & 0x01) as usize,
+    };
+    (table, offset)
+}
+
+/// Splits a CIRAM offset chosen by the cartridge into page and offset.
+#[inline]
+const fn ciram_index(offset: usize) -> (usize, usize) {
+    ((offset >> 10) & 0x01, offset & 0x03FF)
+}
+
+/// The backdrop entries of the sprite palettes ($3F10/$14/$18/$1C) are
+/// mirrors of the background ones.
+#[inline]
+const fn palette_index(addr: u16) -> usize {
+    let index: u16 = addr & 0x001F;
+    match index {
+        0x10 | 0x14 | 0x18 | 0x1C => (index & 0x000F) as usize,
+        _ => index as usize,
+    }
 }
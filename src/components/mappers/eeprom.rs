@@ -0,0 +1,203 @@
+//! Serial EEPROMs found on Bandai boards, emulated at the level of the two
+//! I2C lines the mapper drives.
+
+/// Which chip is fitted. The 24C01 takes a 7-bit word address in its first
+/// byte and sends everything LSB first, the 24C02 has a proper device
+/// select byte followed by an 8-bit word address and is MSB first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum EepromChip {
+    C24C01,
+    C24C02,
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    IDLE,
+    DEVICE,  // first byte after a start condition
+    ADDRESS, // 24C02 word address
+    WRITE,
+    READ,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Eeprom {
+    chip: EepromChip,
+    data: [u8; 256],
+    mode: Mode,
+    next_mode: Mode,
+    address: u8,
+    bit: u8, // data bits clocked so far, 8-9 around the acknowledge
+    shift: u8,
+    nack: bool,
+    scl: bool,
+    sda: bool,
+    output: bool,
+}
+impl Eeprom {
+    pub(super) fn new(chip: EepromChip) -> Self {
+        Self {
+            chip,
+            data: [0xFF; 256],
+            mode: Mode::IDLE,
+            next_mode: Mode::IDLE,
+            address: 0x00,
+            bit: 0,
+            shift: 0x00,
+            nack: false,
+            scl: true,
+            sda: true,
+            output: true,
+        }
+    }
+
+    /// Bytes of storage, 128 or 256.
+    pub(super) fn size(&self) -> usize {
+        match self.chip {
+            EepromChip::C24C01 => 0x80,
+            EepromChip::C24C02 => 0x100,
+        }
+    }
+
+    pub(super) fn contents(&self) -> &[u8] {
+        &self.data[..self.size()]
+    }
+
+    pub(super) fn load(&mut self, data: &[u8]) {
+        let len: usize = data.len().min(self.size());
+        self.data[..len].copy_from_slice(&data[..len]);
+    }
+
+    /// Level the chip drives on SDA, it can only pull the line low.
+    pub(super) fn output(&self) -> bool {
+        self.output
+    }
+
+    /// New levels on the clock and data lines.
+    pub(super) fn write(&mut self, scl: bool, sda: bool) {
+        if self.scl && scl {
+            // data changing while the clock is high frames a transfer
+            if self.sda && !sda {
+                self.mode = Mode::DEVICE;
+                self.bit = 0;
+                self.output = true;
+            } else if !self.sda && sda {
+                self.mode = Mode::IDLE;
+                self.output = true;
+            }
+        } else if !self.scl && scl {
+            self.rising_edge(sda);
+        } else if self.scl && !scl {
+            self.falling_edge();
+        }
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    /// Data is sampled while the clock rises.
+    fn rising_edge(&mut self, sda: bool) {
+        match self.mode {
+            Mode::IDLE => (),
+            Mode::READ if self.bit == 8 => {
+                self.nack = sda;
+                self.bit = 9;
+            }
+            Mode::READ => self.bit += 1,
+            _ if self.bit < 8 => {
+                self.shift = match self.chip {
+                    EepromChip::C24C01 => (self.shift >> 1) | (sda as u8) << 7,
+                    EepromChip::C24C02 => (self.shift << 1) | sda as u8,
+                };
+                self.bit += 1;
+            }
+            _ => (),
+        }
+    }
+
+    /// The chip changes what it drives while the clock is low.
+    fn falling_edge(&mut self) {
+        match self.mode {
+            Mode::IDLE => (),
+            Mode::READ => match self.bit {
+                // let go of SDA for the master's acknowledge
+                8 => self.output = true,
+                9 if self.nack => {
+                    self.mode = Mode::IDLE;
+                    self.output = true;
+                }
+                9 => {
+                    self.address = self.wrap(self.address.wrapping_add(1));
+                    self.bit = 0;
+                    self.output_bit();
+                }
+                _ => self.output_bit(),
+            },
+            _ => match self.bit {
+                8 => {
+                    // acknowledge by pulling SDA low for the ninth clock
+                    self.output = !self.receive(self.shift);
+                    self.bit = 9;
+                }
+                9 => {
+                    self.output = true;
+                    self.bit = 0;
+                    self.mode = self.next_mode;
+                    if self.mode == Mode::READ {
+                        self.output_bit();
+                    }
+                }
+                _ => (),
+            },
+        }
+    }
+
+    fn output_bit(&mut self) {
+        let byte: u8 = self.data[self.address as usize];
+        let shift: u8 = match self.chip {
+            EepromChip::C24C01 => self.bit,
+            EepromChip::C24C02 => 7 - self.bit,
+        };
+        self.output = (byte >> shift) & 0x01 != 0;
+    }
+
+    #[inline]
+    fn wrap(&self, address: u8) -> u8 {
+        (address as usize % self.size()) as u8
+    }
+
+    /// Handles a complete byte, `false` when the chip does not acknowledge.
+    fn receive(&mut self, byte: u8) -> bool {
+        match (self.mode, self.chip) {
+            (Mode::DEVICE, EepromChip::C24C01) => {
+                let read: bool = byte & 0x80 != 0;
+                self.address = byte & 0x7F;
+                self.next_mode = if read { Mode::READ } else { Mode::WRITE };
+            }
+            (Mode::DEVICE, EepromChip::C24C02) => {
+                let read: bool = byte & 0x01 != 0;
+                if byte & 0xF0 != 0xA0 {
+                    self.next_mode = Mode::IDLE;
+                    return false;
+                }
+                self.next_mode = if read { Mode::READ } else { Mode::ADDRESS };
+            }
+            (Mode::ADDRESS, _) => {
+                self.address = byte;
+                self.next_mode = Mode::WRITE;
+            }
+            (Mode::WRITE, chip) => {
+                self.data[self.address as usize] = byte;
+                // writes wrap within a page of 4 (24C01) or 8 (24C02) bytes
+                let page: u8 = match chip {
+                    EepromChip::C24C01 => 0x03,
+                    EepromChip::C24C02 => 0x07,
+                };
+                self.address = (self.address & !page)
+                    | (self.address.wrapping_add(1) & page);
+                self.next_mode = Mode::WRITE;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
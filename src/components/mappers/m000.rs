@@ -1,13 +1,15 @@
-use super::mapper::{self, MapperData};
+use super::mapper::{Mapped, Mapper, MapperData};
 
+/// NROM: no bank switching at all.
 #[derive(Debug, Clone, Copy)]
 pub struct M000(MapperData);
-impl mapper::MapperFn for M000 {
-    fn new(prg_bank: u8, chr_bank: u8) -> Self {
-        Self(MapperData { prg_bank, chr_bank })
+impl M000 {
+    pub fn new(data: MapperData) -> Self {
+        Self(data)
     }
-
-    fn allow_cpu_read(&self, addr: u16, mapped_addr: &mut u32) -> bool {
+}
+impl Mapper for M000 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
         // if PRGROM is 16KB
         //     CPU Address Bus          PRG ROM
         //     0x8000 -> 0xBFFF: Map    0x0000 -> 0x3FFF
@@ -16,49 +18,46 @@ impl mapper::MapperFn for M000 {
         //     CPU Address Bus          PRG ROM
         //     0x8000 -> 0xFFFF: Map    0x0000 -> 0x7FFF
         match addr {
+            // Family Basic carries work ram here
+            0x6000..=0x7FFF if self.0.prg_ram_size > 0 => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
             0x8000..=0xFFFF => {
-                let mapping: u32 =
+                let mapping: u16 =
                     if self.0.prg_bank > 1 { 0x7FFF } else { 0x3FFF };
-                *mapped_addr = addr as u32 & mapping;
-                true
+                Mapped::Prg((addr & mapping) as usize)
             }
-            _ => false,
+            _ => Mapped::Unmapped,
         }
     }
 
-    fn allow_cpu_write(&self, addr: u16, mapped_addr: &mut u32) -> bool {
+    fn cpu_write(&mut self, addr: u16, _data: u8) -> Mapped {
         match addr {
-            0x8000..=0xFFFF => {
-                let mapping: u32 =
-                    if self.0.prg_bank > 1 { 0x7FFF } else { 0x3FFF };
-                *mapped_addr = addr as u32 & mapping;
-                true
+            0x6000..=0x7FFF if self.0.prg_ram_size > 0 => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
             }
-            _ => false,
+            // PRG is ROM, nothing to write to
+            _ => Mapped::Unmapped,
         }
     }
 
     //no mapping for ppu treat as RAM
-    fn allow_ppu_read(&self, addr: u16, mapped_addr: &mut u32) -> bool {
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
         // There is no mapping required for PPU
         // PPU Address Bus          CHR ROM
         // 0x0000 -> 0x1FFF: Map    0x0000 -> 0x1FFF
         match addr {
-            0x0000..=0x1FFF => {
-                *mapped_addr = addr as u32;
-                true
-            }
-            _ => false,
+            0x0000..=0x1FFF => Mapped::Chr(addr as usize),
+            _ => Mapped::Unmapped,
         }
     }
 
-    fn allow_ppu_write(&self, addr: u16, mapped_addr: &mut u32) -> bool {
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
         match addr {
             0x0000..=0x1FFF if self.0.chr_bank == 0 => {
-                *mapped_addr = addr as u32;
-                true
+                Mapped::Chr(addr as usize)
             }
-            _ => false,
+            _ => Mapped::Unmapped,
         }
     }
 }
@@ -0,0 +1,198 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// control register ($8000-$9FFF)
+const CONTROL_MIRRORING: u8 = 0x03;
+const CONTROL_PRG_MODE: u8 = 0x0C;
+const CONTROL_CHR_4KB: u8 = 0x10;
+// PRG register ($E000-$FFFF)
+const PRG_RAM_DISABLE: u8 = 0x10;
+
+/// MMC1 (SxROM). All registers are loaded serially through a 5-bit shift
+/// register: five writes to $8000-$FFFF shift in bit 0 of each value, the
+/// fifth write's address picks the target register.
+///
+/// ```no_run
+/// $8000-$9FFF  control  CPPMM  C: CHR mode, PP: PRG mode, MM: mirroring
+/// $A000-$BFFF  CHR bank 0 (4KB at $0000, or 8KB with bit 0 ignored)
+/// $C000-$DFFF  CHR bank 1 (4KB at $1000, ignored in 8KB mode)
+/// $E000-$FFFF  PRG bank  RPPPP  R: PRG RAM disable, PPPP: 16KB bank
+/// ```
+///
+/// Boards with more than 256KB PRG (SUROM) reuse CHR bank 0 bit 4 as the
+/// PRG A18 line, boards with more than 8KB PRG RAM (SOROM/SXROM) use CHR
+/// bank 0 bits 2-3 to select the 8KB RAM bank.
+#[derive(Debug, Clone, Copy)]
+pub struct M001 {
+    data: MapperData,
+    shift: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+    // the serial port ignores writes on back to back cpu cycles, which is
+    // what the dummy write of a read-modify-write instruction looks like
+    cycle: u64,
+    last_write: Option<u64>,
+}
+impl M001 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            shift: 0x00,
+            shift_count: 0,
+            control: CONTROL_PRG_MODE,
+            chr_bank_0: 0x00,
+            chr_bank_1: 0x00,
+            prg_bank: 0x00,
+            cycle: 0,
+            last_write: None,
+        }
+    }
+
+    #[inline]
+    fn prg_ram_enabled(&self) -> bool {
+        self.data.prg_ram_size > 0 && self.prg_bank & PRG_RAM_DISABLE == 0
+    }
+
+    #[inline]
+    fn prg_ram_offset(&self, addr: u16) -> usize {
+        let bank: usize = match self.data.prg_ram_size > 0x2000 {
+            true => ((self.chr_bank_0 >> 2) & 0x03) as usize,
+            false => 0,
+        };
+        bank * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    /// 16KB bank visible at `addr`, including the SUROM outer bank.
+    fn prg_bank_at(&self, addr: u16) -> usize {
+        let outer: usize = match self.data.prg_bank > 16 {
+            true => (self.chr_bank_0 & 0x10) as usize,
+            false => 0,
+        };
+        let bank: u8 = self.prg_bank & 0x0F;
+        let upper: bool = addr >= 0xC000;
+        let inner: u8 = match (self.control & CONTROL_PRG_MODE) >> 2 {
+            // 32KB mode, low bit of the bank number is ignored
+            0 | 1 => (bank & 0x0E) | upper as u8,
+            // first bank fixed at $8000, switch $C000
+            2 => match upper {
+                true => bank,
+                false => 0x00,
+            },
+            // switch $8000, last bank fixed at $C000
+            _ => match upper {
+                true => 0x0F,
+                false => bank,
+            },
+        };
+        outer | inner as usize
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        if self.control & CONTROL_CHR_4KB == 0 {
+            let bank: usize = (self.chr_bank_0 & 0x1E) as usize;
+            bank * 0x1000 + (addr & 0x1FFF) as usize
+        } else {
+            let bank: usize = match addr < 0x1000 {
+                true => self.chr_bank_0 as usize,
+                false => self.chr_bank_1 as usize,
+            };
+            bank * 0x1000 + (addr & 0x0FFF) as usize
+        }
+    }
+
+    fn load_register(&mut self, addr: u16, data: u8) {
+        let consecutive: bool =
+            matches!(self.last_write, Some(last) if self.cycle <= last + 1);
+        self.last_write = Some(self.cycle);
+        if consecutive {
+            return;
+        }
+
+        if data & 0x80 != 0 {
+            // reset the shift register and lock the last bank at $C000
+            self.shift = 0x00;
+            self.shift_count = 0;
+            self.control |= CONTROL_PRG_MODE;
+            return;
+        }
+
+        self.shift = (self.shift >> 1) | ((data & 0x01) << 4);
+        self.shift_count += 1;
+        if self.shift_count == 5 {
+            match addr {
+                0x8000..=0x9FFF => self.control = self.shift,
+                0xA000..=0xBFFF => self.chr_bank_0 = self.shift,
+                0xC000..=0xDFFF => self.chr_bank_1 = self.shift,
+                _ => self.prg_bank = self.shift,
+            }
+            self.shift = 0x00;
+            self.shift_count = 0;
+        }
+    }
+}
+impl Mapper for M001 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Mapped::PrgRam(self.prg_ram_offset(addr))
+            }
+            0x8000..=0xFFFF => Mapped::Prg(
+                self.prg_bank_at(addr) * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Mapped::PrgRam(self.prg_ram_offset(addr))
+            }
+            0x8000..=0xFFFF => {
+                self.load_register(addr, data);
+                Mapped::Data(data)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(match self.control & CONTROL_MIRRORING {
+            0 => Mirroring::ONESCREAN_LO,
+            1 => Mirroring::ONESCREAN_HI,
+            2 => Mirroring::VERTICAL,
+            _ => Mirroring::HORIZONTAL,
+        })
+    }
+
+    fn cpu_clock(&mut self) {
+        self.cycle += 1;
+    }
+
+    fn reset(&mut self) {
+        self.shift = 0x00;
+        self.shift_count = 0;
+        self.control |= CONTROL_PRG_MODE;
+        self.last_write = None;
+    }
+}
@@ -0,0 +1,72 @@
+use super::mapper::{Mapped, Mapper, MapperData};
+
+/// UxROM: a switchable 16KB bank at $8000 with the last bank fixed at
+/// $C000. Any write to $8000-$FFFF selects the bank.
+///
+/// NES 2.0 submapper 2 marks boards where the ROM drives the data bus
+/// during the write, so the value written is ANDed with the ROM byte at
+/// that address.
+#[derive(Debug, Clone, Copy)]
+pub struct M002 {
+    data: MapperData,
+    prg_bank_lo: u8,
+}
+impl M002 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            prg_bank_lo: 0x00,
+        }
+    }
+}
+impl Mapper for M002 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        // CPU Address Bus          PRG ROM
+        // 0x8000 -> 0xBFFF: Map    selected 16KB bank
+        // 0xC000 -> 0xFFFF: Map    last 16KB bank
+        match addr {
+            0x8000..=0xBFFF => Mapped::Prg(
+                self.prg_bank_lo as usize * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            0xC000..=0xFFFF => {
+                let last: usize = self.data.prg_bank.saturating_sub(1) as usize;
+                Mapped::Prg(last * 0x4000 + (addr & 0x3FFF) as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => {
+                self.prg_bank_lo = data;
+                Mapped::Data(data)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(addr as usize),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(addr as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn bus_conflicts(&self) -> bool {
+        self.data.submapper == 2
+    }
+
+    fn reset(&mut self) {
+        self.prg_bank_lo = 0x00;
+    }
+}
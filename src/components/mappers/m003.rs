@@ -0,0 +1,72 @@
+use super::mapper::{Mapped, Mapper, MapperData};
+
+/// CNROM: fixed 16/32KB of PRG like NROM, any write to $8000-$FFFF selects
+/// the 8KB CHR bank.
+///
+/// NES 2.0 submapper 2 marks boards where the ROM drives the data bus
+/// during the write, so the value written is ANDed with the ROM byte at
+/// that address.
+#[derive(Debug, Clone, Copy)]
+pub struct M003 {
+    data: MapperData,
+    chr_bank: u8,
+}
+impl M003 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            chr_bank: 0x00,
+        }
+    }
+}
+impl Mapper for M003 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => {
+                let mapping: u16 = if self.data.prg_bank > 1 {
+                    0x7FFF
+                } else {
+                    0x3FFF
+                };
+                Mapped::Prg((addr & mapping) as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => {
+                self.chr_bank = data;
+                Mapped::Data(data)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(
+                self.chr_bank as usize * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => Mapped::Chr(
+                self.chr_bank as usize * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn bus_conflicts(&self) -> bool {
+        self.data.submapper == 2
+    }
+
+    fn reset(&mut self) {
+        self.chr_bank = 0x00;
+    }
+}
@@ -0,0 +1,235 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// bank select ($8000, even)
+const SELECT_REGISTER: u8 = 0x07;
+const SELECT_PRG_MODE: u8 = 0x40;
+const SELECT_CHR_INVERSION: u8 = 0x80;
+// PRG RAM protect ($A001, odd)
+const RAM_WRITE_DENY: u8 = 0x40;
+const RAM_ENABLE: u8 = 0x80;
+
+/// A12 has to sit low for this many cpu cycles before a rising edge is
+/// counted, which filters out the short dips between sprite fetches.
+const A12_FILTER_CYCLES: u64 = 3;
+
+/// MMC3 (TxROM). Registers are decoded by address range and A0:
+///
+/// ```no_run
+/// $8000 even  bank select   CPxx xRRR  C: CHR inversion, P: PRG mode
+/// $8001 odd   bank data     value for R0-R7
+/// $A000 even  mirroring     0: vertical, 1: horizontal
+/// $A001 odd   PRG RAM protect  EWxx xxxx  E: enable, W: deny writes
+/// $C000 even  IRQ latch
+/// $C001 odd   IRQ reload
+/// $E000 even  IRQ disable (and acknowledge)
+/// $E001 odd   IRQ enable
+/// ```
+///
+/// CHR is split into two 2KB banks (R0, R1) and four 1KB banks (R2-R5),
+/// the inversion bit swaps which pattern table gets the 2KB ones. PRG has
+/// two switchable 8KB banks (R6, R7) and the last bank fixed at $E000, the
+/// PRG mode swaps R6 with the second to last bank.
+///
+/// The scanline counter is clocked by rising edges on PPU A12, which the
+/// PPU produces once per line when backgrounds use $0000 and sprites use
+/// $1000. NES 2.0 submapper 4 selects the older MMC3A behaviour where a
+/// counter reloaded with zero does not raise an IRQ.
+#[derive(Debug, Clone, Copy)]
+pub struct M004 {
+    data: MapperData,
+    bank_select: u8,
+    registers: [u8; 8],
+    mirror: Mirroring,
+    ram_protect: u8,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_active: bool,
+
+    cycle: u64,
+    a12: bool,
+    a12_low_since: u64,
+}
+impl M004 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            bank_select: 0x00,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            mirror: Mirroring::VERTICAL,
+            ram_protect: RAM_ENABLE,
+            irq_latch: 0x00,
+            irq_counter: 0x00,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_active: false,
+            cycle: 0,
+            a12: false,
+            a12_low_since: 0,
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let second_last: usize =
+            (self.data.prg_bank as usize * 2).saturating_sub(2);
+        let swap: bool = self.bank_select & SELECT_PRG_MODE != 0;
+        let bank: usize = match (addr >> 13) & 0x03 {
+            0 if swap => second_last,
+            0 => (self.registers[6] & 0x3F) as usize,
+            1 => (self.registers[7] & 0x3F) as usize,
+            2 if swap => (self.registers[6] & 0x3F) as usize,
+            2 => second_last,
+            _ => second_last + 1,
+        };
+        bank * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let addr: u16 = match self.bank_select & SELECT_CHR_INVERSION {
+            0 => addr,
+            _ => addr ^ 0x1000,
+        };
+        let bank: usize = match addr >> 10 {
+            0 => (self.registers[0] & 0xFE) as usize,
+            1 => (self.registers[0] | 0x01) as usize,
+            2 => (self.registers[1] & 0xFE) as usize,
+            3 => (self.registers[1] | 0x01) as usize,
+            n => self.registers[(n - 2) as usize] as usize,
+        };
+        bank * 0x0400 + (addr & 0x03FF) as usize
+    }
+
+    #[inline]
+    fn ram_readable(&self) -> bool {
+        self.data.prg_ram_size > 0 && self.ram_protect & RAM_ENABLE != 0
+    }
+
+    #[inline]
+    fn ram_writable(&self) -> bool {
+        self.ram_readable() && self.ram_protect & RAM_WRITE_DENY == 0
+    }
+
+    /// Watches PPU A12 and clocks the scanline counter on filtered rising
+    /// edges.
+    fn watch_a12(&mut self, addr: u16) {
+        let a12: bool = addr & 0x1000 != 0;
+        if a12 && !self.a12 {
+            if self.cycle - self.a12_low_since >= A12_FILTER_CYCLES {
+                self.clock_irq_counter();
+            }
+        } else if !a12 && self.a12 {
+            self.a12_low_since = self.cycle;
+        }
+        self.a12 = a12;
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let reloaded: bool = self.irq_counter == 0 || self.irq_reload;
+        if reloaded {
+            self.irq_counter = self.irq_latch;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        let mmc3a: bool = self.data.submapper == 4;
+        let fire: bool =
+            self.irq_counter == 0 && (!mmc3a || !reloaded || self.irq_reload);
+        if fire && self.irq_enabled {
+            self.irq_active = true;
+        }
+        self.irq_reload = false;
+    }
+}
+impl Mapper for M004 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.ram_readable() => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
+            0x8000..=0xFFFF => Mapped::Prg(self.prg_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        let even: bool = addr & 0x0001 == 0;
+        match addr {
+            0x6000..=0x7FFF if self.ram_writable() => {
+                return Mapped::PrgRam((addr & 0x1FFF) as usize);
+            }
+            0x8000..=0x9FFF if even => self.bank_select = data,
+            0x8000..=0x9FFF => {
+                self.registers[(self.bank_select & SELECT_REGISTER) as usize] =
+                    data;
+            }
+            0xA000..=0xBFFF if even => {
+                self.mirror = match data & 0x01 {
+                    0 => Mirroring::VERTICAL,
+                    _ => Mirroring::HORIZONTAL,
+                };
+            }
+            0xA000..=0xBFFF => self.ram_protect = data,
+            0xC000..=0xDFFF if even => self.irq_latch = data,
+            0xC000..=0xDFFF => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xE000..=0xFFFF if even => {
+                self.irq_enabled = false;
+                self.irq_active = false;
+            }
+            0xE000..=0xFFFF => self.irq_enabled = true,
+            _ => return Mapped::Unmapped,
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        self.watch_a12(addr);
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        self.watch_a12(addr);
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_address(&mut self, addr: u16) {
+        self.watch_a12(addr);
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        match self.data.mirror {
+            // boards with their own nametable ram ignore $A000
+            Mirroring::FOUR_SCREEN => None,
+            _ => Some(self.mirror),
+        }
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn cpu_clock(&mut self) {
+        self.cycle += 1;
+    }
+
+    fn reset(&mut self) {
+        self.bank_select = 0x00;
+        self.irq_enabled = false;
+        self.irq_active = false;
+        self.irq_reload = false;
+    }
+}
@@ -0,0 +1,696 @@
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// PPU registers snooped from the cpu bus
+const PPU_CTRL_SPRITE_8X16: u8 = 0x20;
+const PPU_MASK_RENDERING: u8 = 0x18;
+// $5200 vertical split
+const SPLIT_ENABLE: u8 = 0x80;
+const SPLIT_RIGHT: u8 = 0x40;
+const SPLIT_TILE: u8 = 0x1F;
+// $5204 scanline IRQ
+const IRQ_PENDING: u8 = 0x80;
+const IRQ_IN_FRAME: u8 = 0x40;
+const IRQ_ENABLE: u8 = 0x80;
+// $5104 ExRAM modes
+const EXRAM_NAMETABLE: u8 = 0;
+const EXRAM_EXTENDED_ATTRIBUTES: u8 = 1;
+const EXRAM_RAM: u8 = 2;
+// $5010 PCM control
+const PCM_READ_MODE: u8 = 0x01;
+
+/// Cpu cycles without a single PPU read before the MMC5 decides the PPU
+/// stopped rendering and drops its in-frame flag.
+const IDLE_CYCLES: u8 = 3;
+/// After the scanline detection point the PPU does 32 background tile
+/// slots, then 8 sprite slots, then the first two tiles of the next line,
+/// four reads per slot.
+const BACKGROUND_FETCHES: u16 = 32 * 4;
+const SPRITE_FETCHES: u16 = 8 * 4;
+const PREFETCH_START: u16 = BACKGROUND_FETCHES + SPRITE_FETCHES;
+/// The pulse channels' envelope and length units run off a fixed 240Hz
+/// divider instead of the APU frame counter.
+const QUARTER_FRAME_CYCLES: u16 = 7457;
+
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24,
+    18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/// MMC5 (ExROM), the most capable board Nintendo made. Everything lives in
+/// $5000-$5FFF:
+///
+/// ```no_run
+/// $5000-$5007  two pulse channels, APU layout without the sweep units
+/// $5010/$5011  PCM control / raw 8-bit sample
+/// $5015        pulse enables, reads back the length counter status
+/// $5100        PRG mode (0: 32KB, 1: 16KB+16KB, 2: 16KB+8KB+8KB, 3: 4x8KB)
+/// $5101        CHR mode (0: 8KB, 1: 4KB, 2: 2KB, 3: 1KB)
+/// $5102/$5103  PRG RAM write protect, writes need $02 and $01
+/// $5104        ExRAM mode (0: nametable, 1: extended attributes,
+///              2: cpu ram, 3: cpu read only)
+/// $5105        nametable slots, two bits each (0/1: CIRAM page, 2: ExRAM,
+///              3: fill mode)
+/// $5106/$5107  fill mode tile and palette
+/// $5113        PRG RAM bank at $6000
+/// $5114-$5117  PRG banks, bit 7 selects ROM, $5117 is always ROM
+/// $5120-$5127  CHR set A, used for sprites and everything in 8x8 mode
+/// $5128-$512B  CHR set B, used for the background with 8x16 sprites
+/// $5130        upper CHR bank bits
+/// $5200-$5202  vertical split control, scroll and 4KB CHR bank
+/// $5203/$5204  scanline IRQ compare / status and enable
+/// $5205/$5206  8x8 unsigned multiplier, reads back the 16-bit product
+/// $5C00-$5FFF  1KB ExRAM
+/// ```
+///
+/// The board has no idea where the PPU is, it works it out from the
+/// fetches: three reads in a row from the same nametable address only
+/// happen at the start of a scanline, and counting reads from there tells
+/// background, sprite and prefetch slots apart. That is what drives the
+/// scanline IRQ, the split screen, extended attributes and the 8x16 sprite
+/// CHR set.
+///
+/// PCM read mode, where the channel samples cpu reads from $8000-$BFFF, is
+/// not emulated; no game is known to use it.
+#[derive(Debug, Clone, Copy)]
+pub struct M005 {
+    data: MapperData,
+
+    prg_mode: u8,
+    chr_mode: u8,
+    ram_protect: [u8; 2],
+    exram_mode: u8,
+    nametables: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+    prg_banks: [u8; 5], // $5113-$5117
+    chr_a: [u16; 8],
+    chr_b: [u16; 4],
+    chr_upper: u8,
+    last_set_b: bool, // which CHR set the cpu wrote last
+    exram: [u8; 0x400],
+
+    split_control: u8,
+    split_scroll: u8,
+    split_bank: u8,
+
+    irq_compare: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    in_frame: bool,
+    scanline: u8,
+
+    multiplicand: u8,
+    multiplier: u8,
+
+    // snooped from $2000/$2001
+    sprite_8x16: bool,
+    rendering: bool,
+
+    // fetch tracking
+    idle: u8,
+    last_nametable_read: Option<u16>,
+    nametable_matches: u8,
+    fetch: u16,             // reads since the scanline detection point
+    ext_attribute: u8,      // ExRAM byte behind the current background tile
+    split_tile: Option<u8>, // tile id when the current tile is in the split
+
+    pulse: [Pulse; 2],
+    pcm_control: u8,
+    pcm: u8,
+    odd_cycle: bool,
+    quarter_frame: u16,
+}
+impl M005 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            prg_mode: 0x03,
+            chr_mode: 0x00,
+            ram_protect: [0x00; 2],
+            exram_mode: EXRAM_NAMETABLE,
+            nametables: 0x00,
+            fill_tile: 0x00,
+            fill_attribute: 0x00,
+            prg_banks: [0x00, 0x00, 0x00, 0x00, 0xFF],
+            chr_a: [0; 8],
+            chr_b: [0; 4],
+            chr_upper: 0x00,
+            last_set_b: false,
+            exram: [0u8; 0x400],
+            split_control: 0x00,
+            split_scroll: 0x00,
+            split_bank: 0x00,
+            irq_compare: 0x00,
+            irq_enabled: false,
+            irq_pending: false,
+            in_frame: false,
+            scanline: 0,
+            multiplicand: 0xFF,
+            multiplier: 0xFF,
+            sprite_8x16: false,
+            rendering: false,
+            idle: IDLE_CYCLES,
+            last_nametable_read: None,
+            nametable_matches: 0,
+            fetch: 0,
+            ext_attribute: 0x00,
+            split_tile: None,
+            pulse: [Pulse::default(); 2],
+            pcm_control: 0x00,
+            pcm: 0x00,
+            odd_cycle: false,
+            quarter_frame: 0,
+        }
+    }
+
+    #[inline]
+    fn ram_writable(&self) -> bool {
+        self.ram_protect[0] & 0x03 == 0x02 && self.ram_protect[1] & 0x03 == 0x01
+    }
+
+    /// Resolves $8000-$FFFF according to the PRG mode. Banks are counted
+    /// in 8KB units, larger windows ignore the low bits.
+    fn prg_target(&self, addr: u16) -> Mapped {
+        let slot: usize = ((addr >> 13) & 0x03) as usize;
+        let (register, size): (usize, usize) = match (self.prg_mode, slot) {
+            (0, _) => (4, 4),
+            (1, 0 | 1) | (2, 0 | 1) => (2, 2),
+            (1, _) => (4, 2),
+            (2, 2) => (3, 1),
+            (_, n) => (n + 1, 1),
+        };
+        let value: u8 = self.prg_banks[register];
+        let inner: usize = slot & (size - 1);
+        let offset: usize = (addr & 0x1FFF) as usize;
+        if register == 4 || value & 0x80 != 0 {
+            let bank: usize = ((value & 0x7F) as usize & !(size - 1)) | inner;
+            Mapped::Prg(bank * 0x2000 + offset)
+        } else {
+            let bank: usize = ((value & 0x07) as usize & !(size - 1)) | inner;
+            Mapped::PrgRam(bank * 0x2000 + offset)
+        }
+    }
+
+    fn chr_offset(&self, addr: u16, set_b: bool) -> usize {
+        let (bank, size): (u16, usize) = match (set_b, self.chr_mode) {
+            (false, 0) => (self.chr_a[7], 0x2000),
+            (false, 1) => {
+                (self.chr_a[(((addr >> 12) & 1) * 4 + 3) as usize], 0x1000)
+            }
+            (false, 2) => {
+                (self.chr_a[(((addr >> 11) & 3) * 2 + 1) as usize], 0x0800)
+            }
+            (false, _) => (self.chr_a[((addr >> 10) & 7) as usize], 0x0400),
+            (true, 0) => (self.chr_b[3], 0x2000),
+            (true, 1) => (self.chr_b[3], 0x1000),
+            (true, 2) => {
+                (self.chr_b[(((addr >> 11) & 1) * 2 + 1) as usize], 0x0800)
+            }
+            (true, _) => (self.chr_b[((addr >> 10) & 3) as usize], 0x0400),
+        };
+        bank as usize * size + (addr as usize & (size - 1))
+    }
+
+    /// Set B only feeds the background while 8x16 sprites are rendering,
+    /// any other access sees whichever set the cpu wrote last.
+    #[inline]
+    fn chr_set_b(&self, sprite_fetch: bool) -> bool {
+        match self.sprite_8x16 && self.rendering {
+            true => !sprite_fetch,
+            false => self.last_set_b,
+        }
+    }
+
+    /// What the nametable slot at `addr` is wired to in $5105.
+    fn nametable(&self, addr: u16) -> Mapped {
+        let offset: usize = (addr & 0x03FF) as usize;
+        match (self.nametables >> (((addr >> 10) & 0x03) * 2)) & 0x03 {
+            0 => Mapped::Ciram(offset),
+            1 => Mapped::Ciram(0x0400 | offset),
+            2 => match self.exram_mode {
+                EXRAM_NAMETABLE | EXRAM_EXTENDED_ATTRIBUTES => {
+                    Mapped::Data(self.exram[offset])
+                }
+                _ => Mapped::Data(0x00),
+            },
+            _ => match offset < 0x03C0 {
+                true => Mapped::Data(self.fill_tile),
+                false => Mapped::Data(self.fill_attribute * 0x55),
+            },
+        }
+    }
+
+    /// Watches for the three identical nametable reads that start every
+    /// rendered scanline and numbers the reads in between.
+    fn track_fetch(&mut self, addr: u16) {
+        // the first read after the PPU went quiet is the first background
+        // tile of the pre-render line, the same slot the detection lands on
+        self.fetch = match self.idle >= IDLE_CYCLES {
+            true => 0,
+            false => self.fetch.saturating_add(1),
+        };
+        self.idle = 0;
+
+        let nametable: bool = (0x2000..=0x2FFF).contains(&addr);
+        if nametable && self.last_nametable_read == Some(addr) {
+            self.nametable_matches += 1;
+        } else {
+            self.nametable_matches = 0;
+        }
+        self.last_nametable_read = nametable.then_some(addr);
+
+        if self.nametable_matches == 2 {
+            self.fetch = 0;
+            if !self.in_frame {
+                self.in_frame = true;
+                self.scanline = 0;
+            } else {
+                self.scanline = self.scanline.wrapping_add(1);
+                if self.scanline == self.irq_compare {
+                    self.irq_pending = true;
+                }
+            }
+        }
+    }
+
+    /// Screen column and line of the background tile being fetched, `None`
+    /// for sprite slots and the dummy reads at the end of the line.
+    fn background_tile(&self) -> Option<(u8, u8)> {
+        match self.fetch {
+            0..BACKGROUND_FETCHES => {
+                Some((2 + (self.fetch / 4) as u8, self.scanline))
+            }
+            PREFETCH_START..=199 => Some((
+                ((self.fetch - PREFETCH_START) / 4) as u8,
+                self.scanline.wrapping_add(1),
+            )),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn split_y(&self, line: u8) -> u16 {
+        (line as u16 + self.split_scroll as u16) % 240
+    }
+
+    fn in_split(&self, column: u8) -> bool {
+        if self.split_control & SPLIT_ENABLE == 0
+            || self.exram_mode > EXRAM_EXTENDED_ATTRIBUTES
+        {
+            return false;
+        }
+        let threshold: u8 = self.split_control & SPLIT_TILE;
+        match self.split_control & SPLIT_RIGHT {
+            0 => column < threshold,
+            _ => column >= threshold,
+        }
+    }
+
+    /// Background fetches while rendering, where the split screen and the
+    /// extended attributes get to replace what the PPU asked for.
+    fn background_fetch(&mut self, addr: u16, column: u8, line: u8) -> Mapped {
+        let column: u8 = column & 0x1F;
+        match self.fetch % 4 {
+            // nametable
+            0 => {
+                self.split_tile = None;
+                if self.in_split(column) {
+                    let y: u16 = self.split_y(line);
+                    let tile: u8 =
+                        self.exram[((y / 8) * 32 + column as u16) as usize];
+                    self.split_tile = Some(tile);
+                    return Mapped::Data(tile);
+                }
+                if self.exram_mode == EXRAM_EXTENDED_ATTRIBUTES {
+                    self.ext_attribute = self.exram[(addr & 0x03FF) as usize];
+                }
+                self.nametable(addr)
+            }
+            // attribute, replicated to all four quadrants since the PPU
+            // picks the quadrant from its own scroll position
+            1 => {
+                if self.split_tile.is_some() {
+                    let y: u16 = self.split_y(line);
+                    let byte: u8 = self.exram
+                        [(0x03C0 + (y / 32) * 8 + column as u16 / 4) as usize];
+                    let shift: u16 =
+                        ((y / 16) & 1) * 4 + ((column as u16 / 2) & 1) * 2;
+                    return Mapped::Data(((byte >> shift) & 0x03) * 0x55);
+                }
+                if self.exram_mode == EXRAM_EXTENDED_ATTRIBUTES {
+                    return Mapped::Data((self.ext_attribute >> 6) * 0x55);
+                }
+                self.nametable(addr)
+            }
+            // pattern planes
+            _ => {
+                if let Some(tile) = self.split_tile {
+                    let y: u16 = self.split_y(line);
+                    return Mapped::Chr(
+                        self.split_bank as usize * 0x1000
+                            + tile as usize * 16
+                            + (y & 0x07) as usize
+                            + (addr & 0x0008) as usize,
+                    );
+                }
+                if self.exram_mode == EXRAM_EXTENDED_ATTRIBUTES {
+                    let bank: usize = ((self.chr_upper as usize) << 6)
+                        | (self.ext_attribute & 0x3F) as usize;
+                    return Mapped::Chr(
+                        bank * 0x1000 + (addr & 0x0FFF) as usize,
+                    );
+                }
+                Mapped::Chr(self.chr_offset(addr, self.chr_set_b(false)))
+            }
+        }
+    }
+
+    fn clock_audio(&mut self) {
+        self.odd_cycle = !self.odd_cycle;
+        if self.odd_cycle {
+            self.pulse.iter_mut().for_each(Pulse::clock_timer);
+        }
+        self.quarter_frame += 1;
+        if self.quarter_frame == QUARTER_FRAME_CYCLES {
+            self.quarter_frame = 0;
+            for pulse in self.pulse.iter_mut() {
+                pulse.clock_envelope();
+                pulse.clock_length();
+            }
+        }
+    }
+}
+impl Mapper for M005 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x5015 => Mapped::Data(
+                (self.pulse[0].length > 0) as u8
+                    | ((self.pulse[1].length > 0) as u8) << 1,
+            ),
+            0x5204 => {
+                let status: u8 = (self.irq_pending as u8 * IRQ_PENDING)
+                    | (self.in_frame as u8 * IRQ_IN_FRAME);
+                self.irq_pending = false;
+                Mapped::Data(status)
+            }
+            0x5205 => Mapped::Data(
+                (self.multiplicand as u16 * self.multiplier as u16) as u8,
+            ),
+            0x5206 => Mapped::Data(
+                ((self.multiplicand as u16 * self.multiplier as u16) >> 8)
+                    as u8,
+            ),
+            // ExRAM reads back in modes 2 and 3
+            0x5C00..=0x5FFF if self.exram_mode >= EXRAM_RAM => {
+                Mapped::Data(self.exram[(addr & 0x03FF) as usize])
+            }
+            0x6000..=0x7FFF if self.data.prg_ram_size > 0 => Mapped::PrgRam(
+                (self.prg_banks[0] & 0x07) as usize * 0x2000
+                    + (addr & 0x1FFF) as usize,
+            ),
+            0x8000..=0xFFFF => {
+                // the NMI vector fetch marks the end of the frame
+                if addr == 0xFFFA || addr == 0xFFFB {
+                    self.in_frame = false;
+                    self.last_nametable_read = None;
+                }
+                self.prg_target(addr)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x5000..=0x5007 => {
+                self.pulse[((addr >> 2) & 1) as usize].write(addr, data);
+            }
+            0x5010 => self.pcm_control = data,
+            0x5011 => {
+                // a zero is ignored in write mode
+                if self.pcm_control & PCM_READ_MODE == 0 && data != 0 {
+                    self.pcm = data;
+                }
+            }
+            0x5015 => {
+                self.pulse[0].set_enabled(data & 0x01 != 0);
+                self.pulse[1].set_enabled(data & 0x02 != 0);
+            }
+            0x5100 => self.prg_mode = data & 0x03,
+            0x5101 => self.chr_mode = data & 0x03,
+            0x5102 | 0x5103 => {
+                self.ram_protect[(addr - 0x5102) as usize] = data;
+            }
+            0x5104 => self.exram_mode = data & 0x03,
+            0x5105 => self.nametables = data,
+            0x5106 => self.fill_tile = data,
+            0x5107 => self.fill_attribute = data & 0x03,
+            0x5113..=0x5117 => {
+                self.prg_banks[(addr - 0x5113) as usize] = data;
+            }
+            0x5120..=0x5127 => {
+                self.chr_a[(addr - 0x5120) as usize] =
+                    ((self.chr_upper as u16) << 8) | data as u16;
+                self.last_set_b = false;
+            }
+            0x5128..=0x512B => {
+                self.chr_b[(addr - 0x5128) as usize] =
+                    ((self.chr_upper as u16) << 8) | data as u16;
+                self.last_set_b = true;
+            }
+            0x5130 => self.chr_upper = data & 0x03,
+            0x5200 => self.split_control = data,
+            0x5201 => self.split_scroll = data,
+            0x5202 => self.split_bank = data,
+            0x5203 => self.irq_compare = data,
+            0x5204 => self.irq_enabled = data & IRQ_ENABLE != 0,
+            0x5205 => self.multiplicand = data,
+            0x5206 => self.multiplier = data,
+            0x5C00..=0x5FFF => {
+                // in the nametable modes the PPU owns ExRAM while
+                // rendering, outside of it the cpu can only write zeros
+                let value: u8 = match self.exram_mode {
+                    EXRAM_NAMETABLE | EXRAM_EXTENDED_ATTRIBUTES => {
+                        match self.in_frame {
+                            true => data,
+                            false => 0x00,
+                        }
+                    }
+                    EXRAM_RAM => data,
+                    // EXRAM_READ_ONLY
+                    _ => return Mapped::Data(data),
+                };
+                self.exram[(addr & 0x03FF) as usize] = value;
+            }
+            0x6000..=0x7FFF
+                if self.data.prg_ram_size > 0 && self.ram_writable() =>
+            {
+                return Mapped::PrgRam(
+                    (self.prg_banks[0] & 0x07) as usize * 0x2000
+                        + (addr & 0x1FFF) as usize,
+                );
+            }
+            0x8000..=0xFFFF if self.ram_writable() => {
+                return match self.prg_target(addr) {
+                    Mapped::PrgRam(offset) => Mapped::PrgRam(offset),
+                    _ => Mapped::Unmapped,
+                };
+            }
+            _ => return Mapped::Unmapped,
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        self.track_fetch(addr);
+        if self.rendering && self.in_frame && addr < 0x3000 {
+            // a read that does not match its slot is not a background fetch,
+            // $2007 on a glitched frame for one
+            let nametable_slot: bool = self.fetch % 4 < 2;
+            if let Some((column, line)) = self.background_tile() {
+                if nametable_slot == (addr >= 0x2000) {
+                    return self.background_fetch(addr, column, line);
+                }
+            }
+        }
+        match addr {
+            0x0000..=0x1FFF => {
+                let sprite: bool = self.rendering
+                    && (BACKGROUND_FETCHES..PREFETCH_START)
+                        .contains(&self.fetch);
+                Mapped::Chr(self.chr_offset(addr, self.chr_set_b(sprite)))
+            }
+            0x2000..=0x3EFF => self.nametable(addr),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr, self.last_set_b))
+            }
+            0x2000..=0x3EFF => match self.nametable(addr) {
+                Mapped::Ciram(offset) => Mapped::Ciram(offset),
+                _ => {
+                    let slot: u8 =
+                        (self.nametables >> (((addr >> 10) & 0x03) * 2)) & 0x03;
+                    if slot == 2 && self.exram_mode <= EXRAM_EXTENDED_ATTRIBUTES
+                    {
+                        self.exram[(addr & 0x03FF) as usize] = data;
+                    }
+                    Mapped::Data(data)
+                }
+            },
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_register_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x2000 => self.sprite_8x16 = data & PPU_CTRL_SPRITE_8X16 != 0,
+            0x2001 => self.rendering = data & PPU_MASK_RENDERING != 0,
+            _ => (),
+        }
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_pending && self.irq_enabled
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.idle < IDLE_CYCLES {
+            self.idle += 1;
+            if self.idle == IDLE_CYCLES {
+                self.in_frame = false;
+                self.last_nametable_read = None;
+            }
+        }
+        self.clock_audio();
+    }
+
+    fn audio_output(&self) -> f32 {
+        // same nonlinear curves the APU uses for its pulse and DMC outputs
+        let pulses: f32 =
+            (self.pulse[0].output() + self.pulse[1].output()) as f32;
+        let pulse_out: f32 = match pulses > 0.0 {
+            true => 95.88 / (8128.0 / pulses + 100.0),
+            false => 0.0,
+        };
+        let pcm: f32 = (self.pcm >> 1) as f32;
+        let pcm_out: f32 = match pcm > 0.0 {
+            true => 159.79 / (22638.0 / pcm + 100.0),
+            false => 0.0,
+        };
+        pulse_out + pcm_out
+    }
+
+    fn reset(&mut self) {
+        self.prg_mode = 0x03;
+        self.prg_banks[4] = 0xFF;
+        self.irq_enabled = false;
+        self.irq_pending = false;
+        self.in_frame = false;
+        self.pulse = [Pulse::default(); 2];
+        self.pcm = 0x00;
+    }
+}
+
+/// One of the MMC5's pulse channels: an APU pulse channel without the
+/// sweep unit.
+#[derive(Debug, Clone, Copy, Default)]
+struct Pulse {
+    enabled: bool,
+    duty: u8,
+    halt: bool, // length counter halt, doubles as envelope loop
+    constant_volume: bool,
+    volume: u8, // constant volume or envelope period
+    period: u16,
+    timer: u16,
+    step: u8,
+    length: u8,
+    envelope_start: bool,
+    envelope_divider: u8,
+    envelope_decay: u8,
+}
+impl Pulse {
+    fn write(&mut self, addr: u16, data: u8) {
+        match addr & 0x03 {
+            0 => {
+                self.duty = data >> 6;
+                self.halt = data & 0x20 != 0;
+                self.constant_volume = data & 0x10 != 0;
+                self.volume = data & 0x0F;
+            }
+            // there is no sweep unit behind the second register
+            1 => (),
+            2 => self.period = (self.period & 0x0700) | data as u16,
+            _ => {
+                self.period =
+                    (self.period & 0x00FF) | ((data as u16 & 0x07) << 8);
+                if self.enabled {
+                    self.length = LENGTH_TABLE[(data >> 3) as usize];
+                }
+                self.step = 0;
+                self.envelope_start = true;
+            }
+        }
+    }
+
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.length = 0;
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.step = (self.step + 1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn clock_envelope(&mut self) {
+        if self.envelope_start {
+            self.envelope_start = false;
+            self.envelope_decay = 15;
+            self.envelope_divider = self.volume;
+        } else if self.envelope_divider == 0 {
+            self.envelope_divider = self.volume;
+            if self.envelope_decay > 0 {
+                self.envelope_decay -= 1;
+            } else if self.halt {
+                self.envelope_decay = 15;
+            }
+        } else {
+            self.envelope_divider -= 1;
+        }
+    }
+
+    fn clock_length(&mut self) {
+        if !self.halt && self.length > 0 {
+            self.length -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.length == 0
+            || DUTY_TABLE[self.duty as usize][self.step as usize] == 0
+        {
+            return 0;
+        }
+        match self.constant_volume {
+            true => self.volume,
+            false => self.envelope_decay,
+        }
+    }
+}
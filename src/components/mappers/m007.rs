@@ -0,0 +1,80 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// bank register ($8000-$FFFF)
+const BANK_PRG: u8 = 0x07;
+const BANK_NAMETABLE: u8 = 0x10;
+
+/// AxROM: any write to $8000-$FFFF selects a 32KB PRG bank and which of
+/// the two console nametables backs the whole screen. CHR is 8KB of RAM.
+///
+/// ```no_run
+/// 7  bit  0
+/// xxxM xPPP  M: one-screen nametable, PPP: 32KB PRG bank
+/// ```
+///
+/// NES 2.0 submapper 2 (AMROM) has bus conflicts, ANROM and AOROM gate the
+/// ROM off during writes.
+#[derive(Debug, Clone, Copy)]
+pub struct M007 {
+    data: MapperData,
+    bank: u8,
+}
+impl M007 {
+    pub fn new(data: MapperData) -> Self {
+        Self { data, bank: 0x00 }
+    }
+}
+impl Mapper for M007 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => Mapped::Prg(
+                (self.bank & BANK_PRG) as usize * 0x8000
+                    + (addr & 0x7FFF) as usize,
+            ),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => {
+                self.bank = data;
+                Mapped::Data(data)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(addr as usize),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(addr as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(match self.bank & BANK_NAMETABLE {
+            0 => Mirroring::ONESCREAN_LO,
+            _ => Mirroring::ONESCREAN_HI,
+        })
+    }
+
+    fn bus_conflicts(&self) -> bool {
+        self.data.submapper == 2
+    }
+
+    fn reset(&mut self) {
+        self.bank = 0x00;
+    }
+}
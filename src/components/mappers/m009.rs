@@ -0,0 +1,147 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// tiles that flip the latches
+const TILE_FD: u8 = 0xFD;
+const TILE_FE: u8 = 0xFE;
+
+/// MMC2 (PxROM). One switchable 8KB PRG bank at $8000 with the last three
+/// banks fixed behind it, and two pairs of 4KB CHR banks picked by latches
+/// that the PPU flips itself while fetching tiles.
+///
+/// ```no_run
+/// $A000-$AFFF  PRG bank at $8000
+/// $B000-$BFFF  CHR bank at $0000 when latch 0 is $FD
+/// $C000-$CFFF  CHR bank at $0000 when latch 0 is $FE
+/// $D000-$DFFF  CHR bank at $1000 when latch 1 is $FD
+/// $E000-$EFFF  CHR bank at $1000 when latch 1 is $FE
+/// $F000-$FFFF  mirroring, 0: vertical, 1: horizontal
+/// ```
+///
+/// Fetching the pattern of tile $FD or $FE sets the latch of that pattern
+/// table once the fetch is done, so the tile itself still comes from the
+/// old bank.
+#[derive(Debug, Clone, Copy)]
+pub struct M009 {
+    data: MapperData,
+    prg_bank: u8,
+    chr: ChrLatches,
+    mirror: Mirroring,
+}
+impl M009 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            prg_bank: 0x00,
+            chr: ChrLatches::new(),
+            mirror: Mirroring::VERTICAL,
+        }
+    }
+}
+impl Mapper for M009 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.data.prg_ram_size > 0 => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
+            0x8000..=0x9FFF => Mapped::Prg(
+                (self.prg_bank & 0x0F) as usize * 0x2000
+                    + (addr & 0x1FFF) as usize,
+            ),
+            0xA000..=0xFFFF => {
+                // the last three 8KB banks, in order
+                let banks: usize = self.data.prg_bank as usize * 2;
+                let bank: usize =
+                    banks.saturating_sub(4) + ((addr >> 13) & 0x03) as usize;
+                Mapped::Prg(bank * 0x2000 + (addr & 0x1FFF) as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.data.prg_ram_size > 0 => {
+                return Mapped::PrgRam((addr & 0x1FFF) as usize);
+            }
+            0xA000..=0xAFFF => self.prg_bank = data,
+            0xB000..=0xEFFF => self.chr.write(addr, data),
+            0xF000..=0xFFFF => {
+                self.mirror = match data & 0x01 {
+                    0 => Mirroring::VERTICAL,
+                    _ => Mirroring::HORIZONTAL,
+                };
+            }
+            _ => return Mapped::Unmapped,
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => {
+                let offset: usize = self.chr.offset(addr);
+                self.chr.watch(addr, false);
+                Mapped::Chr(offset)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr.offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirror)
+    }
+}
+
+/// The latch pair shared by MMC2 and MMC4: four 4KB CHR bank registers and
+/// one latch per pattern table choosing between the $FD and $FE bank.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct ChrLatches {
+    banks: [u8; 4], // $0000/FD, $0000/FE, $1000/FD, $1000/FE
+    latch: [u8; 2],
+}
+impl ChrLatches {
+    pub(super) fn new() -> Self {
+        Self {
+            banks: [0x00; 4],
+            latch: [TILE_FE; 2],
+        }
+    }
+
+    /// Bank registers live at $B000, $C000, $D000 and $E000.
+    pub(super) fn write(&mut self, addr: u16, data: u8) {
+        self.banks[((addr >> 12) - 0x0B) as usize] = data & 0x1F;
+    }
+
+    pub(super) fn offset(&self, addr: u16) -> usize {
+        let table: usize = ((addr >> 12) & 0x01) as usize;
+        let register: usize =
+            table * 2 + (self.latch[table] == TILE_FE) as usize;
+        self.banks[register] as usize * 0x1000 + (addr & 0x0FFF) as usize
+    }
+
+    /// Looks at a pattern fetch and flips the latches. MMC2 only reacts to
+    /// the exact first address of the tile in the left table, MMC4
+    /// (`wide`) to any row of it like it does in the right table.
+    pub(super) fn watch(&mut self, addr: u16, wide: bool) {
+        let table: usize = ((addr >> 12) & 0x01) as usize;
+        let tile: u8 = ((addr >> 4) & 0xFF) as u8;
+        let row_ok: bool = match table == 0 && !wide {
+            true => addr & 0x000F == 0x0008,
+            false => addr & 0x0008 != 0,
+        };
+        if row_ok && (tile == TILE_FD || tile == TILE_FE) {
+            self.latch[table] = tile;
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::components::cartridge::Mirroring;
+
+use super::m009::ChrLatches;
+use super::mapper::{Mapped, Mapper, MapperData};
+
+/// MMC4 (FxROM). The same latched CHR banking as MMC2, but with a 16KB
+/// PRG bank at $8000, the last bank fixed at $C000 and 8KB of PRG RAM.
+///
+/// ```no_run
+/// $A000-$AFFF  PRG bank at $8000
+/// $B000-$EFFF  CHR banks, see MMC2
+/// $F000-$FFFF  mirroring, 0: vertical, 1: horizontal
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct M010 {
+    data: MapperData,
+    prg_bank: u8,
+    chr: ChrLatches,
+    mirror: Mirroring,
+}
+impl M010 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            prg_bank: 0x00,
+            chr: ChrLatches::new(),
+            mirror: Mirroring::VERTICAL,
+        }
+    }
+}
+impl Mapper for M010 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.data.prg_ram_size > 0 => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
+            0x8000..=0xBFFF => Mapped::Prg(
+                (self.prg_bank & 0x0F) as usize * 0x4000
+                    + (addr & 0x3FFF) as usize,
+            ),
+            0xC000..=0xFFFF => {
+                let last: usize = self.data.prg_bank.saturating_sub(1) as usize;
+                Mapped::Prg(last * 0x4000 + (addr & 0x3FFF) as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.data.prg_ram_size > 0 => {
+                return Mapped::PrgRam((addr & 0x1FFF) as usize);
+            }
+            0xA000..=0xAFFF => self.prg_bank = data,
+            0xB000..=0xEFFF => self.chr.write(addr, data),
+            0xF000..=0xFFFF => {
+                self.mirror = match data & 0x01 {
+                    0 => Mirroring::VERTICAL,
+                    _ => Mirroring::HORIZONTAL,
+                };
+            }
+            _ => return Mapped::Unmapped,
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => {
+                let offset: usize = self.chr.offset(addr);
+                self.chr.watch(addr, true);
+                Mapped::Chr(offset)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr.offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirror)
+    }
+}
@@ -0,0 +1,74 @@
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// bank register ($8000-$FFFF)
+const BANK_PRG: u8 = 0x03;
+const BANK_CHR: u8 = 0xF0;
+
+/// Color Dreams: the unlicensed cousin of GxROM, with the nibbles swapped
+/// and a wider CHR field. Writes are subject to bus conflicts.
+///
+/// ```no_run
+/// 7  bit  0
+/// CCCC xxPP  CCCC: 8KB CHR bank, PP: 32KB PRG bank
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct M011 {
+    data: MapperData,
+    bank: u8,
+}
+impl M011 {
+    pub fn new(data: MapperData) -> Self {
+        Self { data, bank: 0x00 }
+    }
+
+    #[inline]
+    fn chr_offset(&self, addr: u16) -> usize {
+        ((self.bank & BANK_CHR) >> 4) as usize * 0x2000
+            + (addr & 0x1FFF) as usize
+    }
+}
+impl Mapper for M011 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => Mapped::Prg(
+                (self.bank & BANK_PRG) as usize * 0x8000
+                    + (addr & 0x7FFF) as usize,
+            ),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => {
+                self.bank = data;
+                Mapped::Data(data)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn bus_conflicts(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        self.bank = 0x00;
+    }
+}
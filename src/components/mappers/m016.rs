@@ -0,0 +1,246 @@
+use crate::components::cartridge::Mirroring;
+
+use super::eeprom::{Eeprom, EepromChip};
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// $xxxA IRQ control
+const IRQ_ENABLE: u8 = 0x01;
+// $xxxD EEPROM control (PRG RAM enable on mapper 153)
+const EEPROM_SCL: u8 = 0x20;
+const EEPROM_SDA: u8 = 0x40;
+const PRG_RAM_ENABLE: u8 = 0x20;
+// $6000-$7FFF reads
+const EEPROM_DATA_OUT: u8 = 0x10;
+
+/// Which chip the board carries, they share the register layout.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Board {
+    /// FCG-1/2, registers at $6000-$7FFF and an IRQ counter written
+    /// directly (mapper 16 submapper 4).
+    FCG,
+    /// LZ93D50 with a 24C01/24C02 EEPROM (mappers 16 and 159).
+    LZ93D50,
+    /// LZ93D50 with 8KB of battery backed PRG RAM and 512KB of PRG, the
+    /// CHR registers select the 256KB half (mapper 153).
+    SRAM,
+}
+
+/// Bandai FCG boards (mappers 16, 153 and 159). Registers repeat every 16
+/// bytes, at $6000-$7FFF on the FCG and $8000-$FFFF on the LZ93D50;
+/// mapper 16 without a submapper decodes both:
+///
+/// ```no_run
+/// $x0-$x7  1KB CHR banks (mapper 153: bit 0 picks the 256KB PRG half)
+/// $x8      16KB PRG bank at $8000, the last bank is fixed at $C000
+/// $x9      mirroring  0: vertical, 1: horizontal, 2/3: one-screen
+/// $xA      IRQ control  xxxx xxxE  (acknowledges, LZ93D50 reloads)
+/// $xB-$xC  IRQ counter (FCG) or reload latch (LZ93D50), low then high
+/// $xD      EEPROM  RDCx xxxx  R: read, D: SDA, C: SCL (mapper 153: C
+///          enables PRG RAM)
+/// ```
+///
+/// The counter decrements every cpu cycle while enabled and raises the IRQ
+/// as it passes zero. EEPROM data comes back in bit 4 of reads from
+/// $6000-$7FFF, its contents are saved like battery backed PRG RAM.
+#[derive(Debug, Clone, Copy)]
+pub struct M016 {
+    data: MapperData,
+    board: Board,
+    low_registers: bool, // registers also answer at $6000-$7FFF
+    chr_banks: [u8; 8],
+    prg_bank: u8,
+    prg_outer: u8, // mapper 153
+    mirror: Mirroring,
+    control: u8,
+
+    irq_counter: u16,
+    irq_latch: u16,
+    irq_enabled: bool,
+    irq_active: bool,
+
+    eeprom: Option<Eeprom>,
+}
+impl M016 {
+    pub fn new(data: MapperData, mapper_id: u16) -> Self {
+        let (board, eeprom) = match (mapper_id, data.submapper) {
+            (153, _) => (Board::SRAM, None),
+            (159, _) => (Board::LZ93D50, Some(EepromChip::C24C01)),
+            (_, 4) => (Board::FCG, None),
+            _ => (Board::LZ93D50, Some(EepromChip::C24C02)),
+        };
+        Self {
+            data,
+            board,
+            low_registers: board == Board::FCG
+                || (mapper_id == 16 && data.submapper == 0),
+            chr_banks: [0x00; 8],
+            prg_bank: 0x00,
+            prg_outer: 0x00,
+            mirror: Mirroring::VERTICAL,
+            control: 0x00,
+            irq_counter: 0x0000,
+            irq_latch: 0x0000,
+            irq_enabled: false,
+            irq_active: false,
+            eeprom: eeprom.map(Eeprom::new),
+        }
+    }
+
+    /// Whether a write at this address reaches the registers.
+    fn decodes(&self, addr: u16) -> bool {
+        match addr {
+            0x6000..=0x7FFF => self.low_registers,
+            0x8000..=0xFFFF => self.board != Board::FCG,
+            _ => false,
+        }
+    }
+
+    #[inline]
+    fn prg_ram_enabled(&self) -> bool {
+        self.board == Board::SRAM
+            && self.data.prg_ram_size > 0
+            && self.control & PRG_RAM_ENABLE != 0
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let bank: usize = match (self.board, addr) {
+            (Board::SRAM, 0x8000..=0xBFFF) => {
+                (self.prg_outer << 4 | (self.prg_bank & 0x0F)) as usize
+            }
+            (Board::SRAM, _) => (self.prg_outer << 4 | 0x0F) as usize,
+            (_, 0x8000..=0xBFFF) => self.prg_bank as usize & 0x0F,
+            _ => (self.data.prg_bank as usize).saturating_sub(1),
+        };
+        bank * 0x4000 + (addr & 0x3FFF) as usize
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        match self.board {
+            Board::SRAM => addr as usize,
+            _ => {
+                self.chr_banks[(addr >> 10) as usize] as usize * 0x0400
+                    + (addr & 0x03FF) as usize
+            }
+        }
+    }
+
+    fn write_register(&mut self, register: u16, data: u8) {
+        match register {
+            n @ 0x00..=0x07 => {
+                self.chr_banks[n as usize] = data;
+                self.prg_outer = data & 0x01;
+            }
+            0x08 => self.prg_bank = data,
+            0x09 => {
+                self.mirror = match data & 0x03 {
+                    0 => Mirroring::VERTICAL,
+                    1 => Mirroring::HORIZONTAL,
+                    2 => Mirroring::ONESCREAN_LO,
+                    _ => Mirroring::ONESCREAN_HI,
+                }
+            }
+            0x0A => {
+                self.irq_enabled = data & IRQ_ENABLE != 0;
+                self.irq_active = false;
+                if self.board != Board::FCG {
+                    self.irq_counter = self.irq_latch;
+                }
+            }
+            0x0B | 0x0C => {
+                let shift: u16 = (register - 0x0B) * 8;
+                let target: &mut u16 = match self.board {
+                    Board::FCG => &mut self.irq_counter,
+                    _ => &mut self.irq_latch,
+                };
+                *target =
+                    (*target & !(0x00FF << shift)) | (data as u16) << shift;
+            }
+            0x0D => {
+                self.control = data;
+                if let Some(eeprom) = &mut self.eeprom {
+                    eeprom
+                        .write(data & EEPROM_SCL != 0, data & EEPROM_SDA != 0);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+impl Mapper for M016 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
+            0x6000..=0x7FFF => match &self.eeprom {
+                Some(eeprom) if eeprom.output() => {
+                    Mapped::Data(EEPROM_DATA_OUT)
+                }
+                Some(_) => Mapped::Data(0x00),
+                None => Mapped::Unmapped,
+            },
+            0x8000..=0xFFFF => Mapped::Prg(self.prg_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        if self.prg_ram_enabled() && (0x6000..=0x7FFF).contains(&addr) {
+            return Mapped::PrgRam((addr & 0x1FFF) as usize);
+        }
+        if !self.decodes(addr) {
+            return Mapped::Unmapped;
+        }
+        self.write_register(addr & 0x000F, data);
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirror)
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_enabled {
+            if self.irq_counter == 0 {
+                self.irq_active = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        self.eeprom.as_ref().map(Eeprom::contents)
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        if let Some(eeprom) = &mut self.eeprom {
+            eeprom.load(data);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.irq_enabled = false;
+        self.irq_active = false;
+    }
+}
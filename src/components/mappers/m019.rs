@@ -0,0 +1,306 @@
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// $5800 IRQ counter high
+const IRQ_ENABLE: u8 = 0x80;
+// $E000 PRG bank at $8000
+const SOUND_DISABLE: u8 = 0x40;
+// $E800 PRG bank at $A000
+const CIRAM_LOW_DISABLE: u8 = 0x40;
+const CIRAM_HIGH_DISABLE: u8 = 0x80;
+// $F800 write protect and sound address
+const WRITE_KEY_MASK: u8 = 0xF0;
+const WRITE_KEY: u8 = 0x40;
+const SOUND_AUTO_INCREMENT: u8 = 0x80;
+
+/// Bank values from here on select a CIRAM page instead of CHR.
+const CIRAM_BANKS: u8 = 0xE0;
+/// The IRQ counter stops here and raises the IRQ.
+const IRQ_COUNTER_MAX: u16 = 0x7FFF;
+/// Cpu cycles spent on each channel before moving on to the next.
+const CYCLES_PER_CHANNEL: u8 = 15;
+/// Sound RAM byte holding the number of enabled channels in bits 4-6.
+const CHANNEL_COUNT: usize = 0x7F;
+/// One step of sample × volume, a channel at full volume peaks a little
+/// below an APU pulse channel.
+const LEVEL_STEP: f32 = 0.0012;
+
+/// Namco 129/163 (mapper 19).
+///
+/// ```no_run
+/// $4800-$4FFF  sound RAM data port
+/// $5000-$57FF  IRQ counter low                  (writes acknowledge)
+/// $5800-$5FFF  IRQ counter high  Ehhh hhhh  E: enable (writes acknowledge)
+/// $8000-$BFFF  CHR banks at $0000-$1FFF, one register per $800
+/// $C000-$DFFF  nametable banks at $2000-$2FFF, one register per $800
+/// $E000        xSPP PPPP  S: sound disable, P: 8KB PRG bank at $8000
+/// $E800        HLPP PPPP  H/L: CIRAM disable for $1000/$0000, P: $A000
+/// $F000        xxPP PPPP  8KB PRG bank at $C000, $E000 is the last bank
+/// $F800        KKKK DCBA  PRG RAM write key and 2KB protects, doubling as
+///              IAAA AAAA  sound RAM address and auto increment
+/// ```
+///
+/// Bank values of $E0 and up select a page of the console's nametable RAM
+/// instead of CHR ROM. The nametable registers always do, the pattern
+/// table ones only while their $E800 bit is clear.
+///
+/// The 128 bytes of sound RAM hold the wavetables and the state of up to 8
+/// channels; the chip updates one channel every 15 cpu cycles so more
+/// channels means each plays for a smaller share of the time. With the
+/// battery bit set the sound RAM is saved along with the PRG RAM, games use
+/// it as extra work RAM.
+#[derive(Debug, Clone, Copy)]
+pub struct M019 {
+    data: MapperData,
+    chr_banks: [u8; 8],
+    nt_banks: [u8; 4],
+    prg_banks: [u8; 3],
+    write_protect: u8,
+
+    irq_counter: u16,
+    irq_enabled: bool,
+    irq_active: bool,
+
+    sound_ram: [u8; 0x80],
+    sound_address: u8,
+    sound_timer: u8,
+    channel: usize,
+    outputs: [i16; 8],
+}
+impl M019 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            chr_banks: [0x00; 8],
+            nt_banks: [CIRAM_BANKS; 4],
+            prg_banks: [0x00; 3],
+            write_protect: 0x00,
+            irq_counter: 0x0000,
+            irq_enabled: false,
+            irq_active: false,
+            sound_ram: [0x00; 0x80],
+            sound_address: 0x00,
+            sound_timer: 0,
+            channel: 7,
+            outputs: [0; 8],
+        }
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let bank: usize = match (addr >> 13) & 0x03 {
+            3 => (self.data.prg_bank as usize * 2).saturating_sub(1),
+            n => (self.prg_banks[n as usize] & 0x3F) as usize,
+        };
+        bank * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    fn prg_ram_writable(&self, addr: u16) -> bool {
+        let section: u8 = ((addr >> 11) & 0x03) as u8;
+        self.data.prg_ram_size > 0
+            && self.write_protect & WRITE_KEY_MASK == WRITE_KEY
+            && self.write_protect & (0x01 << section) == 0
+    }
+
+    /// Pattern and nametable fetches, CHR or CIRAM depending on the bank.
+    fn ppu_target(&self, addr: u16) -> Mapped {
+        let slot: usize = ((addr >> 10) & 0x07) as usize;
+        let (bank, ciram): (u8, bool) = match addr {
+            0x0000..=0x0FFF => (
+                self.chr_banks[slot],
+                self.prg_banks[1] & CIRAM_LOW_DISABLE == 0,
+            ),
+            0x1000..=0x1FFF => (
+                self.chr_banks[slot],
+                self.prg_banks[1] & CIRAM_HIGH_DISABLE == 0,
+            ),
+            _ => (self.nt_banks[slot & 0x03], true),
+        };
+        match ciram && bank >= CIRAM_BANKS {
+            true => Mapped::Ciram(
+                (bank & 0x01) as usize * 0x0400 + (addr & 0x03FF) as usize,
+            ),
+            false => {
+                Mapped::Chr(bank as usize * 0x0400 + (addr & 0x03FF) as usize)
+            }
+        }
+    }
+
+    /// Data port access, the address moves on after it when auto increment
+    /// is on.
+    fn sound_port(&mut self) -> usize {
+        let index: usize = (self.sound_address & 0x7F) as usize;
+        if self.sound_address & SOUND_AUTO_INCREMENT != 0 {
+            self.sound_address = SOUND_AUTO_INCREMENT
+                | (self.sound_address.wrapping_add(1) & 0x7F);
+        }
+        index
+    }
+
+    fn channel_count(&self) -> usize {
+        ((self.sound_ram[CHANNEL_COUNT] >> 4) & 0x07) as usize + 1
+    }
+
+    /// Channels are serviced from 7 downwards, one per 15 cpu cycles.
+    fn clock_audio(&mut self) {
+        self.sound_timer += 1;
+        if self.sound_timer < CYCLES_PER_CHANNEL {
+            return;
+        }
+        self.sound_timer = 0;
+
+        let lowest: usize = 8 - self.channel_count();
+        self.channel = match self.channel {
+            n if n <= lowest => 7,
+            n => n - 1,
+        };
+        self.update_channel(self.channel);
+    }
+
+    /// Channel registers, 8 bytes from $40 + channel * 8:
+    ///
+    /// ```no_run
+    /// +0/+2/+4  frequency, 18 bits (low, mid, bits 0-1 of +4)
+    /// +4        LLLL LLxx  wave length is 256 - 4 * L samples
+    /// +1/+3/+5  phase, 24 bits, the top 8 index the wave
+    /// +6        wave start, in 4-bit samples
+    /// +7        xxxx VVVV  volume
+    /// ```
+    fn update_channel(&mut self, channel: usize) {
+        let base: usize = 0x40 + channel * 8;
+        let ram = &mut self.sound_ram;
+        let frequency: u32 = ram[base] as u32
+            | (ram[base + 2] as u32) << 8
+            | ((ram[base + 4] & 0x03) as u32) << 16;
+        let length: u32 = 0x100 - (ram[base + 4] & 0xFC) as u32;
+        let mut phase: u32 = ram[base + 1] as u32
+            | (ram[base + 3] as u32) << 8
+            | (ram[base + 5] as u32) << 16;
+
+        phase = (phase + frequency) % (length << 16);
+        ram[base + 1] = phase as u8;
+        ram[base + 3] = (phase >> 8) as u8;
+        ram[base + 5] = (phase >> 16) as u8;
+
+        let index: u8 = ((phase >> 16) as u8).wrapping_add(ram[base + 6]);
+        let sample: u8 =
+            (ram[(index >> 1) as usize] >> ((index & 0x01) * 4)) & 0x0F;
+        let volume: u8 = ram[base + 7] & 0x0F;
+        self.outputs[channel] = (sample as i16 - 8) * volume as i16;
+    }
+}
+impl Mapper for M019 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x4800..=0x4FFF => {
+                let index: usize = self.sound_port();
+                Mapped::Data(self.sound_ram[index])
+            }
+            0x5000..=0x57FF => Mapped::Data(self.irq_counter as u8),
+            0x5800..=0x5FFF => Mapped::Data(
+                (self.irq_counter >> 8) as u8
+                    | if self.irq_enabled { IRQ_ENABLE } else { 0x00 },
+            ),
+            0x6000..=0x7FFF if self.data.prg_ram_size > 0 => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
+            0x8000..=0xFFFF => Mapped::Prg(self.prg_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x4800..=0x4FFF => {
+                let index: usize = self.sound_port();
+                self.sound_ram[index] = data;
+            }
+            0x5000..=0x57FF => {
+                self.irq_counter = (self.irq_counter & 0x7F00) | data as u16;
+                self.irq_active = false;
+            }
+            0x5800..=0x5FFF => {
+                self.irq_counter =
+                    (self.irq_counter & 0x00FF) | ((data & 0x7F) as u16) << 8;
+                self.irq_enabled = data & IRQ_ENABLE != 0;
+                self.irq_active = false;
+            }
+            0x6000..=0x7FFF if self.prg_ram_writable(addr) => {
+                return Mapped::PrgRam((addr & 0x1FFF) as usize);
+            }
+            0x8000..=0xBFFF => {
+                self.chr_banks[((addr - 0x8000) >> 11) as usize] = data
+            }
+            0xC000..=0xDFFF => {
+                self.nt_banks[((addr - 0xC000) >> 11) as usize] = data
+            }
+            0xE000..=0xE7FF => self.prg_banks[0] = data,
+            0xE800..=0xEFFF => self.prg_banks[1] = data,
+            0xF000..=0xF7FF => self.prg_banks[2] = data,
+            0xF800..=0xFFFF => {
+                self.write_protect = data;
+                self.sound_address = data;
+            }
+            _ => return Mapped::Unmapped,
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x3EFF => self.ppu_target(addr),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x3EFF => match self.ppu_target(addr) {
+                Mapped::Chr(_) if self.data.chr_bank != 0 => Mapped::Data(data),
+                target => target,
+            },
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_enabled && self.irq_counter < IRQ_COUNTER_MAX {
+            self.irq_counter += 1;
+            if self.irq_counter == IRQ_COUNTER_MAX {
+                self.irq_active = true;
+            }
+        }
+        self.clock_audio();
+    }
+
+    /// The channels take turns on the output, the average is what is left
+    /// after the console's filtering.
+    fn audio_output(&self) -> f32 {
+        if self.prg_banks[0] & SOUND_DISABLE != 0 {
+            return 0.0;
+        }
+        let count: usize = self.channel_count();
+        let sum: i16 = self.outputs[8 - count..].iter().sum();
+        sum as f32 / count as f32 * LEVEL_STEP
+    }
+
+    fn battery_ram(&self) -> Option<&[u8]> {
+        match self.data.battery {
+            true => Some(&self.sound_ram),
+            false => None,
+        }
+    }
+
+    fn load_battery_ram(&mut self, data: &[u8]) {
+        let len: usize = data.len().min(self.sound_ram.len());
+        self.sound_ram[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn reset(&mut self) {
+        self.irq_enabled = false;
+        self.irq_active = false;
+        self.outputs = [0; 8];
+    }
+}
@@ -0,0 +1,672 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+
+/// Side of a disk as stored in a .fds image, gaps and CRCs stripped.
+pub const FDS_SIDE_SIZE: usize = 65500;
+/// Every side starts with its disk info block.
+pub const FDS_SIDE_MAGIC: &[u8; 15] = b"\x01*NINTENDO-HVC*";
+/// fwNES header in front of some images, byte 4 is the side count.
+pub const FDS_HEADER_MAGIC: [u8; 4] = [0x46, 0x44, 0x53, 0x1A];
+pub const FDS_HEADER_SIZE: usize = 16;
+
+// $4022 timer IRQ control
+const TIMER_REPEAT: u8 = 0x01;
+const TIMER_ENABLE: u8 = 0x02;
+// $4023 master I/O enable
+const IO_DISK: u8 = 0x01;
+const IO_SOUND: u8 = 0x02;
+// $4025 drive control
+const CONTROL_MOTOR: u8 = 0x01;
+const CONTROL_RESET_TRANSFER: u8 = 0x02;
+const CONTROL_READ: u8 = 0x04;
+const CONTROL_MIRRORING: u8 = 0x08;
+const CONTROL_CRC: u8 = 0x10;
+const CONTROL_TRANSFER: u8 = 0x40;
+const CONTROL_IRQ: u8 = 0x80;
+// $4030 status
+const STATUS_TIMER_IRQ: u8 = 0x01;
+const STATUS_TRANSFERRED: u8 = 0x02;
+const STATUS_END_OF_HEAD: u8 = 0x40;
+// $4032 drive status
+const DRIVE_NO_DISK: u8 = 0x01;
+const DRIVE_NOT_READY: u8 = 0x02;
+const DRIVE_PROTECTED: u8 = 0x04;
+// $4033 external connector, bit 7 reports a healthy battery
+const BATTERY_GOOD: u8 = 0x80;
+
+/// Gap in front of the first block and between blocks, in bytes.
+const LEAD_IN_GAP: usize = 28300 / 8;
+const BLOCK_GAP: usize = 976 / 8;
+/// A side with its gaps and CRCs, rounded up to leave room for writes.
+const RAW_SIDE_SIZE: usize = 68000;
+/// Cpu cycles the head needs to get back to the start of the disk, and to
+/// move on by one byte.
+const REWIND_CYCLES: u32 = 50000;
+const BYTE_CYCLES: u32 = 150;
+/// Time the drive stays empty when switching sides, long enough for the
+/// BIOS to notice the disk went away.
+const SWAP_CYCLES: u32 = 1_000_000;
+
+/// Famicom Disk System RAM adapter (mapper 20).
+///
+/// ```no_run
+/// $4020-$4021  timer IRQ reload value     $4022  timer control  xxxx xxER
+/// $4023  I/O enable  xxxx xxSD  S: sound, D: disk registers
+/// $4024  data to write                    $4025  drive control  IS1B MRTD
+/// $4030  status (reads acknowledge)       $4031  data read
+/// $4032  drive status  xxxx xPRN  P: protected, R: not ready, N: no disk
+/// $4040-$4092  wavetable audio
+/// $6000-$DFFF  32KB PRG RAM
+/// $E000-$FFFF  the 8KB BIOS
+/// ```
+///
+/// In $4025 D runs the motor, T resets the transfer, R reads (clear
+/// writes), M picks horizontal mirroring, B sends the CRC, S starts the
+/// transfer of a block and I raises an IRQ per byte.
+///
+/// The disk sides are kept as the drive sees them, with the gaps and block
+/// start marks a .fds image leaves out, and one byte passes under the head
+/// every 150 cpu cycles. CRCs are not checked: $4030 never reports an
+/// error and the CRC written after a block is zero.
+#[derive(Debug, Clone)]
+pub struct M020 {
+    data: MapperData,
+    sides: Vec<Vec<u8>>,
+    side: Option<usize>,
+    pending_side: Option<usize>,
+    swap_delay: u32,
+
+    io_enable: u8,
+    control: u8,
+    timer_control: u8,
+    timer_reload: u16,
+    timer_counter: u16,
+    timer_irq: bool,
+
+    disk_irq: bool,
+    transferred: bool,
+    read_data: u8,
+    write_data: u8,
+    position: usize,
+    delay: u32,
+    end_of_head: bool,
+    scanning: bool,
+    gap_ended: bool,
+
+    audio: FdsAudio,
+}
+impl M020 {
+    /// Builds the adapter around the disk sides of a .fds image, the first
+    /// side starts in the drive.
+    pub fn new(data: MapperData, sides: &[&[u8]]) -> Self {
+        Self {
+            data,
+            sides: sides.iter().map(|side| raw_side(side)).collect(),
+            side: (!sides.is_empty()).then_some(0),
+            pending_side: None,
+            swap_delay: 0,
+            io_enable: IO_DISK | IO_SOUND,
+            control: CONTROL_RESET_TRANSFER,
+            timer_control: 0x00,
+            timer_reload: 0x0000,
+            timer_counter: 0x0000,
+            timer_irq: false,
+            disk_irq: false,
+            transferred: false,
+            read_data: 0x00,
+            write_data: 0x00,
+            position: 0,
+            delay: 0,
+            end_of_head: true,
+            scanning: false,
+            gap_ended: false,
+            audio: FdsAudio::new(),
+        }
+    }
+
+    #[inline]
+    fn disk_enabled(&self) -> bool {
+        self.io_enable & IO_DISK != 0
+    }
+
+    fn read_register(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x4030 => {
+                let flag = |set: bool, bit: u8| if set { bit } else { 0x00 };
+                let status: u8 = flag(self.timer_irq, STATUS_TIMER_IRQ)
+                    | flag(self.transferred, STATUS_TRANSFERRED)
+                    | flag(self.end_of_head, STATUS_END_OF_HEAD);
+                self.timer_irq = false;
+                self.transferred = false;
+                self.disk_irq = false;
+                Mapped::Data(status)
+            }
+            0x4031 => {
+                self.transferred = false;
+                self.disk_irq = false;
+                Mapped::Data(self.read_data)
+            }
+            0x4032 => Mapped::Data(match self.side {
+                None => DRIVE_NO_DISK | DRIVE_NOT_READY | DRIVE_PROTECTED,
+                Some(_) if !self.scanning => DRIVE_NOT_READY,
+                Some(_) => 0x00,
+            }),
+            0x4033 => Mapped::Data(BATTERY_GOOD),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4020 => {
+                self.timer_reload = (self.timer_reload & 0xFF00) | data as u16
+            }
+            0x4021 => {
+                self.timer_reload =
+                    (self.timer_reload & 0x00FF) | (data as u16) << 8
+            }
+            0x4022 => {
+                self.timer_control = data;
+                if data & TIMER_ENABLE != 0 {
+                    self.timer_counter = self.timer_reload;
+                } else {
+                    self.timer_irq = false;
+                }
+            }
+            0x4023 => {
+                self.io_enable = data;
+                if data & IO_DISK == 0 {
+                    self.timer_control &= !TIMER_ENABLE;
+                    self.timer_irq = false;
+                }
+            }
+            0x4024 => {
+                self.write_data = data;
+                self.transferred = false;
+                self.disk_irq = false;
+            }
+            0x4025 => {
+                self.control = data;
+                self.disk_irq = false;
+            }
+            _ => (),
+        }
+    }
+
+    fn clock_timer(&mut self) {
+        if self.timer_control & TIMER_ENABLE == 0 || !self.disk_enabled() {
+            return;
+        }
+        if self.timer_counter == 0 {
+            self.timer_irq = true;
+            self.timer_counter = self.timer_reload;
+            if self.timer_control & TIMER_REPEAT == 0 {
+                self.timer_control &= !TIMER_ENABLE;
+            }
+        } else {
+            self.timer_counter -= 1;
+        }
+    }
+
+    /// Moves the head along the disk and transfers one byte whenever it
+    /// reaches the next.
+    fn clock_drive(&mut self) {
+        if self.swap_delay > 0 {
+            self.swap_delay -= 1;
+            if self.swap_delay == 0 {
+                self.side = self.pending_side.take();
+            }
+        }
+
+        let Some(side) = self.side else {
+            self.end_of_head = true;
+            self.scanning = false;
+            return;
+        };
+        if self.control & CONTROL_MOTOR == 0 {
+            self.end_of_head = true;
+            self.scanning = false;
+            return;
+        }
+        if self.control & CONTROL_RESET_TRANSFER != 0 && !self.scanning {
+            return;
+        }
+        if self.end_of_head {
+            self.delay = REWIND_CYCLES;
+            self.end_of_head = false;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        let transfer: bool = self.control & CONTROL_TRANSFER != 0;
+        let mut irq: bool = self.control & CONTROL_IRQ != 0;
+        if self.control & CONTROL_READ != 0 {
+            let byte: u8 = self.sides[side][self.position];
+            if !transfer {
+                self.gap_ended = false;
+            } else if byte != 0x00 && !self.gap_ended {
+                // the block start mark is latched without an IRQ
+                self.gap_ended = true;
+                irq = false;
+            }
+            if self.gap_ended {
+                self.transferred = true;
+                self.read_data = byte;
+                self.disk_irq |= irq;
+            }
+        } else {
+            let mut byte: u8 = 0x00;
+            if self.control & CONTROL_CRC == 0 {
+                self.transferred = true;
+                byte = self.write_data;
+                self.disk_irq |= irq;
+            }
+            if !transfer {
+                byte = 0x00;
+            }
+            self.sides[side][self.position] = byte;
+            self.gap_ended = false;
+        }
+
+        self.position += 1;
+        if self.position >= self.sides[side].len() {
+            self.control &= !CONTROL_MOTOR;
+        } else {
+            self.delay = BYTE_CYCLES;
+        }
+    }
+}
+impl Mapper for M020 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x4030..=0x4033 if self.disk_enabled() => self.read_register(addr),
+            0x4040..=0x4097 if self.io_enable & IO_SOUND != 0 => {
+                self.audio.read(addr)
+            }
+            0x6000..=0xDFFF => Mapped::PrgRam((addr - 0x6000) as usize),
+            0xE000..=0xFFFF => Mapped::Prg((addr & 0x1FFF) as usize),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x4023 => self.write_register(addr, data),
+            0x4020..=0x4026 if self.disk_enabled() => {
+                self.write_register(addr, data)
+            }
+            0x4040..=0x408A if self.io_enable & IO_SOUND != 0 => {
+                self.audio.write(addr, data)
+            }
+            0x6000..=0xDFFF => return Mapped::PrgRam((addr - 0x6000) as usize),
+            _ => return Mapped::Unmapped,
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(addr as usize),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(addr as usize),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(match self.control & CONTROL_MIRRORING {
+            0 => Mirroring::VERTICAL,
+            _ => Mirroring::HORIZONTAL,
+        })
+    }
+
+    fn irq_state(&self) -> bool {
+        self.timer_irq || self.disk_irq
+    }
+
+    fn cpu_clock(&mut self) {
+        self.clock_timer();
+        self.clock_drive();
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn disk_sides(&self) -> usize {
+        self.sides.len()
+    }
+
+    fn disk_side(&self) -> Option<usize> {
+        self.side
+    }
+
+    fn insert_disk(&mut self, side: Option<usize>) {
+        let side: Option<usize> = side.filter(|n| *n < self.sides.len());
+        if self.side.is_none() || side.is_none() {
+            self.side = side;
+            self.pending_side = None;
+            self.swap_delay = 0;
+        } else {
+            // the drive has to be seen empty before the new side goes in
+            self.side = None;
+            self.pending_side = side;
+            self.swap_delay = SWAP_CYCLES;
+        }
+    }
+
+    fn disk_image(&self) -> Option<Vec<u8>> {
+        Some(self.sides.iter().flat_map(|side| fds_side(side)).collect())
+    }
+
+    fn reset(&mut self) {
+        self.timer_control = 0x00;
+        self.timer_irq = false;
+        self.disk_irq = false;
+        self.control = CONTROL_RESET_TRANSFER;
+        self.end_of_head = true;
+        self.scanning = false;
+        self.audio = FdsAudio::new();
+    }
+}
+
+/// Length of the block starting with `kind`, `file_size` comes from the
+/// file header block in front of a file's data.
+fn block_size(kind: u8, file_size: usize) -> Option<usize> {
+    match kind {
+        1 => Some(56),
+        2 => Some(2),
+        3 => Some(16),
+        4 => Some(1 + file_size),
+        _ => None,
+    }
+}
+
+/// Walks the blocks of a side, handing each one to `f`. `next` finds the
+/// start of the following block and returns `None` past the last one.
+fn walk_blocks(
+    side: &[u8],
+    mut next: impl FnMut(usize) -> Option<usize>,
+    mut f: impl FnMut(&[u8]),
+) {
+    let mut file_size: usize = 0;
+    let mut pos: usize = 0;
+    while let Some(start) = next(pos) {
+        let Some(size) =
+            side.get(start).and_then(|k| block_size(*k, file_size))
+        else {
+            break;
+        };
+        let Some(block) = side.get(start..start + size) else {
+            break;
+        };
+        if block[0] == 3 {
+            file_size = u16::from_le_bytes([block[13], block[14]]) as usize;
+        }
+        f(block);
+        pos = start + size;
+    }
+}
+
+/// Lays out a .fds side the way it passes under the head: a lead-in gap,
+/// then every block behind a start mark, followed by its CRC and a gap.
+fn raw_side(side: &[u8]) -> Vec<u8> {
+    let mut raw: Vec<u8> = vec![0x00; LEAD_IN_GAP];
+    walk_blocks(side, Some, |block| {
+        raw.push(0x80);
+        raw.extend_from_slice(block);
+        raw.extend_from_slice(&[0x00, 0x00]); // CRC
+        raw.extend(std::iter::repeat_n(0x00, BLOCK_GAP));
+    });
+    raw.resize(raw.len().max(RAW_SIDE_SIZE), 0x00);
+    raw
+}
+
+/// The reverse of [`raw_side`], picks the blocks back out from between the
+/// gaps.
+fn fds_side(raw: &[u8]) -> Vec<u8> {
+    let mut side: Vec<u8> = Vec::with_capacity(FDS_SIDE_SIZE);
+    walk_blocks(
+        raw,
+        |pos| {
+            // skip the CRC, then the gap up to the start mark
+            let from: usize = if pos == 0 { 0 } else { pos + 2 };
+            let mark: usize =
+                from + raw.get(from..)?.iter().position(|b| *b != 0)?;
+            Some(mark + 1)
+        },
+        |block| side.extend_from_slice(block),
+    );
+    side.resize(FDS_SIDE_SIZE, 0x00);
+    side
+}
+
+/// Envelope shared by the volume and modulation gains.
+///
+/// ```no_run
+/// $4080/$4084  MDSS SSSS  M: manual (gain = S), D: increase, S: speed
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+struct FdsEnvelope {
+    control: u8,
+    gain: u8,
+    timer: u32,
+}
+impl FdsEnvelope {
+    fn write(&mut self, data: u8) {
+        self.control = data;
+        self.timer = 0;
+        if data & 0x80 != 0 {
+            self.gain = data & 0x3F;
+        }
+    }
+
+    /// Ticks every 8 * (speed + 1) * master speed cpu cycles.
+    fn clock(&mut self, master_speed: u8) {
+        if self.control & 0x80 != 0 || master_speed == 0 {
+            return;
+        }
+        self.timer += 1;
+        let period: u32 =
+            8 * ((self.control & 0x3F) as u32 + 1) * master_speed as u32;
+        if self.timer < period {
+            return;
+        }
+        self.timer = 0;
+        if self.control & 0x40 != 0 {
+            self.gain = (self.gain + 1).min(32);
+        } else {
+            self.gain = self.gain.saturating_sub(1);
+        }
+    }
+}
+
+/// Output of a full scale wave at full gain and master volume, the FDS is
+/// a fair bit louder than an APU pulse channel.
+const FDS_FULL_SCALE: f32 = 0.35;
+/// Master volume from $4089, 2/2, 2/3, 2/4 and 2/5.
+const FDS_MASTER_VOLUME: [f32; 4] = [1.0, 2.0 / 3.0, 2.0 / 4.0, 2.0 / 5.0];
+/// Steps the modulation table applies to the sweep bias, 4 resets it.
+const FDS_MOD_STEPS: [i8; 8] = [0, 1, 2, 4, 0, -4, -2, -1];
+
+/// FDS wavetable channel, a 64 step 6-bit wave with a frequency modulator.
+///
+/// ```no_run
+/// $4040-$407F  wave table (writable while $4089 bit 7 is set)
+/// $4080        volume envelope
+/// $4082-$4083  frequency, 12 bits  $4083: HE..  H: halt wave, E: halt envelopes
+/// $4084        modulation envelope
+/// $4085        modulation counter (7-bit signed)
+/// $4086-$4087  modulation frequency, 12 bits  $4087: H: halt modulation
+/// $4088        modulation table, two entries per write while halted
+/// $4089        W..  ..VV  W: wave write, V: master volume
+/// $408A        envelope speed multiplier
+/// $4090/$4092  volume/modulation gain (reads)
+/// ```
+#[derive(Debug, Clone, Copy)]
+struct FdsAudio {
+    wave: [u8; 64],
+    mod_table: [u8; 64],
+    volume: FdsEnvelope,
+    modulation: FdsEnvelope,
+    frequency: u16,
+    frequency_control: u8, // $4083 high bits
+    mod_frequency: u16,
+    mod_halted: bool,
+    mod_counter: i8,
+    mod_position: u8,
+    mod_accumulator: u32,
+    wave_accumulator: u32,
+    master: u8, // $4089
+    envelope_speed: u8,
+    output: u8,
+}
+impl FdsAudio {
+    fn new() -> Self {
+        Self {
+            wave: [0x00; 64],
+            mod_table: [0x00; 64],
+            volume: FdsEnvelope::default(),
+            modulation: FdsEnvelope::default(),
+            frequency: 0,
+            frequency_control: 0x00,
+            mod_frequency: 0,
+            mod_halted: true,
+            mod_counter: 0,
+            mod_position: 0,
+            mod_accumulator: 0,
+            wave_accumulator: 0,
+            master: 0x00,
+            envelope_speed: 0xE8,
+            output: 0,
+        }
+    }
+
+    fn read(&self, addr: u16) -> Mapped {
+        match addr {
+            0x4040..=0x407F => Mapped::Data(self.wave[(addr & 0x3F) as usize]),
+            0x4090 => Mapped::Data(self.volume.gain),
+            0x4092 => Mapped::Data(self.modulation.gain),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4040..=0x407F if self.master & 0x80 != 0 => {
+                self.wave[(addr & 0x3F) as usize] = data & 0x3F
+            }
+            0x4080 => self.volume.write(data),
+            0x4082 => self.frequency = (self.frequency & 0x0F00) | data as u16,
+            0x4083 => {
+                self.frequency =
+                    (self.frequency & 0x00FF) | ((data & 0x0F) as u16) << 8;
+                self.frequency_control = data;
+                if data & 0x80 != 0 {
+                    self.wave_accumulator = 0;
+                }
+            }
+            0x4084 => self.modulation.write(data),
+            0x4085 => self.mod_counter = ((data << 1) as i8) >> 1,
+            0x4086 => {
+                self.mod_frequency = (self.mod_frequency & 0x0F00) | data as u16
+            }
+            0x4087 => {
+                self.mod_frequency =
+                    (self.mod_frequency & 0x00FF) | ((data & 0x0F) as u16) << 8;
+                self.mod_halted = data & 0x80 != 0;
+                if self.mod_halted {
+                    self.mod_accumulator = 0;
+                }
+            }
+            0x4088 if self.mod_halted => {
+                let pos: usize = self.mod_position as usize;
+                self.mod_table[pos] = data & 0x07;
+                self.mod_table[(pos + 1) & 0x3F] = data & 0x07;
+                self.mod_position = (self.mod_position + 2) & 0x3F;
+            }
+            0x4089 => self.master = data,
+            0x408A => self.envelope_speed = data,
+            _ => (),
+        }
+    }
+
+    /// Frequency after modulation, following the hardware's integer math.
+    fn modulated_frequency(&self) -> u32 {
+        let mut temp: i32 =
+            self.mod_counter as i32 * self.modulation.gain as i32;
+        let remainder: i32 = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if self.mod_counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+        temp *= self.frequency as i32;
+        let remainder: i32 = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+        (self.frequency as i32 + temp).max(0) as u32
+    }
+
+    fn clock(&mut self) {
+        let halted: bool = self.frequency_control & 0x80 != 0;
+        if !halted && self.frequency_control & 0x40 == 0 {
+            self.volume.clock(self.envelope_speed);
+            self.modulation.clock(self.envelope_speed);
+        }
+
+        if !self.mod_halted && self.mod_frequency != 0 {
+            self.mod_accumulator += self.mod_frequency as u32;
+            if self.mod_accumulator >= 0x10000 {
+                self.mod_accumulator &= 0xFFFF;
+                let step: u8 = self.mod_table[self.mod_position as usize];
+                self.mod_counter = match step {
+                    4 => 0,
+                    n => {
+                        let counter: i8 = self
+                            .mod_counter
+                            .wrapping_add(FDS_MOD_STEPS[n as usize]);
+                        // 7-bit signed, wraps from 63 to -64
+                        (counter << 1) >> 1
+                    }
+                };
+                self.mod_position = (self.mod_position + 1) & 0x3F;
+            }
+        }
+
+        // the output holds while the wave is being rewritten
+        if halted || self.master & 0x80 != 0 {
+            return;
+        }
+        self.wave_accumulator =
+            (self.wave_accumulator + self.modulated_frequency()) & 0x3FFFFF;
+        self.output = self.wave[(self.wave_accumulator >> 16) as usize & 0x3F];
+    }
+
+    fn output(&self) -> f32 {
+        let gain: f32 = self.volume.gain.min(32) as f32 / 32.0;
+        self.output as f32 / 63.0
+            * gain
+            * FDS_MASTER_VOLUME[(self.master & 0x03) as usize]
+            * FDS_FULL_SCALE
+    }
+}
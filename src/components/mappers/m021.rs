@@ -0,0 +1,207 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+use super::vrc::VrcIrq;
+
+// $9002 (VRC4)
+const PRG_SWAP_MODE: u8 = 0x02;
+
+/// Konami VRC2 and VRC4, spread over four mapper numbers because every
+/// board revision wired different cpu address lines to the chip's two
+/// register select pins. Registers are listed with the chip's own A1/A0:
+///
+/// ```no_run
+/// $8000-$8003  PRG bank at $8000 (or $C000 in swap mode)
+/// $9000/$9001  mirroring, 0: vertical, 1: horizontal, 2/3: one-screen
+/// $9002/$9003  VRC4 only, bit 1 swaps $8000 and $C000
+/// $A000-$A003  PRG bank at $A000
+/// $B000-$E003  CHR banks 0-7, low nibble at even, high bits at odd
+/// $F000/$F001  VRC4 IRQ latch, low and high nibble
+/// $F002        VRC4 IRQ control
+/// $F003        VRC4 IRQ acknowledge
+/// ```
+///
+/// The last two 8KB banks are fixed, CHR banks are 1KB each with a 9-bit
+/// bank number. VRC2 has no IRQ and no PRG swap mode, and boards without
+/// work ram have a single bit latch at $6000 instead.
+///
+/// ```no_run
+/// mapper/submapper  chip   A0     A1
+/// 21/1              VRC4a  A1     A2
+/// 21/2              VRC4c  A6     A7
+/// 22                VRC2a  A1     A0   (CHR bank numbers are halved)
+/// 23/1              VRC4f  A0     A1
+/// 23/2              VRC4e  A2     A3
+/// 23/3              VRC2b  A0     A1
+/// 25/1              VRC4b  A1     A0
+/// 25/2              VRC4d  A3     A2
+/// 25/3              VRC2c  A1     A0
+/// ```
+///
+/// Submapper 0 means the board is unknown, then both candidate lines are
+/// ORed together which works for every known game.
+#[derive(Debug, Clone, Copy)]
+pub struct M021 {
+    data: MapperData,
+    mapper_id: u16,
+    vrc2: bool,
+    prg_banks: [u8; 2],
+    prg_swap: bool,
+    chr_banks: [u16; 8],
+    mirror: Mirroring,
+    latch: u8, // VRC2 $6000 microwire latch
+    irq: VrcIrq,
+}
+impl M021 {
+    pub fn new(data: MapperData, mapper_id: u16) -> Self {
+        let vrc2: bool = mapper_id == 22 || data.submapper == 3;
+        Self {
+            data,
+            mapper_id,
+            vrc2,
+            prg_banks: [0x00; 2],
+            prg_swap: false,
+            chr_banks: [0; 8],
+            mirror: Mirroring::VERTICAL,
+            latch: 0x00,
+            irq: VrcIrq::new(),
+        }
+    }
+
+    /// Translates a cpu address into the chip's A1/A0 register select.
+    fn register(&self, addr: u16) -> u16 {
+        let line = |n: u16| (addr >> n) & 0x01;
+        let (a0, a1): (u16, u16) = match (self.mapper_id, self.data.submapper) {
+            (21, 1) => (line(1), line(2)),
+            (21, 2) => (line(6), line(7)),
+            (21, _) => (line(1) | line(6), line(2) | line(7)),
+            (22, _) => (line(1), line(0)),
+            (23, 1) | (23, 3) => (line(0), line(1)),
+            (23, 2) => (line(2), line(3)),
+            (23, _) => (line(0) | line(2), line(1) | line(3)),
+            (25, 1) | (25, 3) => (line(1), line(0)),
+            (25, 2) => (line(3), line(2)),
+            (_, _) => (line(1) | line(3), line(0) | line(2)),
+        };
+        (addr & 0xF000) | (a1 << 1) | a0
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let second_last: usize =
+            (self.data.prg_bank as usize * 2).saturating_sub(2);
+        let bank: usize = match ((addr >> 13) & 0x03, self.prg_swap) {
+            (0, false) | (2, true) => (self.prg_banks[0] & 0x1F) as usize,
+            (0, true) | (2, false) => second_last,
+            (1, _) => (self.prg_banks[1] & 0x1F) as usize,
+            (_, _) => second_last + 1,
+        };
+        bank * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank: u16 = self.chr_banks[(addr >> 10) as usize];
+        // VRC2a leaves CHR A10 unconnected
+        let bank: u16 = match self.mapper_id {
+            22 => bank >> 1,
+            _ => bank,
+        };
+        bank as usize * 0x0400 + (addr & 0x03FF) as usize
+    }
+}
+impl Mapper for M021 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.data.prg_ram_size > 0 => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
+            0x6000..=0x6FFF if self.vrc2 => Mapped::Data(self.latch),
+            0x8000..=0xFFFF => Mapped::Prg(self.prg_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        if addr < 0x8000 {
+            return match addr {
+                0x6000..=0x7FFF if self.data.prg_ram_size > 0 => {
+                    Mapped::PrgRam((addr & 0x1FFF) as usize)
+                }
+                0x6000..=0x6FFF if self.vrc2 => {
+                    self.latch = data & 0x01;
+                    Mapped::Data(data)
+                }
+                _ => Mapped::Unmapped,
+            };
+        }
+
+        match self.register(addr) {
+            0x8000..=0x8003 => self.prg_banks[0] = data,
+            0x9000 | 0x9001 => {
+                let mask: u8 = if self.vrc2 { 0x01 } else { 0x03 };
+                self.mirror = match data & mask {
+                    0 => Mirroring::VERTICAL,
+                    1 => Mirroring::HORIZONTAL,
+                    2 => Mirroring::ONESCREAN_LO,
+                    _ => Mirroring::ONESCREAN_HI,
+                };
+            }
+            0x9002 | 0x9003 if !self.vrc2 => {
+                self.prg_swap = data & PRG_SWAP_MODE != 0;
+            }
+            0xA000..=0xA003 => self.prg_banks[1] = data,
+            reg @ 0xB000..=0xE003 => {
+                let bank: usize =
+                    (((reg >> 12) - 0x0B) * 2 + ((reg >> 1) & 0x01)) as usize;
+                let value: u16 = self.chr_banks[bank];
+                self.chr_banks[bank] = match reg & 0x01 {
+                    0 => (value & 0x1F0) | (data & 0x0F) as u16,
+                    _ => (value & 0x00F) | ((data & 0x1F) as u16) << 4,
+                };
+            }
+            0xF000 if !self.vrc2 => {
+                let latch: u8 = self.irq.latch();
+                self.irq.set_latch((latch & 0xF0) | (data & 0x0F));
+            }
+            0xF001 if !self.vrc2 => {
+                let latch: u8 = self.irq.latch();
+                self.irq.set_latch((latch & 0x0F) | (data << 4));
+            }
+            0xF002 if !self.vrc2 => self.irq.write_control(data),
+            0xF003 if !self.vrc2 => self.irq.acknowledge(),
+            _ => (),
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirror)
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+    }
+
+    fn reset(&mut self) {
+        self.irq = VrcIrq::new();
+    }
+}
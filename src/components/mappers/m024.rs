@@ -0,0 +1,316 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+use super::vrc::VrcIrq;
+
+// $B003 PPU banking style
+const BANKING_CHR_MODE: u8 = 0x03;
+const BANKING_MIRRORING: u8 = 0x0C;
+const BANKING_PRG_RAM: u8 = 0x80;
+// $9003 audio control
+const AUDIO_HALT: u8 = 0x01;
+const AUDIO_SHIFT_4: u8 = 0x02;
+const AUDIO_SHIFT_8: u8 = 0x04;
+// $9002/$A002/$B002
+const CHANNEL_ENABLE: u8 = 0x80;
+
+/// Linear approximation of the APU's pulse curve near silence, one step of
+/// VRC6 volume is about as loud as one step of an APU pulse channel.
+const VOLUME_STEP: f32 = 0.00752;
+
+/// Konami VRC6 (mapper 24, mapper 26 has A0 and A1 swapped).
+///
+/// ```no_run
+/// $8000-$8003  16KB PRG bank at $8000
+/// $9000-$9002  pulse 1          $9003  audio control  xxxx xBAH
+/// $A000-$A002  pulse 2
+/// $B000-$B002  sawtooth         $B003  PPU banking    WxxN MMDD
+/// $C000-$C003  8KB PRG bank at $C000, the last bank is fixed at $E000
+/// $D000-$E003  CHR registers R0-R7
+/// $F000-$F002  IRQ latch, control and acknowledge
+/// ```
+///
+/// In $B003, DD picks the CHR layout (0: 8x1KB, 1: 4x2KB, 2/3: 4x1KB then
+/// 2x2KB), MM the mirroring and W enables PRG RAM. Nametables sourced from
+/// CHR ROM (N) are not used by any game and not emulated.
+#[derive(Debug, Clone, Copy)]
+pub struct M024 {
+    data: MapperData,
+    swapped: bool, // mapper 26
+    prg_16k: u8,
+    prg_8k: u8,
+    chr_banks: [u8; 8],
+    banking: u8,
+    irq: VrcIrq,
+
+    audio_control: u8,
+    pulse: [VrcPulse; 2],
+    saw: VrcSaw,
+}
+impl M024 {
+    pub fn new(data: MapperData, mapper_id: u16) -> Self {
+        Self {
+            data,
+            swapped: mapper_id == 26,
+            prg_16k: 0x00,
+            prg_8k: 0x00,
+            chr_banks: [0x00; 8],
+            banking: 0x00,
+            irq: VrcIrq::new(),
+            audio_control: 0x00,
+            pulse: [VrcPulse::default(); 2],
+            saw: VrcSaw::default(),
+        }
+    }
+
+    #[inline]
+    fn register(&self, addr: u16) -> u16 {
+        match self.swapped {
+            true => {
+                (addr & 0xF000) | ((addr & 0x01) << 1) | ((addr >> 1) & 0x01)
+            }
+            false => addr & 0xF003,
+        }
+    }
+
+    #[inline]
+    fn prg_ram_enabled(&self) -> bool {
+        self.data.prg_ram_size > 0 && self.banking & BANKING_PRG_RAM != 0
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let r = |n: usize| self.chr_banks[n] as usize;
+        let slot: usize = (addr >> 10) as usize;
+        match (self.banking & BANKING_CHR_MODE, slot) {
+            (0, n) => r(n) * 0x0400 + (addr & 0x03FF) as usize,
+            (1, n) => r(n / 2) * 0x0800 + (addr & 0x07FF) as usize,
+            (_, 0..=3) => r(slot) * 0x0400 + (addr & 0x03FF) as usize,
+            (_, n) => r(4 + (n - 4) / 2) * 0x0800 + (addr & 0x07FF) as usize,
+        }
+    }
+
+    /// The channels' timers divide the cpu clock, $9003 can speed all of
+    /// them up by 16 or 256 or stop them.
+    fn clock_audio(&mut self) {
+        if self.audio_control & AUDIO_HALT != 0 {
+            return;
+        }
+        let shift: u8 = match self.audio_control {
+            c if c & AUDIO_SHIFT_8 != 0 => 8,
+            c if c & AUDIO_SHIFT_4 != 0 => 4,
+            _ => 0,
+        };
+        self.pulse.iter_mut().for_each(|p| p.clock(shift));
+        self.saw.clock(shift);
+    }
+}
+impl Mapper for M024 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
+            0x8000..=0xBFFF => Mapped::Prg(
+                self.prg_16k as usize * 0x4000 + (addr & 0x3FFF) as usize,
+            ),
+            0xC000..=0xDFFF => Mapped::Prg(
+                self.prg_8k as usize * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            0xE000..=0xFFFF => {
+                let last: usize =
+                    (self.data.prg_bank as usize * 2).saturating_sub(1);
+                Mapped::Prg(last * 0x2000 + (addr & 0x1FFF) as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        if addr < 0x8000 {
+            return match addr {
+                0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                    Mapped::PrgRam((addr & 0x1FFF) as usize)
+                }
+                _ => Mapped::Unmapped,
+            };
+        }
+
+        match self.register(addr) {
+            0x8000..=0x8003 => self.prg_16k = data & 0x0F,
+            reg @ 0x9000..=0x9002 => self.pulse[0].write(reg, data),
+            0x9003 => self.audio_control = data,
+            reg @ 0xA000..=0xA002 => self.pulse[1].write(reg, data),
+            reg @ 0xB000..=0xB002 => self.saw.write(reg, data),
+            0xB003 => self.banking = data,
+            0xC000..=0xC003 => self.prg_8k = data & 0x1F,
+            reg @ 0xD000..=0xE003 => {
+                let bank: usize =
+                    (((reg >> 12) - 0x0D) * 4 + (reg & 0x03)) as usize;
+                self.chr_banks[bank] = data;
+            }
+            0xF000 => self.irq.set_latch(data),
+            0xF001 => self.irq.write_control(data),
+            0xF002 => self.irq.acknowledge(),
+            _ => (),
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(match (self.banking & BANKING_MIRRORING) >> 2 {
+            0 => Mirroring::VERTICAL,
+            1 => Mirroring::HORIZONTAL,
+            2 => Mirroring::ONESCREAN_LO,
+            _ => Mirroring::ONESCREAN_HI,
+        })
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+        self.clock_audio();
+    }
+
+    fn audio_output(&self) -> f32 {
+        let level: u8 =
+            self.pulse[0].output() + self.pulse[1].output() + self.saw.output();
+        level as f32 * VOLUME_STEP
+    }
+
+    fn reset(&mut self) {
+        self.irq = VrcIrq::new();
+        self.audio_control = 0x00;
+        self.pulse = [VrcPulse::default(); 2];
+        self.saw = VrcSaw::default();
+    }
+}
+
+/// VRC6 pulse: 16 step sequencer with 8 duty settings and a 4-bit volume.
+///
+/// ```no_run
+/// $x000  MDDD VVVV  M: ignore duty (constant output), D: duty, V: volume
+/// $x001  period low
+/// $x002  Exxx PPPP  E: enable, P: period high
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+struct VrcPulse {
+    control: u8,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+}
+impl VrcPulse {
+    fn write(&mut self, addr: u16, data: u8) {
+        match addr & 0x03 {
+            0 => self.control = data,
+            1 => self.period = (self.period & 0x0F00) | data as u16,
+            _ => {
+                self.period =
+                    (self.period & 0x00FF) | ((data & 0x0F) as u16) << 8;
+                self.enabled = data & CHANNEL_ENABLE != 0;
+                // disabling the channel restarts the duty cycle
+                if !self.enabled {
+                    self.step = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> shift;
+            self.step = (self.step + 1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        let duty: u8 = (self.control >> 4) & 0x07;
+        let constant: bool = self.control & 0x80 != 0;
+        match self.enabled && (constant || self.step <= duty) {
+            true => self.control & 0x0F,
+            false => 0,
+        }
+    }
+}
+
+/// VRC6 sawtooth: an accumulator that gains the rate every other timer
+/// tick and resets after the seventh addition.
+///
+/// ```no_run
+/// $B000  xxAA AAAA  accumulator rate
+/// $B001  period low
+/// $B002  Exxx PPPP  E: enable, P: period high
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+struct VrcSaw {
+    rate: u8,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+    accumulator: u8,
+}
+impl VrcSaw {
+    fn write(&mut self, addr: u16, data: u8) {
+        match addr & 0x03 {
+            0 => self.rate = data & 0x3F,
+            1 => self.period = (self.period & 0x0F00) | data as u16,
+            _ => {
+                self.period =
+                    (self.period & 0x00FF) | ((data & 0x0F) as u16) << 8;
+                self.enabled = data & CHANNEL_ENABLE != 0;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer != 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period >> shift;
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step.is_multiple_of(2) {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
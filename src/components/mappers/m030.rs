@@ -0,0 +1,203 @@
+use std::ops::Range;
+
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// bank register ($8000-$FFFF, $C000-$FFFF on flashable boards)
+const BANK_PRG: u8 = 0x1F;
+const BANK_CHR: u8 = 0x60;
+const BANK_ONESCREEN: u8 = 0x80;
+
+/// Flash addresses the command sequences are written to, only A0-A14 are
+/// decoded.
+const FLASH_UNLOCK_1: usize = 0x5555;
+const FLASH_UNLOCK_2: usize = 0x2AAA;
+const FLASH_ADDRESS_MASK: usize = 0x7FFF;
+const FLASH_SECTOR_SIZE: usize = 0x1000;
+/// SST39SF040 software ID: manufacturer and device code.
+const FLASH_ID: [u8; 2] = [0xBF, 0xB7];
+
+/// Where the SST39SF040 is in its command sequence.
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Flash {
+    IDLE,
+    UNLOCKED, // $AA to $5555
+    COMMAND,  // $55 to $2AAA, the next write to $5555 is the command
+    PROGRAM,  // the next write stores a byte
+    ERASE,    // $80, erase needs a second unlock
+    ERASE_UNLOCKED,
+    ERASE_COMMAND, // $30 to a sector or $10 to $5555
+}
+
+/// UNROM 512 (mapper 30), the board most homebrew ships on.
+///
+/// ```no_run
+/// 7  bit  0
+/// MCCP PPPP  M: one-screen page, C: 8KB CHR RAM bank, P: 16KB PRG bank
+/// ```
+///
+/// The PRG bank is switched in at $8000 with the last bank fixed at $C000,
+/// and the 32KB of CHR RAM are banked 8KB at a time. The header's four
+/// screen bit has a meaning of its own: alone it selects one-screen
+/// mirroring switched by M, together with the mirroring bit it puts the
+/// nametables in the last 8KB of CHR RAM.
+///
+/// With the battery bit set the board is flashable: the register moves to
+/// $C000-$FFFF and writes to $8000-$BFFF reach the SST39SF040 flash chip,
+/// at the offset the PRG bank maps them to. Its command sequences program
+/// single bytes, erase 4KB sectors or the whole chip and read back the chip
+/// ID. Like the real chip programming can only clear bits, and every
+/// operation finishes instantly instead of reporting busy. Without the
+/// battery bit the board is a discrete UNROM with bus conflicts.
+#[derive(Debug, Clone, Copy)]
+pub struct M030 {
+    data: MapperData,
+    bank: u8,
+    flash: Flash,
+    software_id: bool,
+    erased: Option<(usize, usize)>, // PRG range waiting to be filled with $FF
+}
+impl M030 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            bank: 0x00,
+            flash: Flash::IDLE,
+            software_id: false,
+            erased: None,
+        }
+    }
+
+    #[inline]
+    fn flashable(&self) -> bool {
+        self.data.battery
+    }
+
+    fn prg_offset(&self, addr: u16) -> usize {
+        let bank: usize = match addr {
+            0x8000..=0xBFFF => (self.bank & BANK_PRG) as usize,
+            _ => (self.data.prg_bank as usize).saturating_sub(1),
+        };
+        bank * 0x4000 + (addr & 0x3FFF) as usize
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        let bank: usize = ((self.bank & BANK_CHR) >> 5) as usize;
+        bank * 0x2000 + (addr & 0x1FFF) as usize
+    }
+
+    /// Steps the flash command state machine, returns where to store a
+    /// programmed byte.
+    fn flash_write(&mut self, offset: usize, data: u8) -> Option<usize> {
+        let address: usize = offset & FLASH_ADDRESS_MASK;
+        if data == 0xF0 && self.flash != Flash::PROGRAM {
+            // reset, also leaves software ID mode
+            self.flash = Flash::IDLE;
+            self.software_id = false;
+            return None;
+        }
+        self.flash = match (self.flash, address, data) {
+            (Flash::IDLE, FLASH_UNLOCK_1, 0xAA) => Flash::UNLOCKED,
+            (Flash::UNLOCKED, FLASH_UNLOCK_2, 0x55) => Flash::COMMAND,
+            (Flash::COMMAND, FLASH_UNLOCK_1, 0xA0) => Flash::PROGRAM,
+            (Flash::COMMAND, FLASH_UNLOCK_1, 0x80) => Flash::ERASE,
+            (Flash::COMMAND, FLASH_UNLOCK_1, 0x90) => {
+                self.software_id = true;
+                Flash::IDLE
+            }
+            (Flash::PROGRAM, _, _) => {
+                self.flash = Flash::IDLE;
+                return Some(offset);
+            }
+            (Flash::ERASE, FLASH_UNLOCK_1, 0xAA) => Flash::ERASE_UNLOCKED,
+            (Flash::ERASE_UNLOCKED, FLASH_UNLOCK_2, 0x55) => {
+                Flash::ERASE_COMMAND
+            }
+            (Flash::ERASE_COMMAND, _, 0x30) => {
+                let start: usize = offset & !(FLASH_SECTOR_SIZE - 1);
+                self.erased = Some((start, start + FLASH_SECTOR_SIZE));
+                Flash::IDLE
+            }
+            (Flash::ERASE_COMMAND, FLASH_UNLOCK_1, 0x10) => {
+                self.erased = Some((0, self.data.prg_bank as usize * 0x4000));
+                Flash::IDLE
+            }
+            _ => Flash::IDLE,
+        };
+        None
+    }
+}
+impl Mapper for M030 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF if self.software_id => {
+                Mapped::Data(FLASH_ID[(addr & 0x01) as usize])
+            }
+            0x8000..=0xFFFF => Mapped::Prg(self.prg_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x8000..=0xBFFF if self.flashable() => {
+                let offset: usize = self.prg_offset(addr);
+                if let Some(offset) = self.flash_write(offset, data) {
+                    return Mapped::Prg(offset);
+                }
+            }
+            0x8000..=0xFFFF => self.bank = data,
+            _ => return Mapped::Unmapped,
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            0x2000..=0x3EFF if self.data.mirror == Mirroring::FOUR_SCREEN => {
+                Mapped::Chr(0x6000 + (addr & 0x0FFF) as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank != 0 => Mapped::Unmapped,
+            _ => self.ppu_read(addr),
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        match self.data.mirror {
+            Mirroring::ONESCREAN_LO | Mirroring::ONESCREAN_HI => {
+                Some(match self.bank & BANK_ONESCREEN {
+                    0 => Mirroring::ONESCREAN_LO,
+                    _ => Mirroring::ONESCREAN_HI,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn bus_conflicts(&self) -> bool {
+        !self.flashable()
+    }
+
+    fn prg_erased(&mut self) -> Option<Range<usize>> {
+        self.erased.take().map(|(start, end)| start..end)
+    }
+
+    fn prg_flash(&self) -> bool {
+        self.flashable()
+    }
+
+    fn reset(&mut self) {
+        self.bank = 0x00;
+        self.flash = Flash::IDLE;
+        self.software_id = false;
+    }
+}
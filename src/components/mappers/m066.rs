@@ -0,0 +1,74 @@
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// bank register ($8000-$FFFF)
+const BANK_CHR: u8 = 0x03;
+const BANK_PRG: u8 = 0x30;
+
+/// GxROM (and MHROM): one register at $8000-$FFFF selects both the 32KB
+/// PRG bank and the 8KB CHR bank. The boards have no write enable on the
+/// ROM, so every write is subject to bus conflicts.
+///
+/// ```no_run
+/// 7  bit  0
+/// xxPP xxCC  PP: 32KB PRG bank, CC: 8KB CHR bank
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct M066 {
+    data: MapperData,
+    bank: u8,
+}
+impl M066 {
+    pub fn new(data: MapperData) -> Self {
+        Self { data, bank: 0x00 }
+    }
+
+    #[inline]
+    fn chr_offset(&self, addr: u16) -> usize {
+        (self.bank & BANK_CHR) as usize * 0x2000 + (addr & 0x1FFF) as usize
+    }
+}
+impl Mapper for M066 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => Mapped::Prg(
+                ((self.bank & BANK_PRG) >> 4) as usize * 0x8000
+                    + (addr & 0x7FFF) as usize,
+            ),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x8000..=0xFFFF => {
+                self.bank = data;
+                Mapped::Data(data)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn bus_conflicts(&self) -> bool {
+        true
+    }
+
+    fn reset(&mut self) {
+        self.bank = 0x00;
+    }
+}
@@ -0,0 +1,371 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+
+// $8000 command
+const COMMAND_MASK: u8 = 0x0F;
+// command 8, PRG bank at $6000
+const RAM_ENABLE: u8 = 0x80;
+const RAM_SELECT: u8 = 0x40;
+// command D, IRQ control
+const IRQ_ENABLE: u8 = 0x01;
+const IRQ_COUNTER_ENABLE: u8 = 0x80;
+// 5B volume registers
+const VOLUME_ENVELOPE: u8 = 0x10;
+// 5B envelope shape
+const SHAPE_HOLD: u8 = 0x01;
+const SHAPE_ALTERNATE: u8 = 0x02;
+const SHAPE_ATTACK: u8 = 0x04;
+const SHAPE_CONTINUE: u8 = 0x08;
+
+/// Each channel's level is a 5-bit value, 1.5dB apart, 0 is silent.
+const LEVEL_STEP_DB: f32 = 1.5;
+/// Output of one channel at full level, a bit louder than an APU pulse
+/// channel like on the real board.
+const CHANNEL_LEVEL: f32 = 0.15;
+/// Tone and noise timers run off the cpu clock divided by 16.
+const AUDIO_PRESCALER: u8 = 16;
+
+/// Sunsoft FME-7, 5A and 5B (mapper 69). Two ports, a command and the
+/// parameter for it:
+///
+/// ```no_run
+/// $8000-$9FFF  command    xxxx CCCC
+/// $A000-$BFFF  parameter
+///   0-7  1KB CHR banks
+///   8    PRG bank at $6000  ERBB BBBB  E: RAM enable, R: RAM instead of ROM
+///   9-B  8KB PRG banks at $8000, $A000 and $C000, $E000 is the last bank
+///   C    mirroring  0: vertical, 1: horizontal, 2/3: one-screen
+///   D    IRQ control  Cxxx xxxI  C: count, I: raise IRQs (acknowledges)
+///   E-F  IRQ counter low and high
+/// $C000-$DFFF  5B audio register select
+/// $E000-$FFFF  5B audio register data
+/// ```
+///
+/// The IRQ counter decrements every cpu cycle while C is set and fires when
+/// it wraps from $0000 to $FFFF. Only the 5B has the audio chip, writes to
+/// its ports do nothing on the other two, so it is always emulated.
+#[derive(Debug, Clone, Copy)]
+pub struct M069 {
+    data: MapperData,
+    command: u8,
+    chr_banks: [u8; 8],
+    prg_banks: [u8; 4],
+    mirror: Mirroring,
+
+    irq_control: u8,
+    irq_counter: u16,
+    irq_active: bool,
+
+    audio: Sunsoft5b,
+}
+impl M069 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            command: 0x00,
+            chr_banks: [0x00; 8],
+            prg_banks: [0x00; 4],
+            mirror: Mirroring::VERTICAL,
+            irq_control: 0x00,
+            irq_counter: 0x0000,
+            irq_active: false,
+            audio: Sunsoft5b::new(),
+        }
+    }
+
+    fn write_parameter(&mut self, data: u8) {
+        match self.command {
+            n @ 0x00..=0x07 => self.chr_banks[n as usize] = data,
+            n @ 0x08..=0x0B => self.prg_banks[(n - 0x08) as usize] = data,
+            0x0C => {
+                self.mirror = match data & 0x03 {
+                    0 => Mirroring::VERTICAL,
+                    1 => Mirroring::HORIZONTAL,
+                    2 => Mirroring::ONESCREAN_LO,
+                    _ => Mirroring::ONESCREAN_HI,
+                }
+            }
+            0x0D => {
+                self.irq_control = data;
+                self.irq_active = false;
+            }
+            0x0E => {
+                self.irq_counter = (self.irq_counter & 0xFF00) | data as u16
+            }
+            _ => {
+                self.irq_counter =
+                    (self.irq_counter & 0x00FF) | (data as u16) << 8
+            }
+        }
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        self.chr_banks[(addr >> 10) as usize] as usize * 0x0400
+            + (addr & 0x03FF) as usize
+    }
+
+    /// $6000-$7FFF, ROM or RAM depending on command 8.
+    fn wram(&self, addr: u16, write: bool) -> Mapped {
+        let bank: u8 = self.prg_banks[0];
+        match bank & RAM_SELECT {
+            0 if write => Mapped::Unmapped,
+            0 => Mapped::Prg(
+                (bank & 0x3F) as usize * 0x2000 + (addr & 0x1FFF) as usize,
+            ),
+            _ if bank & RAM_ENABLE != 0 && self.data.prg_ram_size > 0 => {
+                Mapped::PrgRam(
+                    (bank & 0x3F) as usize * 0x2000 + (addr & 0x1FFF) as usize,
+                )
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+}
+impl Mapper for M069 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF => self.wram(addr, false),
+            0x8000..=0xDFFF => {
+                let slot: usize = ((addr - 0x6000) >> 13) as usize;
+                let bank: usize = (self.prg_banks[slot] & 0x3F) as usize;
+                Mapped::Prg(bank * 0x2000 + (addr & 0x1FFF) as usize)
+            }
+            0xE000..=0xFFFF => {
+                let last: usize =
+                    (self.data.prg_bank as usize * 2).saturating_sub(1);
+                Mapped::Prg(last * 0x2000 + (addr & 0x1FFF) as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF => return self.wram(addr, true),
+            0x8000..=0x9FFF => self.command = data & COMMAND_MASK,
+            0xA000..=0xBFFF => self.write_parameter(data),
+            0xC000..=0xDFFF => self.audio.select(data),
+            0xE000..=0xFFFF => self.audio.write(data),
+            _ => return Mapped::Unmapped,
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(self.mirror)
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq_active
+    }
+
+    fn cpu_clock(&mut self) {
+        if self.irq_control & IRQ_COUNTER_ENABLE != 0 {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            if self.irq_counter == 0xFFFF && self.irq_control & IRQ_ENABLE != 0
+            {
+                self.irq_active = true;
+            }
+        }
+        self.audio.clock();
+    }
+
+    fn audio_output(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn reset(&mut self) {
+        self.irq_control = 0x00;
+        self.irq_active = false;
+        self.audio = Sunsoft5b::new();
+    }
+}
+
+/// Sunsoft 5B audio, a YM2149 (AY-3-8910 family) in all but name: three
+/// square channels that can each mix in the shared noise generator, with
+/// either a fixed volume or the shared envelope.
+///
+/// ```no_run
+/// $00-$05  tone period, 12 bits per channel (low, high)
+/// $06      noise period, 5 bits
+/// $07      mixer      xxNN NTTT  N: noise off, T: tone off (per channel)
+/// $08-$0A  volume     xxxE VVVV  E: use the envelope, V: volume
+/// $0B-$0C  envelope period, 16 bits
+/// $0D      envelope shape  CATH  C: continue, A: attack, T: alternate,
+///                                H: hold, writing restarts the envelope
+/// ```
+#[derive(Debug, Clone, Copy)]
+struct Sunsoft5b {
+    address: u8,
+    registers: [u8; 16],
+    prescaler: u8,
+
+    tone_timers: [u16; 3],
+    tone_outputs: [bool; 3],
+
+    noise_timer: u8,
+    noise_toggle: bool, // the noise steps on every other timer expiry
+    noise_shift: u32,
+
+    envelope_timer: u16,
+    envelope_step: u8,
+    envelope_attack: bool,
+    envelope_holding: bool,
+}
+impl Sunsoft5b {
+    fn new() -> Self {
+        Self {
+            address: 0x00,
+            registers: [0x00; 16],
+            prescaler: 0,
+            tone_timers: [0; 3],
+            tone_outputs: [false; 3],
+            noise_timer: 0,
+            noise_toggle: false,
+            noise_shift: 0x0001,
+            envelope_timer: 0,
+            envelope_step: 31,
+            envelope_attack: false,
+            envelope_holding: true,
+        }
+    }
+
+    fn select(&mut self, data: u8) {
+        self.address = data;
+    }
+
+    fn write(&mut self, data: u8) {
+        // the upper address bits select other chips that were never fitted
+        if self.address > 0x0F {
+            return;
+        }
+        self.registers[self.address as usize] = data;
+        if self.address == 0x0D {
+            self.envelope_step = 0;
+            self.envelope_attack = data & SHAPE_ATTACK != 0;
+            self.envelope_holding = false;
+        }
+    }
+
+    fn tone_period(&self, channel: usize) -> u16 {
+        let low: u16 = self.registers[channel * 2] as u16;
+        let high: u16 = (self.registers[channel * 2 + 1] & 0x0F) as u16;
+        ((high << 8) | low).max(1)
+    }
+
+    fn clock(&mut self) {
+        self.prescaler += 1;
+        if self.prescaler < AUDIO_PRESCALER {
+            return;
+        }
+        self.prescaler = 0;
+
+        for ch in 0..3 {
+            self.tone_timers[ch] += 1;
+            if self.tone_timers[ch] >= self.tone_period(ch) {
+                self.tone_timers[ch] = 0;
+                self.tone_outputs[ch] = !self.tone_outputs[ch];
+            }
+        }
+
+        self.noise_timer += 1;
+        if self.noise_timer >= (self.registers[0x06] & 0x1F).max(1) {
+            self.noise_timer = 0;
+            self.noise_toggle = !self.noise_toggle;
+            if self.noise_toggle {
+                let feedback: u32 =
+                    (self.noise_shift ^ (self.noise_shift >> 3)) & 0x01;
+                self.noise_shift = (self.noise_shift >> 1) | (feedback << 16);
+            }
+        }
+
+        // 32 envelope steps per period, two per tone prescaler tick
+        let period: u16 =
+            u16::from_le_bytes([self.registers[0x0B], self.registers[0x0C]])
+                .max(1);
+        for _ in 0..2 {
+            self.envelope_timer += 1;
+            if self.envelope_timer >= period {
+                self.envelope_timer = 0;
+                self.step_envelope();
+            }
+        }
+    }
+
+    fn step_envelope(&mut self) {
+        if self.envelope_holding {
+            return;
+        }
+        self.envelope_step += 1;
+        if self.envelope_step < 32 {
+            return;
+        }
+        let shape: u8 = self.registers[0x0D];
+        self.envelope_step = 0;
+        if shape & SHAPE_CONTINUE == 0 {
+            // one ramp, then silence
+            self.envelope_attack = false;
+            self.envelope_step = 31;
+            self.envelope_holding = true;
+        } else if shape & SHAPE_HOLD != 0 {
+            if shape & SHAPE_ALTERNATE != 0 {
+                self.envelope_attack = !self.envelope_attack;
+            }
+            self.envelope_step = 31;
+            self.envelope_holding = true;
+        } else if shape & SHAPE_ALTERNATE != 0 {
+            self.envelope_attack = !self.envelope_attack;
+        }
+    }
+
+    fn envelope_level(&self) -> u8 {
+        match self.envelope_attack {
+            true => self.envelope_step,
+            false => 31 - self.envelope_step,
+        }
+    }
+
+    fn output(&self) -> f32 {
+        let mixer: u8 = self.registers[0x07];
+        let noise: bool = self.noise_shift & 0x01 != 0;
+        (0..3)
+            .map(|ch| {
+                let tone_off: bool = mixer & (0x01 << ch) != 0;
+                let noise_off: bool = mixer & (0x08 << ch) != 0;
+                let high: bool =
+                    (self.tone_outputs[ch] || tone_off) && (noise || noise_off);
+                let volume: u8 = self.registers[0x08 + ch];
+                // fixed volumes land on the odd envelope levels
+                let level: u8 = match volume & VOLUME_ENVELOPE {
+                    0 if volume & 0x0F == 0 => 0,
+                    0 => (volume & 0x0F) * 2 + 1,
+                    _ => self.envelope_level(),
+                };
+                match high && level > 0 {
+                    true => {
+                        let db: f32 = (31 - level) as f32 * LEVEL_STEP_DB;
+                        10f32.powf(-db / 20.0) * CHANNEL_LEVEL
+                    }
+                    false => 0.0,
+                }
+            })
+            .sum()
+    }
+}
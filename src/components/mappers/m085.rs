@@ -0,0 +1,205 @@
+use crate::components::cartridge::Mirroring;
+
+use super::mapper::{Mapped, Mapper, MapperData};
+use super::opll::{Opll, CYCLES_PER_SAMPLE};
+use super::vrc::VrcIrq;
+
+// $E000 mirroring and control
+const CONTROL_MIRRORING: u8 = 0x03;
+const CONTROL_SOUND_RESET: u8 = 0x40;
+const CONTROL_PRG_RAM: u8 = 0x80;
+
+/// One FM channel at full volume peaks about as high as an APU pulse
+/// channel does.
+const CHANNEL_LEVEL: f32 = 0.13;
+
+/// Konami VRC7 (mapper 85). Registers sit on two address lines, A4 on
+/// VRC7a (submapper 1) and A3 on VRC7b (submapper 2), both are decoded when
+/// the submapper is unknown:
+///
+/// ```no_run
+/// $8000  8KB PRG bank at $8000      $8010  8KB PRG bank at $A000
+/// $9000  8KB PRG bank at $C000, the last bank is fixed at $E000
+/// $9010  audio register select      $9030  audio register data
+/// $A000-$D010  CHR registers R0-R7, 1KB each
+/// $E000  control  WSxx xxMM  W: PRG RAM enable, S: sound reset, M: mirroring
+/// $E010  IRQ latch
+/// $F000  IRQ control                $F010  IRQ acknowledge
+/// ```
+///
+/// The audio ports only exist on VRC7a and feed the OPLL FM synthesizer in
+/// [`super::opll`]. Holding the sound reset bit keeps it silent and
+/// cleared.
+#[derive(Debug, Clone, Copy)]
+pub struct M085 {
+    data: MapperData,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    control: u8,
+    irq: VrcIrq,
+
+    opll: Opll,
+    sample_timer: u8,
+}
+impl M085 {
+    pub fn new(data: MapperData) -> Self {
+        Self {
+            data,
+            prg_banks: [0x00; 3],
+            chr_banks: [0x00; 8],
+            control: 0x00,
+            irq: VrcIrq::new(),
+            opll: Opll::new(),
+            sample_timer: 0,
+        }
+    }
+
+    /// Folds the board's register select line into bit 4.
+    #[inline]
+    fn register(&self, addr: u16) -> u16 {
+        let a3: u16 = (addr >> 3) & 0x01;
+        let a4: u16 = (addr >> 4) & 0x01;
+        let line: u16 = match self.data.submapper {
+            1 => a4,
+            2 => a3,
+            _ => a3 | a4,
+        };
+        (addr & 0xF000) | (line << 4)
+    }
+
+    #[inline]
+    fn prg_ram_enabled(&self) -> bool {
+        self.data.prg_ram_size > 0 && self.control & CONTROL_PRG_RAM != 0
+    }
+
+    #[inline]
+    fn sound_reset(&self) -> bool {
+        self.control & CONTROL_SOUND_RESET != 0
+    }
+
+    fn chr_offset(&self, addr: u16) -> usize {
+        self.chr_banks[(addr >> 10) as usize] as usize * 0x0400
+            + (addr & 0x03FF) as usize
+    }
+}
+impl Mapper for M085 {
+    fn cpu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                Mapped::PrgRam((addr & 0x1FFF) as usize)
+            }
+            0x8000..=0xDFFF => {
+                let bank: u8 = self.prg_banks[((addr - 0x8000) >> 13) as usize];
+                Mapped::Prg(bank as usize * 0x2000 + (addr & 0x1FFF) as usize)
+            }
+            0xE000..=0xFFFF => {
+                let last: usize =
+                    (self.data.prg_bank as usize * 2).saturating_sub(1);
+                Mapped::Prg(last * 0x2000 + (addr & 0x1FFF) as usize)
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped {
+        if addr < 0x8000 {
+            return match addr {
+                0x6000..=0x7FFF if self.prg_ram_enabled() => {
+                    Mapped::PrgRam((addr & 0x1FFF) as usize)
+                }
+                _ => Mapped::Unmapped,
+            };
+        }
+
+        // the audio ports need A5 too, and never move with the submapper
+        match addr & 0xF030 {
+            0x9010 if !self.sound_reset() => {
+                self.opll.write_address(data);
+                return Mapped::Data(data);
+            }
+            0x9030 if !self.sound_reset() => {
+                self.opll.write_data(data);
+                return Mapped::Data(data);
+            }
+            0x9010 | 0x9030 => return Mapped::Data(data),
+            _ => (),
+        }
+
+        match self.register(addr) {
+            0x8000 => self.prg_banks[0] = data & 0x3F,
+            0x8010 => self.prg_banks[1] = data & 0x3F,
+            0x9000 => self.prg_banks[2] = data & 0x3F,
+            reg @ 0xA000..=0xD010 => {
+                let bank: usize =
+                    (((reg >> 12) - 0x0A) * 2 + ((reg >> 4) & 0x01)) as usize;
+                self.chr_banks[bank] = data;
+            }
+            0xE000 => {
+                self.control = data;
+                if self.sound_reset() {
+                    self.opll.reset();
+                }
+            }
+            0xE010 => self.irq.set_latch(data),
+            0xF000 => self.irq.write_control(data),
+            0xF010 => self.irq.acknowledge(),
+            _ => (),
+        }
+        Mapped::Data(data)
+    }
+
+    fn ppu_read(&mut self, addr: u16) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF => Mapped::Chr(self.chr_offset(addr)),
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, _data: u8) -> Mapped {
+        match addr {
+            0x0000..=0x1FFF if self.data.chr_bank == 0 => {
+                Mapped::Chr(self.chr_offset(addr))
+            }
+            _ => Mapped::Unmapped,
+        }
+    }
+
+    fn mirroring(&self) -> Option<Mirroring> {
+        Some(match self.control & CONTROL_MIRRORING {
+            0 => Mirroring::VERTICAL,
+            1 => Mirroring::HORIZONTAL,
+            2 => Mirroring::ONESCREAN_LO,
+            _ => Mirroring::ONESCREAN_HI,
+        })
+    }
+
+    fn irq_state(&self) -> bool {
+        self.irq.pending()
+    }
+
+    fn cpu_clock(&mut self) {
+        self.irq.clock();
+        if self.sound_reset() {
+            return;
+        }
+        self.sample_timer += 1;
+        if self.sample_timer == CYCLES_PER_SAMPLE {
+            self.sample_timer = 0;
+            self.opll.clock();
+        }
+    }
+
+    fn audio_output(&self) -> f32 {
+        match self.sound_reset() {
+            true => 0.0,
+            false => self.opll.output() * CHANNEL_LEVEL,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.control = 0x00;
+        self.irq = VrcIrq::new();
+        self.opll.reset();
+        self.sample_timer = 0;
+    }
+}
@@ -1,27 +1,155 @@
-pub trait MapperFn {
-    fn new(prg_bank: u8, chr_bank: u8) -> Self;
-    fn allow_cpu_read(&self, addr: u16, mapped_addr: &mut u32) -> bool;
-    fn allow_cpu_write(&self, addr: u16, mapped_addr: &mut u32) -> bool;
-    fn allow_ppu_read(&self, addr: u16, mapped_addr: &mut u32) -> bool;
-    fn allow_ppu_write(&self, addr: u16, mapped_addr: &mut u32) -> bool;
+use std::{fmt::Debug, ops::Range};
+
+use crate::components::cartridge::Mirroring;
+
+/// Where a mapper routes a single bus access. Offsets are relative to the
+/// start of the respective memory and wrap at its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mapped {
+    /// The cartridge does not decode this address, the bus floats (cpu) or
+    /// the console's own memory answers (ppu).
+    Unmapped,
+    /// Offset into PRG ROM.
+    Prg(usize),
+    /// Offset into PRG RAM, the work ram usually found at $6000-$7FFF.
+    PrgRam(usize),
+    /// Offset into CHR ROM/RAM.
+    Chr(usize),
+    /// Offset into the console's 2KB of nametable ram (CIRAM), for boards
+    /// that drive the CIRAM A10 line themselves (ppu only).
+    Ciram(usize),
+    /// Value produced by the mapper itself on reads (registers, internal
+    /// ram), or a write the mapper swallowed into its own registers.
+    Data(u8),
 }
 
-// trivially copiable struct on most systems: mov QWORD
+/// A cartridge board. Mappers own their bank registers and are driven by
+/// the cartridge through `&mut self`, so one [`Box<dyn Mapper>`] is all the
+/// cartridge needs to hold no matter which board the game shipped on.
+///
+/// Only the four address translations are required, everything else has a
+/// default that suits boards without the matching hardware.
+pub trait Mapper: Debug {
+    /// CPU read in cartridge space ($4020-$FFFF).
+    fn cpu_read(&mut self, addr: u16) -> Mapped;
+    /// CPU write in cartridge space. Writes landing on mapper registers
+    /// update the banking state and report [`Mapped::Data`].
+    fn cpu_write(&mut self, addr: u16, data: u8) -> Mapped;
+    /// PPU read anywhere in $0000-$3FFF.
+    fn ppu_read(&mut self, addr: u16) -> Mapped;
+    /// PPU write anywhere in $0000-$3FFF.
+    fn ppu_write(&mut self, addr: u16, data: u8) -> Mapped;
+
+    /// Mirroring selected by the mapper, `None` keeps the arrangement
+    /// soldered on the board (the header's mirroring bit).
+    fn mirroring(&self) -> Option<Mirroring> {
+        None
+    }
+
+    /// Boards without a write enable let the ROM fight the cpu for the data
+    /// bus, so register writes only see bits that are set in both the value
+    /// written and the ROM byte at that address.
+    fn bus_conflicts(&self) -> bool {
+        false
+    }
+
+    /// Level of the cartridge's IRQ line, `true` while asserted.
+    fn irq_state(&self) -> bool {
+        false
+    }
+
+    /// Called once per CPU cycle.
+    fn cpu_clock(&mut self) {}
+
+    /// Called by the PPU once per rendered scanline.
+    fn scanline(&mut self) {}
+
+    /// Called whenever the PPU puts a new address on its bus without it
+    /// being a plain read or write (e.g. $2006 updates).
+    fn ppu_address(&mut self, _addr: u16) {}
+
+    /// Called for every cpu write to the PPU registers ($2000-$2007, with
+    /// the mirrors folded). Boards that need to know the sprite size or
+    /// whether rendering is on snoop them from the data bus.
+    fn ppu_register_write(&mut self, _addr: u16, _data: u8) {}
+
+    /// Expansion audio, the board's analog output on the same scale as the
+    /// APU's own mix. Usually within 0.0..=1.0, FM boards swing around zero.
+    fn audio_output(&self) -> f32 {
+        0.0
+    }
+
+    /// Battery backed memory inside the mapper chip itself, saved next to
+    /// the PRG NVRAM. `None` for boards without any.
+    fn battery_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    /// Restores what [`Mapper::battery_ram`] returned in an earlier session.
+    fn load_battery_ram(&mut self, _data: &[u8]) {}
+
+    /// Boards that rewrite their own PRG flash. Writes the mapper routes to
+    /// [`Mapped::Prg`] program the chip and the cartridge keeps the changes.
+    fn prg_flash(&self) -> bool {
+        false
+    }
+
+    /// PRG a flash command just erased, polled after every cpu write. The
+    /// cartridge fills the range with $FF.
+    fn prg_erased(&mut self) -> Option<Range<usize>> {
+        None
+    }
+
+    /// Number of disk sides the drive can be fed, 0 for plain cartridges.
+    fn disk_sides(&self) -> usize {
+        0
+    }
+
+    /// Side currently in the drive, `None` while it is empty.
+    fn disk_side(&self) -> Option<usize> {
+        None
+    }
+
+    /// Swaps the disk in the drive, `None` ejects it.
+    fn insert_disk(&mut self, _side: Option<usize>) {}
+
+    /// The disk sides in .fds layout, with whatever the game wrote to them.
+    fn disk_image(&self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Console reset button.
+    fn reset(&mut self) {}
+}
+
+/// What a mapper needs to know about the cartridge it is built for.
 #[derive(Debug, Clone, Copy)]
 pub struct MapperData {
-    pub prg_bank: u8,
-    pub chr_bank: u8,
+    pub prg_bank: u16, // number of 16KB PRG ROM banks
+    pub chr_bank: u16, // number of 8KB CHR ROM banks, 0 means CHR RAM
+    pub submapper: u8,
+    pub mirror: Mirroring, // hardwired mirroring from the header
+    pub prg_ram_size: usize, // PRG RAM + PRG NVRAM in bytes
+    pub battery: bool,     // battery bit from the header
 }
 impl MapperData {
-    pub fn new(prg_bank: u8, chr_bank: u8) -> Self {
-        Self { prg_bank, chr_bank }
+    pub fn new(prg_bank: u16, chr_bank: u16) -> Self {
+        Self {
+            prg_bank,
+            chr_bank,
+            ..Self::default()
+        }
     }
 }
 impl Default for MapperData {
     fn default() -> Self {
         Self {
-            prg_bank: 0u8,
-            chr_bank: 0u8,
+            prg_bank: 0u16,
+            chr_bank: 0u16,
+            submapper: 0u8,
+            mirror: Mirroring::HORIZONTAL,
+            prg_ram_size: 0,
+            battery: false,
         }
     }
 }
@@ -1,2 +1,71 @@
+mod eeprom;
 pub mod m000;
+pub mod m001;
+pub mod m002;
+pub mod m003;
+pub mod m004;
+pub mod m005;
+pub mod m007;
+pub mod m009;
+pub mod m010;
+pub mod m011;
+pub mod m016;
+pub mod m019;
+pub mod m020;
+pub mod m021;
+pub mod m024;
+pub mod m030;
+pub mod m066;
+pub mod m069;
+pub mod m085;
 pub mod mapper;
+pub mod opll;
+mod vrc;
+
+use self::{
+    m000::M000,
+    m001::M001,
+    m002::M002,
+    m003::M003,
+    m004::M004,
+    m005::M005,
+    m007::M007,
+    m009::M009,
+    m010::M010,
+    m011::M011,
+    m016::M016,
+    m019::M019,
+    m021::M021,
+    m024::M024,
+    m030::M030,
+    m066::M066,
+    m069::M069,
+    m085::M085,
+    mapper::{Mapper, MapperData},
+};
+
+/// Builds the board matching an iNES/NES 2.0 mapper number, `None` when
+/// that board is not emulated.
+pub fn new_mapper(mapper_id: u16, data: MapperData) -> Option<Box<dyn Mapper>> {
+    match mapper_id {
+        0 => Some(Box::new(M000::new(data))),
+        1 => Some(Box::new(M001::new(data))),
+        2 => Some(Box::new(M002::new(data))),
+        3 => Some(Box::new(M003::new(data))),
+        4 => Some(Box::new(M004::new(data))),
+        5 => Some(Box::new(M005::new(data))),
+        7 => Some(Box::new(M007::new(data))),
+        9 => Some(Box::new(M009::new(data))),
+        10 => Some(Box::new(M010::new(data))),
+        11 => Some(Box::new(M011::new(data))),
+        16 | 153 | 159 => Some(Box::new(M016::new(data, mapper_id))),
+        19 => Some(Box::new(M019::new(data))),
+        21 | 22 | 23 | 25 => Some(Box::new(M021::new(data, mapper_id))),
+        24 | 26 => Some(Box::new(M024::new(data, mapper_id))),
+        30 => Some(Box::new(M030::new(data))),
+        66 => Some(Box::new(M066::new(data))),
+        69 => Some(Box::new(M069::new(data))),
+        85 => Some(Box::new(M085::new(data))),
+        _ => None,
+    }
+}
@@ -0,0 +1,541 @@
+//! OPLL, the FM synthesizer inside the VRC7. It is a cut down YM2413: six
+//! two-operator channels instead of nine, no rhythm mode, and its own set
+//! of fifteen built-in instruments.
+//!
+//! The chip is fed from a register port and produces one sample every 36
+//! cpu cycles (about 49.7kHz). Everything runs on integers like the real
+//! chip: levels are attenuations on a log scale, the sine comes out of a
+//! log-sine table and goes back to a linear level through an exponent
+//! table, both with the same contents as the ROMs on the die.
+
+use once_cell::sync::Lazy;
+
+/// Samples produced per second on an NTSC console (cpu clock / 36).
+pub const SAMPLE_RATE: f32 = 1_789_773.0 / 36.0;
+/// Cpu cycles per sample.
+pub const CYCLES_PER_SAMPLE: u8 = 36;
+
+pub const CHANNELS: usize = 6;
+
+/// The VRC7's built-in instruments 1-15, instrument 0 is the custom one
+/// written to registers $00-$07.
+pub const VRC7_PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+// $20-$25
+const KEY_ON: u8 = 0x10;
+const SUSTAIN_ON: u8 = 0x20;
+
+/// Quarter of a sine wave as -log2(sin) in 1/256 steps.
+pub static LOGSIN_TABLE: Lazy<[u16; 256]> = Lazy::new(|| {
+    let mut table = [0u16; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        let angle: f64 = (n as f64 + 0.5) * std::f64::consts::PI / 512.0;
+        *entry = (-angle.sin().log2() * 256.0).round() as u16;
+    }
+    table
+});
+
+/// Fraction part of 2^(n/256) in 1/1024 steps, the implied leading one is
+/// added back when the table is read.
+pub static EXP_TABLE: Lazy<[u16; 256]> = Lazy::new(|| {
+    let mut table = [0u16; 256];
+    for (n, entry) in table.iter_mut().enumerate() {
+        *entry = ((2f64.powf(n as f64 / 256.0) - 1.0) * 1024.0).round() as u16;
+    }
+    table
+});
+
+/// The envelope is 7 bits of 0.375dB steps, 48dB all told. Anything at
+/// the bottom is treated as silence and the envelope stops.
+const MAX_ENVELOPE: u16 = 0x7F;
+/// One envelope step in the 1/256 octave units of the log-sine table.
+const ENVELOPE_SHIFT: u32 = 4;
+/// Release rate used for keyed off channels with the sustain bit set, and
+/// for percussive instruments without it.
+const SUSTAIN_RELEASE_RATE: u8 = 5;
+const PERCUSSIVE_RELEASE_RATE: u8 = 7;
+/// Effective rates from here on attack instantly.
+const INSTANT_ATTACK_RATE: u8 = 60;
+/// Envelope increments for the four fine steps of a rate, picked by the
+/// sample counter. Every coarse step doubles the speed.
+const ENVELOPE_STEPS: [[u8; 8]; 4] = [
+    [0, 1, 0, 1, 0, 1, 0, 1],
+    [0, 1, 0, 1, 1, 1, 0, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 1],
+];
+
+/// One cycle of the phase accumulator, the top bits address the wave.
+const PHASE_BITS: u32 = 19;
+const WAVE_BITS: u32 = 10;
+/// Full scale of an operator's output, (0x3FA | 0x400) << 1 is the peak.
+const OPERATOR_MAX: f32 = 4096.0;
+
+/// Tremolo is a triangle of 210 steps, 64 samples each (3.7Hz), that
+/// attenuates by up to 13 envelope steps (4.875dB).
+const TREMOLO_STEPS: u8 = 210;
+const TREMOLO_STEP_SAMPLES: u32 = 64;
+/// Vibrato moves the doubled F-number by this many halves of its top
+/// three bits, one step every 1024 samples (6.1Hz).
+const VIBRATO_TABLE: [i8; 8] = [0, 1, 2, 1, 0, -1, -2, -1];
+const VIBRATO_STEP_SHIFT: u32 = 10;
+
+/// Frequency multipliers, doubled so the 1/2 setting stays an integer.
+const MULTIPLIER_X2: [u32; 16] =
+    [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+/// Key scale attenuation at block 7 by the top four F-number bits, in
+/// 0.75dB steps.
+const KSL_TABLE: [u8; 16] = [
+    0, 24, 32, 37, 40, 43, 45, 47, 48, 50, 51, 52, 53, 54, 55, 56,
+];
+
+/// Six channel FM synthesizer with the VRC7 patch set.
+#[derive(Debug, Clone, Copy)]
+pub struct Opll {
+    address: u8,
+    custom: [u8; 8],
+    channels: [Channel; CHANNELS],
+    counter: u32, // samples, drives the envelopes and the vibrato
+    tremolo_step: u8,
+    output: f32,
+}
+impl Opll {
+    pub fn new() -> Self {
+        Self {
+            address: 0x00,
+            custom: [0x00; 8],
+            channels: [Channel::default(); CHANNELS],
+            counter: 0,
+            tremolo_step: 0,
+            output: 0.0,
+        }
+    }
+
+    /// Register select port.
+    pub fn write_address(&mut self, address: u8) {
+        self.address = address;
+    }
+
+    /// Data port, writes the register picked with [`Opll::write_address`].
+    pub fn write_data(&mut self, data: u8) {
+        self.write(self.address, data);
+    }
+
+    /// Writes a register directly.
+    ///
+    /// ```no_run
+    /// $00-$07  custom instrument
+    /// $10-$15  F-number low bits
+    /// $20-$25  xxSK BBBF  S: sustain, K: key on, B: block, F: F-number bit 8
+    /// $30-$35  IIII VVVV  I: instrument, V: volume (attenuation)
+    /// ```
+    pub fn write(&mut self, register: u8, data: u8) {
+        let channel: usize = (register & 0x0F) as usize;
+        match register {
+            0x00..=0x07 => self.custom[register as usize] = data,
+            0x10..=0x15 => {
+                let ch = &mut self.channels[channel];
+                ch.fnum = (ch.fnum & 0x100) | data as u16;
+            }
+            0x20..=0x25 => {
+                let ch = &mut self.channels[channel];
+                ch.fnum = (ch.fnum & 0x0FF) | ((data & 0x01) as u16) << 8;
+                ch.block = (data >> 1) & 0x07;
+                ch.sustain = data & SUSTAIN_ON != 0;
+                let key: bool = data & KEY_ON != 0;
+                if key && !ch.key_on {
+                    ch.modulator.key_on();
+                    ch.carrier.key_on();
+                } else if !key && ch.key_on {
+                    ch.modulator.key_off();
+                    ch.carrier.key_off();
+                }
+                ch.key_on = key;
+            }
+            0x30..=0x35 => {
+                let ch = &mut self.channels[channel];
+                ch.instrument = data >> 4;
+                ch.volume = data & 0x0F;
+            }
+            _ => (),
+        }
+    }
+
+    fn patch(&self, instrument: u8) -> Patch {
+        match instrument {
+            0 => Patch::decode(&self.custom),
+            n => Patch::decode(&VRC7_PATCHES[(n - 1) as usize]),
+        }
+    }
+
+    /// Produces the next sample, the sum of all six channels with each
+    /// channel in -1.0..=1.0.
+    pub fn clock(&mut self) -> f32 {
+        self.counter = self.counter.wrapping_add(1);
+        if self.counter.is_multiple_of(TREMOLO_STEP_SAMPLES) {
+            self.tremolo_step = (self.tremolo_step + 1) % TREMOLO_STEPS;
+        }
+        let tremolo: u8 = match self.tremolo_step < TREMOLO_STEPS / 2 {
+            true => self.tremolo_step,
+            false => TREMOLO_STEPS - 1 - self.tremolo_step,
+        };
+        let lfo = Lfo {
+            counter: self.counter,
+            tremolo: (tremolo >> 3) as u16,
+            vibrato: VIBRATO_TABLE
+                [((self.counter >> VIBRATO_STEP_SHIFT) & 0x07) as usize],
+        };
+
+        let mut output: f32 = 0.0;
+        for index in 0..CHANNELS {
+            let patch: Patch = self.patch(self.channels[index].instrument);
+            output +=
+                self.channels[index].clock(&patch, &lfo) as f32 / OPERATOR_MAX;
+        }
+        self.output = output;
+        output
+    }
+
+    /// The last sample produced.
+    pub const fn output(&self) -> f32 {
+        self.output
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lfo {
+    counter: u32,
+    tremolo: u16, // envelope steps
+    vibrato: i8,  // half steps of the top F-number bits
+}
+
+/// One operator's settings out of an instrument.
+#[derive(Debug, Clone, Copy)]
+struct OperatorPatch {
+    tremolo: bool,
+    vibrato: bool,
+    sustained: bool, // envelope holds at the sustain level while keyed
+    key_scale_rate: bool,
+    multiplier: u8,
+    key_scale_level: u8,
+    rectified: bool, // negative half of the sine is cut off
+    attack: u8,
+    decay: u8,
+    sustain_level: u8,
+    release: u8,
+}
+
+/// A decoded instrument.
+///
+/// ```no_run
+/// 0/1  AVSK MMMM  modulator/carrier: tremolo, vibrato, sustained,
+///                 key scale rate, multiplier
+/// 2    KKTT TTTT  modulator key scale level and total level
+/// 3    KKxC DFFF  carrier key scale level, carrier/modulator rectified
+///                 wave, modulator feedback
+/// 4/5  AAAA DDDD  modulator/carrier attack and decay rate
+/// 6/7  SSSS RRRR  modulator/carrier sustain level and release rate
+/// ```
+#[derive(Debug, Clone, Copy)]
+struct Patch {
+    operators: [OperatorPatch; 2],
+    total_level: u8,
+    feedback: u8,
+}
+impl Patch {
+    fn decode(bytes: &[u8; 8]) -> Self {
+        let operator =
+            |n: usize, key_scale_level: u8, rectified: bool| OperatorPatch {
+                tremolo: bytes[n] & 0x80 != 0,
+                vibrato: bytes[n] & 0x40 != 0,
+                sustained: bytes[n] & 0x20 != 0,
+                key_scale_rate: bytes[n] & 0x10 != 0,
+                multiplier: bytes[n] & 0x0F,
+                key_scale_level,
+                rectified,
+                attack: bytes[4 + n] >> 4,
+                decay: bytes[4 + n] & 0x0F,
+                sustain_level: bytes[6 + n] >> 4,
+                release: bytes[6 + n] & 0x0F,
+            };
+        Self {
+            operators: [
+                operator(0, bytes[2] >> 6, bytes[3] & 0x08 != 0),
+                operator(1, bytes[3] >> 6, bytes[3] & 0x10 != 0),
+            ],
+            total_level: bytes[2] & 0x3F,
+            feedback: bytes[3] & 0x07,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Channel {
+    fnum: u16,
+    block: u8,
+    key_on: bool,
+    sustain: bool,
+    instrument: u8,
+    volume: u8,
+    modulator: Operator,
+    carrier: Operator,
+}
+impl Channel {
+    fn clock(&mut self, patch: &Patch, lfo: &Lfo) -> i16 {
+        let [mod_patch, car_patch] = &patch.operators;
+        // the operators only look at the key (F-number, block, sustain)
+        let ch: Channel = *self;
+
+        let feedback: i32 = match patch.feedback {
+            0 => 0,
+            fb => {
+                (self.modulator.output as i32 + self.modulator.previous as i32)
+                    >> (9 - fb)
+            }
+        };
+        let base: u16 = (patch.total_level as u16) << 1;
+        let modulator: i16 =
+            self.modulator.clock(&ch, mod_patch, base, feedback, lfo);
+
+        let base: u16 = (self.volume as u16) << 3;
+        let modulation: i32 = (modulator >> 1) as i32;
+        self.carrier.clock(&ch, car_patch, base, modulation, lfo)
+    }
+
+    /// Key scale rate offset, the rate grows with the note.
+    fn key_scale(&self, enabled: bool) -> u8 {
+        let key: u8 = (self.block << 1) | (self.fnum >> 8) as u8;
+        match enabled {
+            true => key,
+            false => key >> 2,
+        }
+    }
+
+    /// Key scale level attenuation in envelope steps.
+    fn key_scale_level(&self, level: u8) -> u16 {
+        let full: u16 = (KSL_TABLE[(self.fnum >> 5) as usize] as u16)
+            .saturating_sub(8 * (7 - self.block) as u16);
+        match level {
+            0 => 0,
+            n => (full << 1) >> (3 - n),
+        }
+    }
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum EnvelopeState {
+    ATTACK,
+    DECAY,
+    SUSTAIN,
+    RELEASE,
+    #[default]
+    OFF,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Operator {
+    phase: u32,    // one cycle is 1 << PHASE_BITS
+    envelope: u16, // attenuation in 0.375dB steps, 0 is full volume
+    state: EnvelopeState,
+    output: i16,
+    previous: i16, // output before that, for the feedback average
+}
+impl Default for Operator {
+    fn default() -> Self {
+        Self {
+            phase: 0,
+            envelope: MAX_ENVELOPE,
+            state: EnvelopeState::OFF,
+            output: 0,
+            previous: 0,
+        }
+    }
+}
+impl Operator {
+    fn key_on(&mut self) {
+        self.phase = 0;
+        self.state = EnvelopeState::ATTACK;
+    }
+
+    fn key_off(&mut self) {
+        if self.state != EnvelopeState::OFF {
+            self.state = EnvelopeState::RELEASE;
+        }
+    }
+
+    /// 4-bit rate combined with the channel's key scale, 0 stays 0.
+    fn effective_rate(rate: u8, key_scale: u8) -> u8 {
+        match rate {
+            0 => 0,
+            _ => (rate * 4 + key_scale).min(63),
+        }
+    }
+
+    /// Envelope steps to move this sample. Slow rates only move on every
+    /// 2^n-th sample, fast ones move several steps at once.
+    fn envelope_step(rate: u8, counter: u32) -> u16 {
+        let coarse: u8 = rate >> 2;
+        let steps: &[u8; 8] = &ENVELOPE_STEPS[(rate & 0x03) as usize];
+        match coarse {
+            0 => 0,
+            1..=12 => {
+                let shift: u32 = 13 - coarse as u32;
+                match counter & ((1 << shift) - 1) {
+                    0 => steps[((counter >> shift) & 0x07) as usize] as u16,
+                    _ => 0,
+                }
+            }
+            _ => (steps[(counter & 0x07) as usize] as u16) << (coarse - 13),
+        }
+    }
+
+    fn clock_envelope(
+        &mut self,
+        ch: &Channel,
+        patch: &OperatorPatch,
+        lfo: &Lfo,
+    ) {
+        let key_scale: u8 = ch.key_scale(patch.key_scale_rate);
+        let step = |rate: u8| {
+            Self::envelope_step(
+                Self::effective_rate(rate, key_scale),
+                lfo.counter,
+            )
+        };
+        match self.state {
+            EnvelopeState::ATTACK => {
+                let rate: u8 = Self::effective_rate(patch.attack, key_scale);
+                if rate >= INSTANT_ATTACK_RATE {
+                    self.envelope = 0;
+                } else {
+                    // exponential, big steps at first and single ones at
+                    // the top
+                    let step: u16 = Self::envelope_step(rate, lfo.counter);
+                    let delta: u16 = ((self.envelope + 1) * step).div_ceil(8);
+                    self.envelope = self.envelope.saturating_sub(delta);
+                }
+                if self.envelope == 0 {
+                    self.state = EnvelopeState::DECAY;
+                }
+            }
+            EnvelopeState::DECAY => {
+                self.envelope += step(patch.decay);
+                let sustain: u16 = (patch.sustain_level as u16) << 3;
+                if self.envelope >= sustain {
+                    self.envelope = sustain;
+                    self.state = EnvelopeState::SUSTAIN;
+                }
+            }
+            EnvelopeState::SUSTAIN => {
+                // percussive instruments keep fading while held
+                if !patch.sustained {
+                    self.envelope += step(patch.release);
+                }
+            }
+            EnvelopeState::RELEASE => {
+                let rate: u8 = match (ch.sustain, patch.sustained) {
+                    (true, _) => SUSTAIN_RELEASE_RATE,
+                    (false, true) => patch.release,
+                    (false, false) => PERCUSSIVE_RELEASE_RATE,
+                };
+                self.envelope += step(rate);
+            }
+            EnvelopeState::OFF => (),
+        }
+        if self.envelope >= MAX_ENVELOPE {
+            self.envelope = MAX_ENVELOPE;
+            if self.state != EnvelopeState::ATTACK {
+                self.state = EnvelopeState::OFF;
+            }
+        }
+    }
+
+    /// Advances the operator one sample. `base` is the static attenuation
+    /// (total level or channel volume) in envelope steps and `modulation`
+    /// the phase offset in wave steps coming from feedback or the
+    /// modulator.
+    fn clock(
+        &mut self,
+        ch: &Channel,
+        patch: &OperatorPatch,
+        base: u16,
+        modulation: i32,
+        lfo: &Lfo,
+    ) -> i16 {
+        let mut fnum: i32 = (ch.fnum as i32) << 1;
+        if patch.vibrato {
+            fnum += (ch.fnum >> 6) as i32 * lfo.vibrato as i32 / 2;
+        }
+        let step: u32 = (((fnum as u32) << ch.block)
+            * MULTIPLIER_X2[patch.multiplier as usize])
+            >> 2;
+        self.phase = (self.phase + step) & ((1 << PHASE_BITS) - 1);
+
+        self.clock_envelope(ch, patch, lfo);
+        let mut attenuation: u16 =
+            base + self.envelope + ch.key_scale_level(patch.key_scale_level);
+        if patch.tremolo {
+            attenuation += lfo.tremolo;
+        }
+
+        let output: i16 = match self.state {
+            EnvelopeState::OFF => 0,
+            _ => {
+                let phase: i32 = (self.phase >> (PHASE_BITS - WAVE_BITS))
+                    as i32
+                    + modulation;
+                wave(
+                    phase as u32,
+                    patch.rectified,
+                    attenuation.min(MAX_ENVELOPE),
+                )
+            }
+        };
+        self.previous = self.output;
+        self.output = output;
+        output
+    }
+}
+
+/// One sample of the sine at `phase` (the low 10 bits are one cycle)
+/// attenuated by `attenuation` envelope steps. The level is looked up on
+/// a log scale and turned linear by the exponent table.
+fn wave(phase: u32, rectified: bool, attenuation: u16) -> i16 {
+    let negative: bool = phase & 0x200 != 0;
+    if negative && rectified {
+        return 0;
+    }
+    let index: u32 = match phase & 0x100 {
+        0 => phase & 0xFF,
+        _ => !phase & 0xFF,
+    };
+    let level: u32 = LOGSIN_TABLE[index as usize] as u32
+        + ((attenuation as u32) << ENVELOPE_SHIFT);
+    let mantissa: u32 = EXP_TABLE[(!level & 0xFF) as usize] as u32 | 0x400;
+    let magnitude: i16 = ((mantissa << 1) >> (level >> 8)) as i16;
+    match negative {
+        true => -magnitude,
+        false => magnitude,
+    }
+}
@@ -0,0 +1,93 @@
+//! Pieces shared by the Konami VRC boards.
+
+// IRQ control register
+const IRQ_ENABLE_AFTER_ACK: u8 = 0x01;
+const IRQ_ENABLE: u8 = 0x02;
+const IRQ_CYCLE_MODE: u8 = 0x04;
+
+/// The prescaler divides cpu cycles by 341/3 to approximate one tick per
+/// scanline without looking at the PPU.
+const PRESCALER_PERIOD: i16 = 341;
+
+/// The IRQ counter found on VRC4, VRC6 and VRC7: an 8-bit up counter that
+/// raises the IRQ and reloads from the latch when it overflows. It ticks
+/// either every cpu cycle or once per (approximate) scanline.
+///
+/// ```no_run
+/// latch    reload value
+/// control  xxxx xMEA  M: cycle mode, E: enable, A: enable after ack
+/// ack      clears the IRQ and copies A into E
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub(super) struct VrcIrq {
+    latch: u8,
+    counter: u8,
+    prescaler: i16,
+    control: u8,
+    pending: bool,
+}
+impl VrcIrq {
+    pub(super) fn new() -> Self {
+        Self {
+            latch: 0x00,
+            counter: 0x00,
+            prescaler: PRESCALER_PERIOD,
+            control: 0x00,
+            pending: false,
+        }
+    }
+
+    pub(super) const fn latch(&self) -> u8 {
+        self.latch
+    }
+
+    pub(super) fn set_latch(&mut self, latch: u8) {
+        self.latch = latch;
+    }
+
+    pub(super) fn write_control(&mut self, data: u8) {
+        self.control = data & 0x07;
+        self.pending = false;
+        if self.control & IRQ_ENABLE != 0 {
+            self.counter = self.latch;
+            self.prescaler = PRESCALER_PERIOD;
+        }
+    }
+
+    pub(super) fn acknowledge(&mut self) {
+        self.pending = false;
+        self.control = match self.control & IRQ_ENABLE_AFTER_ACK {
+            0 => self.control & !IRQ_ENABLE,
+            _ => self.control | IRQ_ENABLE,
+        };
+    }
+
+    pub(super) const fn pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Called once per cpu cycle.
+    pub(super) fn clock(&mut self) {
+        if self.control & IRQ_ENABLE == 0 {
+            return;
+        }
+        if self.control & IRQ_CYCLE_MODE != 0 {
+            self.tick();
+        } else {
+            self.prescaler -= 3;
+            if self.prescaler <= 0 {
+                self.prescaler += PRESCALER_PERIOD;
+                self.tick();
+            }
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
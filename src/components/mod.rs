@@ -2,8 +2,13 @@ pub mod bus;
 pub mod cartridge;
 pub mod dh_cpu;
 pub mod dh_ppu;
+pub mod hash;
 pub mod mappers;
+pub mod patch;
+pub mod romdb;
 pub mod types;
+pub mod unif;
+pub mod zip;
 
 use self::dh_cpu::CPU;
 use self::types::AddrModeMneumonic;
@@ -15,7 +20,7 @@ use crate::components::types::InstructionMneumonic;
 use once_cell::sync::Lazy;
 
 const START_OF_RAM: u16 = 0x0000;
-const END_OF_RAM: u16 = 0xFFFF;
+const END_OF_RAM: u16 = 0x1FFF; // 2KB of ram and its mirrors
 const LOW_BYTE: u16 = 0x00FF;
 const HIGH_BYTE: u16 = 0xFF00;
 const TOP_BIT_THRESH: u16 = 0x0080;
@@ -54,259 +59,259 @@ static LOOKUP_TABLE: Lazy<[CpuInstruction; 256]> = Lazy::new(|| {
         //    OP  AD  C
         cins!(BRK IMM 7), // CINS{mneumonic: imneumonic!(BRK,IMM), op: M6502::BRK, am: M6502::IMM, cycles: 7},
         cins!(ORA IZX 6),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 3),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(SLO IZX 8), // unofficial
+        cins!(NOP ZP0 3), // unofficial
         cins!(ORA ZP0 3),
         cins!(ASL ZP0 5),
-        cins!(XXX IMP 5), // illegal opcode
+        cins!(SLO ZP0 5), // unofficial
         cins!(PHP IMP 3),
         cins!(ORA IMM 2),
         cins!(ASL IMP 2),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(ANC IMM 2), // unofficial
+        cins!(NOP ABS 4), // unofficial
         cins!(ORA ABS 4),
         cins!(ASL ABS 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(SLO ABS 6), // unofficial
         cins!(BPL REL 2),
         cins!(ORA IZY 5),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(SLO IZY 8), // unofficial
+        cins!(NOP ZPX 4), // unofficial
         cins!(ORA ZPX 4),
         cins!(ASL ZPX 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(SLO ZPX 6), // unofficial
         cins!(CLC IMP 2),
         cins!(ORA ABY 4),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 7), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(NOP IMP 2), // unofficial
+        cins!(SLO ABY 7), // unofficial
+        cins!(NOP ABX 4), // unofficial
         cins!(ORA ABX 4),
         cins!(ASL ABX 7),
-        cins!(XXX IMP 7), // illegal opcode
+        cins!(SLO ABX 7), // unofficial
         cins!(JSR ABS 6),
         cins!(AND IZX 6),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(RLA IZX 8), // unofficial
         cins!(BIT ZP0 3),
         cins!(AND ZP0 3),
         cins!(ROL ZP0 5),
-        cins!(XXX IMP 5), // illegal opcode
+        cins!(RLA ZP0 5), // unofficial
         cins!(PLP IMP 4),
         cins!(AND IMM 2),
         cins!(ROL IMP 2),
-        cins!(XXX IMP 2), // illegal opcode
+        cins!(ANC IMM 2), // unofficial
         cins!(BIT ABS 4),
         cins!(AND ABS 4),
         cins!(ROL ABS 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(RLA ABS 6), // unofficial
         cins!(BMI REL 2),
         cins!(AND IZY 5),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(RLA IZY 8), // unofficial
+        cins!(NOP ZPX 4), // unofficial
         cins!(AND ZPX 4),
         cins!(ROL ZPX 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(RLA ZPX 6), // unofficial
         cins!(SEC IMP 2),
         cins!(AND ABY 4),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 7), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(NOP IMP 2), // unofficial
+        cins!(RLA ABY 7), // unofficial
+        cins!(NOP ABX 4), // unofficial
         cins!(AND ABX 4),
         cins!(ROL ABX 7),
-        cins!(XXX IMP 7), // illegal opcode
+        cins!(RLA ABX 7), // unofficial
         cins!(RTI IMP 6),
         cins!(EOR IZX 6),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 3),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(SRE IZX 8), // unofficial
+        cins!(NOP ZP0 3), // unofficial
         cins!(EOR ZP0 3),
         cins!(LSR ZP0 5),
-        cins!(XXX IMP 5), // illegal opcode
+        cins!(SRE ZP0 5), // unofficial
         cins!(PHA IMP 3),
         cins!(EOR IMM 2),
         cins!(LSR IMP 2),
-        cins!(XXX IMP 2), // illegal opcode
+        cins!(ALR IMM 2), // unofficial
         cins!(JMP ABS 3),
         cins!(EOR ABS 4),
         cins!(LSR ABS 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(SRE ABS 6), // unofficial
         cins!(BVC REL 2),
         cins!(EOR IZY 5),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(SRE IZY 8), // unofficial
+        cins!(NOP ZPX 4), // unofficial
         cins!(EOR ZPX 4),
         cins!(LSR ZPX 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(SRE ZPX 6), // unofficial
         cins!(CLI IMP 2),
         cins!(EOR ABY 4),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 7), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(NOP IMP 2), // unofficial
+        cins!(SRE ABY 7), // unofficial
+        cins!(NOP ABX 4), // unofficial
         cins!(EOR ABX 4),
         cins!(LSR ABX 7),
-        cins!(XXX IMP 7), // illegal opcode
+        cins!(SRE ABX 7), // unofficial
         cins!(RTS IMP 6),
         cins!(ADC IZX 6),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 3),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(RRA IZX 8), // unofficial
+        cins!(NOP ZP0 3), // unofficial
         cins!(ADC ZP0 3),
         cins!(ROR ZP0 5),
-        cins!(XXX IMP 5), // illegal opcode
+        cins!(RRA ZP0 5), // unofficial
         cins!(PLA IMP 4),
         cins!(ADC IMM 2),
         cins!(ROR IMP 2),
-        cins!(XXX IMP 2), // illegal opcode
+        cins!(ARR IMM 2), // unofficial
         cins!(JMP IND 5),
         cins!(ADC ABS 4),
         cins!(ROR ABS 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(RRA ABS 6), // unofficial
         cins!(BVS REL 2),
         cins!(ADC IZY 5),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(RRA IZY 8), // unofficial
+        cins!(NOP ZPX 4), // unofficial
         cins!(ADC ZPX 4),
         cins!(ROR ZPX 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(RRA ZPX 6), // unofficial
         cins!(SEI IMP 2),
         cins!(ADC ABY 4),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 7), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(NOP IMP 2), // unofficial
+        cins!(RRA ABY 7), // unofficial
+        cins!(NOP ABX 4), // unofficial
         cins!(ADC ABX 4),
         cins!(ROR ABX 7),
-        cins!(XXX IMP 7), // illegal opcode
-        cins!(NOP IMP 2),
+        cins!(RRA ABX 7), // unofficial
+        cins!(NOP IMM 2), // unofficial
         cins!(STA IZX 6),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(NOP IMM 2), // unofficial
+        cins!(SAX IZX 6), // unofficial
         cins!(STY ZP0 3),
         cins!(STA ZP0 3),
         cins!(STX ZP0 3),
-        cins!(XXX IMP 3), // illegal opcode
+        cins!(SAX ZP0 3), // unofficial
         cins!(DEY IMP 2),
-        cins!(NOP IMP 2),
+        cins!(NOP IMM 2), // unofficial
         cins!(TXA IMP 2),
-        cins!(XXX IMP 2), // illegal opcode
+        cins!(XAA IMM 2), // unofficial
         cins!(STY ABS 4),
         cins!(STA ABS 4),
         cins!(STX ABS 4),
-        cins!(XXX IMP 4), // illegal opcode
+        cins!(SAX ABS 4), // unofficial
         cins!(BCC REL 2),
         cins!(STA IZY 6),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(SHA IZY 6), // unofficial
         cins!(STY ZPX 4),
         cins!(STA ZPX 4),
         cins!(STX ZPY 4),
-        cins!(XXX IMP 4), // illegal opcode
+        cins!(SAX ZPY 4), // unofficial
         cins!(TYA IMP 2),
         cins!(STA ABY 5),
         cins!(TXS IMP 2),
-        cins!(XXX IMP 5), // illegal opcode
-        cins!(NOP IMP 5),
+        cins!(TAS ABY 5), // unofficial
+        cins!(SHY ABX 5), // unofficial
         cins!(STA ABX 5),
-        cins!(XXX IMP 5), // illegal opcode
-        cins!(XXX IMP 5), // illegal opcode
+        cins!(SHX ABY 5), // unofficial
+        cins!(SHA ABY 5), // unofficial
         cins!(LDY IMM 2),
         cins!(LDA IZX 6),
         cins!(LDX IMM 2),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(LAX IZX 6), // unofficial
         cins!(LDY ZP0 3),
         cins!(LDA ZP0 3),
         cins!(LDX ZP0 3),
-        cins!(XXX IMP 3), // illegal opcode
+        cins!(LAX ZP0 3), // unofficial
         cins!(TAY IMP 2),
         cins!(LDA IMM 2),
         cins!(TAX IMP 2),
-        cins!(XXX IMP 2), // illegal opcode
+        cins!(LXA IMM 2), // unofficial
         cins!(LDY ABS 4),
         cins!(LDA ABS 4),
         cins!(LDX ABS 4),
-        cins!(XXX IMP 4), // illegal opcode
+        cins!(LAX ABS 4), // unofficial
         cins!(BCS REL 2),
         cins!(LDA IZY 5),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 5), // illegal opcode
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(LAX IZY 5), // unofficial
         cins!(LDY ZPX 4),
         cins!(LDA ZPX 4),
         cins!(LDX ZPY 4),
-        cins!(XXX IMP 4), // illegal opcode
+        cins!(LAX ZPY 4), // unofficial
         cins!(CLV IMP 2),
         cins!(LDA ABY 4),
         cins!(TSX IMP 2),
-        cins!(XXX IMP 4), // illegal opcode
+        cins!(LAS ABY 4), // unofficial
         cins!(LDY ABX 4),
         cins!(LDA ABX 4),
         cins!(LDX ABY 4),
-        cins!(XXX IMP 4), // illegal opcode
+        cins!(LAX ABY 4), // unofficial
         cins!(CPY IMM 2),
         cins!(CMP IZX 6),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 8), // illegal opcode
+        cins!(NOP IMM 2), // unofficial
+        cins!(DCP IZX 8), // unofficial
         cins!(CPY ZP0 3),
         cins!(CMP ZP0 3),
         cins!(DEC ZP0 5),
-        cins!(XXX IMP 5), // illegal opcode
+        cins!(DCP ZP0 5), // unofficial
         cins!(INY IMP 2),
         cins!(CMP IMM 2),
         cins!(DEX IMP 2),
-        cins!(XXX IMP 2), // illegal opcode
+        cins!(AXS IMM 2), // unofficial
         cins!(CPY ABS 4),
         cins!(CMP ABS 4),
         cins!(DEC ABS 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(DCP ABS 6), // unofficial
         cins!(BNE REL 2),
         cins!(CMP IZY 5),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(DCP IZY 8), // unofficial
+        cins!(NOP ZPX 4), // unofficial
         cins!(CMP ZPX 4),
         cins!(DEC ZPX 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(DCP ZPX 6), // unofficial
         cins!(CLD IMP 2),
         cins!(CMP ABY 4),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 7), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(NOP IMP 2), // unofficial
+        cins!(DCP ABY 7), // unofficial
+        cins!(NOP ABX 4), // unofficial
         cins!(CMP ABX 4),
         cins!(DEC ABX 7),
-        cins!(XXX IMP 7), // illegal opcode
+        cins!(DCP ABX 7), // unofficial
         cins!(CPX IMM 2),
         cins!(SBC IZX 6),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 8), // illegal opcode
+        cins!(NOP IMM 2), // unofficial
+        cins!(ISC IZX 8), // unofficial
         cins!(CPX ZP0 3),
         cins!(SBC ZP0 3),
         cins!(INC ZP0 5),
-        cins!(XXX IMP 5), // illegal opcode
+        cins!(ISC ZP0 5), // unofficial
         cins!(INX IMP 2),
         cins!(SBC IMM 2),
         cins!(NOP IMP 2),
-        cins!(SBC IMP 2),
+        cins!(SBC IMM 2), // unofficial
         cins!(CPX ABS 4),
         cins!(SBC ABS 4),
         cins!(INC ABS 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(ISC ABS 6), // unofficial
         cins!(BEQ REL 2),
         cins!(SBC IZY 5),
-        cins!(XXX IMP 2), // illegal opcode
-        cins!(XXX IMP 8), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(XXX IMP 2), // unofficial, jams the cpu
+        cins!(ISC IZY 8), // unofficial
+        cins!(NOP ZPX 4), // unofficial
         cins!(SBC ZPX 4),
         cins!(INC ZPX 6),
-        cins!(XXX IMP 6), // illegal opcode
+        cins!(ISC ZPX 6), // unofficial
         cins!(SED IMP 2),
         cins!(SBC ABY 4),
-        cins!(NOP IMP 2),
-        cins!(XXX IMP 7), // illegal opcode
-        cins!(NOP IMP 4),
+        cins!(NOP IMP 2), // unofficial
+        cins!(ISC ABY 7), // unofficial
+        cins!(NOP ABX 4), // unofficial
         cins!(SBC ABX 4),
         cins!(INC ABX 7),
-        cins!(XXX IMP 7), // illegal opcode
+        cins!(ISC ABX 7), // unofficial
     ]
 });
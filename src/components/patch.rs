@@ -0,0 +1,378 @@
+//! ROM patch formats.
+
+use std::fmt;
+
+use super::hash;
+
+const IPS_MAGIC: &[u8; 5] = b"PATCH";
+const IPS_EOF: &[u8; 3] = b"EOF";
+/// An offset equal to "EOF" would end the patch early.
+const IPS_EOF_OFFSET: usize = 0x454F46;
+const IPS_MAX_OFFSET: usize = 0xFFFFFF;
+const IPS_MAX_RECORD: usize = 0xFFFF;
+/// Equal bytes a record may swallow rather than starting a new one, a new
+/// record costs 5 bytes of header.
+const IPS_MERGE_GAP: usize = 5;
+
+const BPS_MAGIC: &[u8; 4] = b"BPS1";
+const UPS_MAGIC: &[u8; 4] = b"UPS1";
+/// BPS and UPS end with the CRC-32 of the source, the target and the patch
+/// itself.
+const FOOTER_SIZE: usize = 12;
+
+/// Which image a BPS/UPS checksum belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Checksum {
+    /// The image the patch is applied to, a mismatch means the patch was
+    /// made for a different dump.
+    Source,
+    /// The patched image.
+    Target,
+    /// The patch file itself, a mismatch means it is corrupted.
+    Patch,
+}
+
+/// Reasons a patch cannot be applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// The patch does not start with the magic bytes of its format.
+    BadMagic,
+    /// The patch ends in the middle of a record.
+    Truncated,
+    /// A BPS/UPS checksum does not match.
+    Checksum {
+        which: Checksum,
+        expected: u32,
+        found: u32,
+    },
+    /// A BPS/UPS action reaches outside the source or target.
+    OutOfBounds,
+}
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a patch file (bad magic bytes)"),
+            Self::Truncated => write!(f, "patch file is truncated"),
+            Self::Checksum {
+                which,
+                expected,
+                found,
+            } => {
+                let what: &str = match which {
+                    Checksum::Source => "the rom does not match the patch",
+                    Checksum::Target => "the patched rom is wrong",
+                    Checksum::Patch => "the patch file is corrupted",
+                };
+                write!(
+                    f,
+                    "{what}: expected CRC32 {expected:08X}, found {found:08X}"
+                )
+            }
+            Self::OutOfBounds => {
+                write!(f, "patch reaches past the end of the rom")
+            }
+        }
+    }
+}
+impl std::error::Error for PatchError {}
+
+/// Applies an IPS, BPS or UPS patch, told apart by their magic bytes.
+pub fn apply(data: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(BPS_MAGIC) {
+        apply_bps(data, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(data, patch)
+    } else {
+        apply_ips(data, patch)
+    }
+}
+
+/// Applies an IPS patch, records past the end grow the data.
+///
+/// ```no_run
+/// "PATCH"
+/// records until "EOF":
+///   offset (3 bytes, big endian), size (2 bytes)
+///   size > 0: size bytes of data
+///   size = 0: run length (2 bytes) and the byte to repeat
+/// optional truncated length (3 bytes)
+/// ```
+pub fn apply_ips(data: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(IPS_MAGIC) {
+        return Err(PatchError::BadMagic);
+    }
+    let mut out: Vec<u8> = data.to_vec();
+    let mut pos: usize = IPS_MAGIC.len();
+    let take = |pos: &mut usize, n: usize| -> Result<&[u8], PatchError> {
+        let bytes = patch.get(*pos..*pos + n).ok_or(PatchError::Truncated)?;
+        *pos += n;
+        Ok(bytes)
+    };
+    let be =
+        |bytes: &[u8]| bytes.iter().fold(0usize, |n, b| n << 8 | *b as usize);
+
+    loop {
+        let offset: &[u8] = take(&mut pos, 3)?;
+        if offset == IPS_EOF {
+            break;
+        }
+        let offset: usize = be(offset);
+        let size: usize = be(take(&mut pos, 2)?);
+        let record: Vec<u8> = match size {
+            0 => {
+                let run: usize = be(take(&mut pos, 2)?);
+                vec![take(&mut pos, 1)?[0]; run]
+            }
+            n => take(&mut pos, n)?.to_vec(),
+        };
+        if out.len() < offset + record.len() {
+            out.resize(offset + record.len(), 0x00);
+        }
+        out[offset..offset + record.len()].copy_from_slice(&record);
+    }
+
+    if let Ok(len) = take(&mut pos, 3) {
+        out.truncate(be(len));
+    }
+    Ok(out)
+}
+
+/// Builds an IPS patch turning `original` into `modified`.
+pub fn create_ips(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    let mut patch: Vec<u8> = IPS_MAGIC.to_vec();
+    let differs = |i: usize| original.get(i) != Some(&modified[i]);
+
+    let mut i: usize = 0;
+    while i < modified.len().min(IPS_MAX_OFFSET) {
+        if !differs(i) {
+            i += 1;
+            continue;
+        }
+        // an offset spelling "EOF" starts one byte early instead
+        let start: usize = if i == IPS_EOF_OFFSET { i - 1 } else { i };
+        // one past the last differing byte, runs of equal bytes up to the
+        // merge gap stay in the record
+        let mut end: usize = i + 1;
+        let mut j: usize = i + 1;
+        while j < modified.len()
+            && j - start < IPS_MAX_RECORD
+            && j - end <= IPS_MERGE_GAP
+        {
+            if differs(j) {
+                end = j + 1;
+            }
+            j += 1;
+        }
+
+        patch.extend_from_slice(&(start as u32).to_be_bytes()[1..]);
+        patch.extend_from_slice(&((end - start) as u16).to_be_bytes());
+        patch.extend_from_slice(&modified[start..end]);
+        i = end;
+    }
+    patch.extend_from_slice(IPS_EOF);
+    if modified.len() < original.len() {
+        patch.extend_from_slice(&(modified.len() as u32).to_be_bytes()[1..]);
+    }
+    patch
+}
+
+/// Applies a BPS patch, checking the source, target and patch CRC-32s.
+///
+/// ```no_run
+/// "BPS1"
+/// source size, target size, metadata size (numbers), metadata
+/// actions until the footer, each a number: length - 1 << 2 | command
+///   0 source read: copy from the source at the output position
+///   1 target read: copy length bytes from the patch
+///   2 source copy: move the source cursor by a signed number, copy from it
+///   3 target copy: move the target cursor by a signed number, copy from the
+///                  output written so far
+/// source CRC32, target CRC32, patch CRC32 (little endian)
+/// ```
+pub fn apply_bps(data: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(BPS_MAGIC) {
+        return Err(PatchError::BadMagic);
+    }
+    let (target_crc, end) = check_footer(data, patch)?;
+    let mut pos: usize = BPS_MAGIC.len();
+    let _source_size: usize = read_number(patch, &mut pos, end)?;
+    let target_size: usize = read_number(patch, &mut pos, end)?;
+    let metadata_size: usize = read_number(patch, &mut pos, end)?;
+    pos = pos
+        .checked_add(metadata_size)
+        .filter(|pos| *pos <= end)
+        .ok_or(PatchError::Truncated)?;
+
+    let mut out: Vec<u8> = Vec::with_capacity(target_size);
+    let mut source_cursor: usize = 0;
+    let mut target_cursor: usize = 0;
+    while pos < end {
+        let action: usize = read_number(patch, &mut pos, end)?;
+        let length: usize = (action >> 2) + 1;
+        if out.len() + length > target_size {
+            return Err(PatchError::OutOfBounds);
+        }
+        match action & 0x03 {
+            0 => {
+                let start: usize = out.len();
+                let bytes = data
+                    .get(start..start + length)
+                    .ok_or(PatchError::OutOfBounds)?;
+                out.extend_from_slice(bytes);
+            }
+            1 => {
+                let bytes = patch
+                    .get(pos..pos + length)
+                    .filter(|_| pos + length <= end)
+                    .ok_or(PatchError::Truncated)?;
+                out.extend_from_slice(bytes);
+                pos += length;
+            }
+            2 => {
+                source_cursor = move_cursor(
+                    source_cursor,
+                    read_number(patch, &mut pos, end)?,
+                )?;
+                let bytes = data
+                    .get(source_cursor..source_cursor + length)
+                    .ok_or(PatchError::OutOfBounds)?;
+                out.extend_from_slice(bytes);
+                source_cursor += length;
+            }
+            _ => {
+                target_cursor = move_cursor(
+                    target_cursor,
+                    read_number(patch, &mut pos, end)?,
+                )?;
+                // the copy may overlap what it writes, byte by byte repeats
+                // a pattern
+                for _ in 0..length {
+                    let byte: u8 = *out
+                        .get(target_cursor)
+                        .ok_or(PatchError::OutOfBounds)?;
+                    out.push(byte);
+                    target_cursor += 1;
+                }
+            }
+        }
+    }
+    if out.len() != target_size {
+        return Err(PatchError::Truncated);
+    }
+    verify(Checksum::Target, target_crc, &out)?;
+    Ok(out)
+}
+
+/// Applies a UPS patch, checking the source, target and patch CRC-32s.
+///
+/// ```no_run
+/// "UPS1"
+/// source size, target size (numbers)
+/// hunks until the footer:
+///   bytes to skip (number), bytes to XOR with the source ending with $00
+/// source CRC32, target CRC32, patch CRC32 (little endian)
+/// ```
+pub fn apply_ups(data: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if !patch.starts_with(UPS_MAGIC) {
+        return Err(PatchError::BadMagic);
+    }
+    let (target_crc, end) = check_footer(data, patch)?;
+    let mut pos: usize = UPS_MAGIC.len();
+    let _source_size: usize = read_number(patch, &mut pos, end)?;
+    let target_size: usize = read_number(patch, &mut pos, end)?;
+
+    let mut out: Vec<u8> = data.to_vec();
+    out.resize(target_size, 0x00);
+    let mut cursor: usize = 0;
+    while pos < end {
+        cursor = cursor
+            .checked_add(read_number(patch, &mut pos, end)?)
+            .filter(|cursor| *cursor <= out.len())
+            .ok_or(PatchError::OutOfBounds)?;
+        loop {
+            let byte: u8 =
+                *patch[..end].get(pos).ok_or(PatchError::Truncated)?;
+            pos += 1;
+            if byte == 0x00 {
+                cursor =
+                    cursor.checked_add(1).ok_or(PatchError::OutOfBounds)?;
+                break;
+            }
+            *out.get_mut(cursor).ok_or(PatchError::OutOfBounds)? ^= byte;
+            cursor = cursor.checked_add(1).ok_or(PatchError::OutOfBounds)?;
+        }
+    }
+    verify(Checksum::Target, target_crc, &out)?;
+    Ok(out)
+}
+
+/// Checks the patch and source CRC-32 in a BPS/UPS footer, returns the
+/// target CRC-32 and where the footer starts.
+fn check_footer(data: &[u8], patch: &[u8]) -> Result<(u32, usize), PatchError> {
+    let end: usize = patch
+        .len()
+        .checked_sub(FOOTER_SIZE)
+        .filter(|end| *end >= 4)
+        .ok_or(PatchError::Truncated)?;
+    let crc = |at: usize| {
+        u32::from_le_bytes([
+            patch[at],
+            patch[at + 1],
+            patch[at + 2],
+            patch[at + 3],
+        ])
+    };
+    verify(Checksum::Patch, crc(end + 8), &patch[..end + 8])?;
+    verify(Checksum::Source, crc(end), data)?;
+    Ok((crc(end + 4), end))
+}
+
+fn verify(
+    which: Checksum,
+    expected: u32,
+    data: &[u8],
+) -> Result<(), PatchError> {
+    match hash::crc32(data) {
+        found if found == expected => Ok(()),
+        found => Err(PatchError::Checksum {
+            which,
+            expected,
+            found,
+        }),
+    }
+}
+
+/// Reads a BPS/UPS number: 7 bits per byte, least significant first, the
+/// last byte has bit 7 set. Every continuation adds one more so each value
+/// has a single encoding.
+fn read_number(
+    patch: &[u8],
+    pos: &mut usize,
+    end: usize,
+) -> Result<usize, PatchError> {
+    let mut number: usize = 0;
+    let mut shift: usize = 1;
+    loop {
+        let byte: u8 = *patch[..end].get(*pos).ok_or(PatchError::Truncated)?;
+        *pos += 1;
+        number = (byte as usize & 0x7F)
+            .checked_mul(shift)
+            .and_then(|n| n.checked_add(number))
+            .ok_or(PatchError::OutOfBounds)?;
+        if byte & 0x80 != 0 {
+            return Ok(number);
+        }
+        shift = shift.checked_mul(0x80).ok_or(PatchError::OutOfBounds)?;
+        number = number.checked_add(shift).ok_or(PatchError::OutOfBounds)?;
+    }
+}
+
+/// Moves a BPS copy cursor by a signed offset, bit 0 of the number is the
+/// sign.
+fn move_cursor(cursor: usize, offset: usize) -> Result<usize, PatchError> {
+    match offset & 0x01 {
+        0 => cursor.checked_add(offset >> 1),
+        _ => cursor.checked_sub(offset >> 1),
+    }
+    .ok_or(PatchError::OutOfBounds)
+}
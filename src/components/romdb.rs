@@ -0,0 +1,228 @@
+//! Database of known dumps. Plenty of iNES images in the wild carry headers
+//! written by old tools with the wrong mapper, mirroring or battery bits,
+//! the PRG and CHR data are what identify a game.
+
+use std::fmt;
+
+use once_cell::sync::Lazy;
+
+use super::cartridge::{HeaderFormat, Mirroring, TimingRegion};
+use super::hash;
+
+static EMBEDDED: Lazy<RomDatabase> = Lazy::new(|| {
+    RomDatabase::parse(include_str!("romdb.txt"))
+        .expect("the embedded rom database is malformed")
+});
+
+/// What identifies a dump: CRC-32 and SHA-1 of its PRG ROM followed by its
+/// CHR ROM, the header and trainer left out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomHash {
+    pub crc32: u32,
+    pub sha1: [u8; 20],
+}
+impl RomHash {
+    pub fn of(data: &[u8]) -> Self {
+        Self {
+            crc32: hash::crc32(data),
+            sha1: hash::sha1(data),
+        }
+    }
+}
+impl fmt::Display for RomHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "CRC32 {:08X} SHA-1 ", self.crc32)?;
+        self.sha1.iter().try_for_each(|b| write!(f, "{b:02X}"))
+    }
+}
+
+/// A known dump. Fields left `None` keep whatever the header says.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomEntry {
+    pub crc32: u32,
+    pub sha1: Option<[u8; 20]>,
+    pub mapper: Option<u16>,
+    pub submapper: Option<u8>,
+    pub mirror: Option<Mirroring>,
+    pub battery: Option<bool>,
+    pub timing: Option<TimingRegion>,
+    pub title: String,
+}
+
+/// A line of the database that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomDbError {
+    pub line: usize,
+    pub message: &'static str,
+}
+impl fmt::Display for RomDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rom database line {}: {}", self.line, self.message)
+    }
+}
+impl std::error::Error for RomDbError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct RomDatabase {
+    entries: Vec<RomEntry>,
+}
+impl RomDatabase {
+    /// The database built into the emulator, from romdb.txt.
+    pub fn embedded() -> &'static Self {
+        &EMBEDDED
+    }
+
+    /// Parses the database's text format, one dump per line:
+    ///
+    /// ```no_run
+    /// # comment
+    /// crc32 | sha1 | mapper | submapper | mirroring | battery | region | title
+    /// 1A2B3C4D | - | 4 | 0 | V | yes | NTSC | Some Game (USA)
+    /// ```
+    ///
+    /// Hashes are hex, "-" leaves a field (or the SHA-1 check) out.
+    /// Mirroring is H, V, 4 (four screen) or 1 (one-screen picked by the
+    /// mapper), battery yes or no, region NTSC, PAL, MULTI or DENDY.
+    pub fn parse(text: &str) -> Result<Self, RomDbError> {
+        let mut entries: Vec<RomEntry> = vec![];
+        for (n, line) in text.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &'static str| RomDbError {
+                line: n + 1,
+                message,
+            };
+            let fields: Vec<&str> =
+                line.splitn(8, '|').map(str::trim).collect();
+            let [crc32, sha1, mapper, submapper, mirror, battery, timing, title] =
+                fields[..]
+            else {
+                return Err(error("expected 8 fields separated by '|'"));
+            };
+            entries.push(RomEntry {
+                crc32: u32::from_str_radix(crc32, 16)
+                    .map_err(|_| error("bad CRC32"))?,
+                sha1: field(sha1)
+                    .map(parse_sha1)
+                    .transpose()
+                    .map_err(|_| error("bad SHA-1"))?,
+                mapper: field(mapper)
+                    .map(str::parse)
+                    .transpose()
+                    .map_err(|_| error("bad mapper number"))?,
+                submapper: field(submapper)
+                    .map(str::parse)
+                    .transpose()
+                    .map_err(|_| error("bad submapper"))?,
+                mirror: field(mirror)
+                    .map(|value| match value {
+                        "H" => Ok(Mirroring::HORIZONTAL),
+                        "V" => Ok(Mirroring::VERTICAL),
+                        "4" => Ok(Mirroring::FOUR_SCREEN),
+                        "1" => Ok(Mirroring::ONESCREAN_LO),
+                        _ => Err(error("mirroring must be H, V, 4 or 1")),
+                    })
+                    .transpose()?,
+                battery: field(battery)
+                    .map(|value| match value {
+                        "yes" => Ok(true),
+                        "no" => Ok(false),
+                        _ => Err(error("battery must be yes or no")),
+                    })
+                    .transpose()?,
+                timing: field(timing)
+                    .map(|value| match value {
+                        "NTSC" => Ok(TimingRegion::NTSC),
+                        "PAL" => Ok(TimingRegion::PAL),
+                        "MULTI" => Ok(TimingRegion::MULTI_REGION),
+                        "DENDY" => Ok(TimingRegion::DENDY),
+                        _ => Err(error(
+                            "region must be NTSC, PAL, MULTI or DENDY",
+                        )),
+                    })
+                    .transpose()?,
+                title: title.to_string(),
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Looks a dump up by its CRC-32, entries with a SHA-1 have to match it
+    /// as well.
+    pub fn find(&self, hash: &RomHash) -> Option<&RomEntry> {
+        self.entries.iter().find(|entry| {
+            entry.crc32 == hash.crc32
+                && entry.sha1.is_none_or(|sha1| sha1 == hash.sha1)
+        })
+    }
+}
+
+/// A field's value, `None` for "-".
+fn field(value: &str) -> Option<&str> {
+    (value != "-").then_some(value)
+}
+
+fn parse_sha1(hex: &str) -> Result<[u8; 20], ()> {
+    if hex.len() != 40 || !hex.is_ascii() {
+        return Err(());
+    }
+    let mut sha1 = [0u8; 20];
+    for (byte, pair) in sha1.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        let pair: &str = std::str::from_utf8(pair).map_err(|_| ())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| ())?;
+    }
+    Ok(sha1)
+}
+
+/// Everything known about a loaded image, for tools to print.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RomInfo {
+    /// Canonical title from the database, `None` for unknown dumps.
+    pub title: Option<String>,
+    pub hash: RomHash,
+    pub format: HeaderFormat,
+    pub mapper: u16,
+    pub submapper: u8,
+    pub mirror: Mirroring,
+    pub battery: bool,
+    pub timing: TimingRegion,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    /// The database disagreed with the header and won.
+    pub corrected: bool,
+}
+impl fmt::Display for RomInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.title.as_deref().unwrap_or("unknown dump"))?;
+        writeln!(f, "  {}", self.hash)?;
+        write!(
+            f,
+            "  {:?} | mapper {}.{} | PRG {}KB | CHR {}KB | {:?} mirroring | \
+             {:?}",
+            self.format,
+            self.mapper,
+            self.submapper,
+            self.prg_rom_size / 1024,
+            self.chr_rom_size / 1024,
+            self.mirror,
+            self.timing,
+        )?;
+        if self.battery {
+            write!(f, " | battery")?;
+        }
+        if self.corrected {
+            write!(f, " | header corrected")?;
+        }
+        Ok(())
+    }
+}
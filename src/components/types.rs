@@ -12,9 +12,10 @@ use super::{bus::Bus, dh_cpu::CPU};
 /// V = 1 << 6  Overflow
 /// N = 1 << 7  Negative
 /// ```
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Default)]
 pub enum CpuFlags {
-    E = 0,      // Empty default
+    #[default]
+    E = 0, // Empty default
     C = 1 << 0, // Carry Bit
     Z = 1 << 1, // Zero
     I = 1 << 2, // Disable Interrupts
@@ -24,11 +25,6 @@ pub enum CpuFlags {
     V = 1 << 6, // Overflow
     N = 1 << 7, // Negative
 }
-impl Default for CpuFlags {
-    fn default() -> Self {
-        Self::E
-    }
-}
 
 /// A struct representing an instruction for the MOS 6502 microprocessor.
 ///
@@ -80,7 +76,7 @@ impl InstructionMneumonic {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Default)]
 pub enum OpcodeMneumonic {
     ADC,
     AND,
@@ -138,16 +134,33 @@ pub enum OpcodeMneumonic {
     TXA,
     TXS,
     TYA,
+    // unofficial opcodes
+    ALR,
+    ANC,
+    ARR,
+    AXS,
+    DCP,
+    ISC,
+    LAS,
+    LAX,
+    LXA,
+    RLA,
+    RRA,
+    SAX,
+    SHA,
+    SHX,
+    SHY,
+    SLO,
+    SRE,
+    TAS,
+    XAA,
+    #[default]
     XXX,
 }
-impl Default for OpcodeMneumonic {
-    fn default() -> Self {
-        Self::XXX
-    }
-}
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Default)]
 pub enum AddrModeMneumonic {
+    #[default]
     IMP,
     IMM,
     ZP0,
@@ -161,11 +174,6 @@ pub enum AddrModeMneumonic {
     IZX,
     IZY,
 }
-impl Default for AddrModeMneumonic {
-    fn default() -> Self {
-        Self::IMP
-    }
-}
 
 pub trait M6502Opcodes {
     fn ADC(cpu: &mut CPU, bus: &mut Bus) -> u8;
@@ -224,6 +232,26 @@ pub trait M6502Opcodes {
     fn TXA(cpu: &mut CPU, bus: &mut Bus) -> u8;
     fn TXS(cpu: &mut CPU, bus: &mut Bus) -> u8;
     fn TYA(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    // unofficial opcodes
+    fn ALR(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn ANC(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn ARR(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn AXS(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn DCP(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn ISC(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn LAS(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn LAX(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn LXA(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn RLA(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn RRA(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn SAX(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn SHA(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn SHX(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn SHY(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn SLO(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn SRE(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn TAS(cpu: &mut CPU, bus: &mut Bus) -> u8;
+    fn XAA(cpu: &mut CPU, bus: &mut Bus) -> u8;
     fn XXX(cpu: &mut CPU, bus: &mut Bus) -> u8;
 }
 
@@ -0,0 +1,301 @@
+//! UNIF (.unf) images. Instead of a mapper number UNIF names the board the
+//! game was dumped from and stores everything in tagged chunks, the board
+//! name is looked up in [`BOARDS`] and the image turned into NES 2.0.
+
+use std::fmt;
+
+use super::cartridge::{
+    CHR_BANK_SIZE, INES_HEADER_SIZE, INES_MAGIC, PRG_BANK_SIZE,
+};
+use super::hash;
+use super::KB;
+
+pub const UNIF_MAGIC: [u8; 4] = *b"UNIF";
+pub const UNIF_HEADER_SIZE: usize = 32;
+const CHUNK_HEADER_SIZE: usize = 8;
+
+/// Prefixes naming who made the board, the rest of the name is the board.
+const BOARD_PREFIXES: [&str; 6] =
+    ["NES-", "HVC-", "UNL-", "BTL-", "BMC-", "IREM-"];
+
+/// Board name, mapper, submapper and the work RAM the board carries in KB.
+/// Only boards for emulated mappers are listed.
+#[rustfmt::skip]
+pub const BOARDS: &[(&str, u16, u8, u32)] = &[
+    ("NROM", 0, 0, 0), ("NROM-128", 0, 0, 0), ("NROM-256", 0, 0, 0),
+    ("RROM", 0, 0, 0), ("RROM-128", 0, 0, 0), ("HROM", 0, 0, 0),
+    ("SAROM", 1, 0, 8), ("SBROM", 1, 0, 0), ("SCROM", 1, 0, 0),
+    ("SC1ROM", 1, 0, 0), ("SEROM", 1, 5, 0), ("SFROM", 1, 0, 0),
+    ("SGROM", 1, 0, 0), ("SHROM", 1, 5, 0), ("SH1ROM", 1, 5, 0),
+    ("SJROM", 1, 0, 8), ("SKROM", 1, 0, 8), ("SLROM", 1, 0, 0),
+    ("SL1ROM", 1, 0, 0), ("SL2ROM", 1, 0, 0), ("SL3ROM", 1, 0, 0),
+    ("SLRROM", 1, 0, 0), ("SNROM", 1, 0, 8), ("SOROM", 1, 0, 16),
+    ("SUROM", 1, 0, 8), ("SXROM", 1, 0, 32),
+    ("UNROM", 2, 0, 0), ("UOROM", 2, 0, 0),
+    ("CNROM", 3, 0, 0),
+    ("TBROM", 4, 0, 0), ("TEROM", 4, 0, 0), ("TFROM", 4, 0, 0),
+    ("TGROM", 4, 0, 0), ("TKROM", 4, 0, 8), ("TLROM", 4, 0, 0),
+    ("TL1ROM", 4, 0, 0), ("TL2ROM", 4, 0, 0), ("TNROM", 4, 0, 8),
+    ("TR1ROM", 4, 0, 0), ("TSROM", 4, 0, 8), ("TVROM", 4, 0, 0),
+    ("B4", 4, 0, 0), ("HKROM", 4, 1, 1),
+    ("EKROM", 5, 0, 8), ("ELROM", 5, 0, 0), ("ETROM", 5, 0, 16),
+    ("EWROM", 5, 0, 32),
+    ("AMROM", 7, 0, 0), ("ANROM", 7, 0, 0), ("AN1ROM", 7, 0, 0),
+    ("AOROM", 7, 0, 0),
+    ("PNROM", 9, 0, 0), ("PEEOROM", 9, 0, 0),
+    ("FJROM", 10, 0, 8), ("FKROM", 10, 0, 8),
+    ("UNROM-512-8", 30, 0, 0), ("UNROM-512-16", 30, 0, 0),
+    ("UNROM-512-32", 30, 0, 0),
+    ("GNROM", 66, 0, 0), ("MHROM", 66, 0, 0),
+    ("JLROM", 69, 0, 0), ("JSROM", 69, 0, 8), ("BTR", 69, 0, 8),
+];
+
+/// Reasons a UNIF image cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UnifError {
+    /// The image does not start with "UNIF".
+    BadMagic,
+    /// A chunk runs past the end of the image.
+    Truncated,
+    /// There is no MAPR chunk naming the board.
+    NoBoard,
+    /// The board is not in [`BOARDS`].
+    UnknownBoard(String),
+    /// A PRGn/CHRn chunk does not match the CRC-32 in its PCKn/CCKn chunk.
+    Checksum {
+        chunk: String,
+        expected: u32,
+        found: u32,
+    },
+}
+impl fmt::Display for UnifError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => write!(f, "not a UNIF image (bad magic bytes)"),
+            Self::Truncated => write!(f, "UNIF image is truncated"),
+            Self::NoBoard => write!(f, "UNIF image does not name its board"),
+            Self::UnknownBoard(board) => {
+                write!(f, "UNIF board {board} is not supported")
+            }
+            Self::Checksum {
+                chunk,
+                expected,
+                found,
+            } => write!(
+                f,
+                "UNIF chunk {chunk} is damaged: expected CRC32 \
+                 {expected:08X}, found {found:08X}"
+            ),
+        }
+    }
+}
+impl std::error::Error for UnifError {}
+
+/// The chunks of a UNIF image the emulator uses.
+///
+/// ```no_run
+/// Header (32 bytes)
+/// 0-3   "UNIF"
+/// 4-7   revision (little endian)
+/// 8-31  padding
+/// Chunks: ID (4 bytes), length (4 bytes, little endian), data
+/// MAPR  board name, zero terminated
+/// PRGn  PRG ROM part n (hex digit 0-F), joined in order
+/// CHRn  CHR ROM part n, CHR RAM when there are none
+/// PCKn  CRC-32 of PRGn, CCKn CRC-32 of CHRn
+/// MIRR  0 horizontal, 1 vertical, 2/3 one-screen, 4 four screen,
+///       5 mapper controlled
+/// BATR  present when the work RAM has a battery
+/// TVCI  0 NTSC, 1 PAL, 2 both
+/// NAME  title, zero terminated
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UnifImage {
+    pub board: String,
+    pub name: Option<String>,
+    pub prg: Vec<u8>,
+    pub chr: Vec<u8>,
+    pub mirroring: u8,
+    pub battery: bool,
+    pub tv: Option<u8>,
+}
+impl UnifImage {
+    pub fn parse(image: &[u8]) -> Result<Self, UnifError> {
+        if !is_unif(image) {
+            return Err(UnifError::BadMagic);
+        }
+        let mut unif = Self::default();
+        let mut prg: [Option<&[u8]>; 16] = [None; 16];
+        let mut chr: [Option<&[u8]>; 16] = [None; 16];
+        let mut prg_crc: [Option<u32>; 16] = [None; 16];
+        let mut chr_crc: [Option<u32>; 16] = [None; 16];
+
+        let mut pos: usize = UNIF_HEADER_SIZE;
+        while pos < image.len() {
+            let header: &[u8] = image
+                .get(pos..pos + CHUNK_HEADER_SIZE)
+                .ok_or(UnifError::Truncated)?;
+            let id: &[u8] = &header[..4];
+            let len: usize = u32::from_le_bytes([
+                header[4], header[5], header[6], header[7],
+            ]) as usize;
+            pos += CHUNK_HEADER_SIZE;
+            let data: &[u8] = image
+                .get(pos..pos.saturating_add(len))
+                .ok_or(UnifError::Truncated)?;
+            pos += len;
+
+            // PRGn/CHRn/PCKn/CCKn end in a hex digit
+            let part: Option<usize> =
+                (id[3] as char).to_digit(16).map(|n| n as usize);
+            let crc = || -> Option<u32> {
+                data.get(..4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            };
+            match (&id[..3], part) {
+                (b"PRG", Some(n)) => prg[n] = Some(data),
+                (b"CHR", Some(n)) => chr[n] = Some(data),
+                (b"PCK", Some(n)) => prg_crc[n] = crc(),
+                (b"CCK", Some(n)) => chr_crc[n] = crc(),
+                _ => match id {
+                    b"MAPR" => unif.board = text(data),
+                    b"NAME" => unif.name = Some(text(data)),
+                    b"MIRR" => {
+                        unif.mirroring = data.first().copied().unwrap_or(0)
+                    }
+                    b"BATR" => unif.battery = true,
+                    b"TVCI" => unif.tv = data.first().copied(),
+                    _ => {}
+                },
+            }
+        }
+
+        for (kind, parts, crcs, out) in [
+            ("PRG", &prg, &prg_crc, &mut unif.prg),
+            ("CHR", &chr, &chr_crc, &mut unif.chr),
+        ] {
+            for (n, data) in parts.iter().enumerate() {
+                let Some(data) = data else { continue };
+                if let Some(expected) = crcs[n] {
+                    let found: u32 = hash::crc32(data);
+                    if found != expected {
+                        return Err(UnifError::Checksum {
+                            chunk: format!("{kind}{n:X}"),
+                            expected,
+                            found,
+                        });
+                    }
+                }
+                out.extend_from_slice(data);
+            }
+        }
+        if unif.board.is_empty() {
+            return Err(UnifError::NoBoard);
+        }
+        Ok(unif)
+    }
+
+    /// Mapper, submapper and work RAM size in bytes of the board.
+    pub fn mapper(&self) -> Result<(u16, u8, usize), UnifError> {
+        board_mapper(&self.board)
+            .ok_or_else(|| UnifError::UnknownBoard(self.board.clone()))
+    }
+
+    /// The same cartridge as an NES 2.0 image. PRG and CHR are repeated up
+    /// to whole 16KB/8KB banks, the way the board mirrors smaller chips.
+    pub fn to_nes2(&self) -> Result<Vec<u8>, UnifError> {
+        let (mapper, submapper, work_ram) = self.mapper()?;
+        let prg: Vec<u8> = fill_banks(&self.prg, PRG_BANK_SIZE);
+        let chr: Vec<u8> = fill_banks(&self.chr, CHR_BANK_SIZE);
+        let prg_banks: usize = prg.len() / PRG_BANK_SIZE;
+        let chr_banks: usize = chr.len() / CHR_BANK_SIZE;
+
+        let mut flags6: u8 = ((mapper & 0x0F) as u8) << 4;
+        flags6 |= match (mapper, self.mirroring) {
+            (_, 1) => 0x01,
+            // UNROM 512 marks its mapper switched one-screen mirroring with
+            // the four screen bit alone, real four screen with both
+            (30, 2 | 3 | 5) => 0x08,
+            (30, 4) => 0x09,
+            (_, 4) => 0x08,
+            _ => 0x00,
+        };
+        let work_ram: usize = match (self.battery, work_ram) {
+            // UNROM 512's battery bit is about its flash, not work RAM
+            (true, 0) if mapper != 30 => KB(8),
+            (_, size) => size,
+        };
+        let ram_shift: u8 = shift_count(work_ram);
+        let chr_ram: usize = match (chr_banks, mapper) {
+            (0, 30) => KB(32),
+            (0, _) => CHR_BANK_SIZE,
+            _ => 0,
+        };
+        if self.battery {
+            flags6 |= 0x02;
+        }
+
+        let mut image: Vec<u8> = INES_MAGIC.to_vec();
+        image.extend_from_slice(&[
+            (prg_banks & 0xFF) as u8,
+            (chr_banks & 0xFF) as u8,
+            flags6,
+            (mapper & 0xF0) as u8 | 0x08, // NES 2.0 identifier
+            ((submapper & 0x0F) << 4) | ((mapper >> 8) & 0x0F) as u8,
+            (((chr_banks >> 8) & 0x0F) << 4) as u8
+                | ((prg_banks >> 8) & 0x0F) as u8,
+            match self.battery {
+                true => ram_shift << 4,
+                false => ram_shift,
+            },
+            shift_count(chr_ram),
+            match self.tv {
+                Some(1) => 1,
+                Some(2) => 2,
+                _ => 0,
+            },
+        ]);
+        image.resize(INES_HEADER_SIZE, 0x00);
+        image.extend_from_slice(&prg);
+        image.extend_from_slice(&chr);
+        Ok(image)
+    }
+}
+
+/// Whether an image is a UNIF image.
+pub fn is_unif(image: &[u8]) -> bool {
+    image.len() >= UNIF_HEADER_SIZE && image.starts_with(&UNIF_MAGIC)
+}
+
+/// Looks a board name up in [`BOARDS`], with or without its "NES-" style
+/// prefix. Returns the mapper, submapper and work RAM in bytes.
+pub fn board_mapper(board: &str) -> Option<(u16, u8, usize)> {
+    let name: &str = BOARD_PREFIXES
+        .iter()
+        .find_map(|prefix| board.strip_prefix(prefix))
+        .unwrap_or(board);
+    BOARDS
+        .iter()
+        .find(|(known, ..)| known.eq_ignore_ascii_case(name))
+        .map(|(_, mapper, submapper, ram)| (*mapper, *submapper, KB(*ram)))
+}
+
+/// A zero terminated string chunk.
+fn text(data: &[u8]) -> String {
+    let end: usize = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+/// Repeats `data` until it fills a whole number of `bank` sized banks.
+fn fill_banks(data: &[u8], bank: usize) -> Vec<u8> {
+    let size: usize = data.len().div_ceil(bank) * bank;
+    data.iter().copied().cycle().take(size).collect()
+}
+
+/// NES 2.0 RAM size byte nibble, the size is `64 << n` bytes.
+fn shift_count(size: usize) -> u8 {
+    match size {
+        0 => 0,
+        size => (size / 64).max(1).ilog2() as u8,
+    }
+}
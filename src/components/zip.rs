@@ -0,0 +1,209 @@
+//! Just enough of the zip format to pull ROMs out of an archive: the central
+//! directory, stored and deflated entries. No ZIP64, no encryption.
+
+use std::fmt;
+
+use super::hash;
+
+const LOCAL_HEADER_MAGIC: u32 = 0x04034B50;
+const CENTRAL_HEADER_MAGIC: u32 = 0x02014B50;
+const END_MAGIC: u32 = 0x06054B50;
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const END_SIZE: usize = 22;
+/// The end of central directory record may be followed by a comment of up
+/// to 64KB.
+const MAX_COMMENT: usize = 0xFFFF;
+
+// compression methods
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+// general purpose flags
+const FLAG_ENCRYPTED: u16 = 0x0001;
+
+/// Reasons an archive or one of its entries cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZipError {
+    /// No end of central directory record, not a zip archive.
+    NotAZip,
+    /// A record points past the end of the archive.
+    Truncated,
+    /// The entry is compressed with a method other than stored or deflate.
+    UnsupportedMethod(u16),
+    /// The entry is encrypted.
+    Encrypted,
+    /// The deflate stream does not decompress to the entry's size.
+    Corrupt,
+    /// The entry's data does not match the CRC-32 in the directory.
+    Checksum { expected: u32, found: u32 },
+}
+impl fmt::Display for ZipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAZip => write!(f, "not a zip archive"),
+            Self::Truncated => write!(f, "zip archive is truncated"),
+            Self::UnsupportedMethod(method) => {
+                write!(f, "zip compression method {method} is not supported")
+            }
+            Self::Encrypted => {
+                write!(f, "encrypted zip entries are not supported")
+            }
+            Self::Corrupt => write!(f, "zip entry does not decompress"),
+            Self::Checksum { expected, found } => write!(
+                f,
+                "zip entry is damaged: expected CRC32 {expected:08X}, found \
+                 {found:08X}"
+            ),
+        }
+    }
+}
+impl std::error::Error for ZipError {}
+
+/// A file in the archive, as listed by the central directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZipEntry {
+    pub name: String,
+    pub size: usize, // uncompressed
+    pub crc32: u32,
+    method: u16,
+    flags: u16,
+    compressed_size: usize,
+    local_header: usize, // offset of the local file header
+}
+impl ZipEntry {
+    /// Directories are listed as entries with a trailing slash.
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    /// The name without the directories leading to it.
+    pub fn file_name(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+}
+
+/// An in memory zip archive.
+///
+/// ```no_run
+/// Local file header (30 bytes) + name + extra field + data, per entry
+/// ...
+/// Central directory header (46 bytes) + name + extra + comment, per entry
+///   8   flags, 10 method, 16 CRC-32, 20 compressed size, 24 size,
+///   28  name length, 30 extra length, 32 comment length,
+///   42  local header offset
+/// End of central directory (22 bytes) + comment
+///   10  number of entries, 12 directory size, 16 directory offset
+/// ```
+#[derive(Debug, Clone)]
+pub struct ZipArchive<'a> {
+    data: &'a [u8],
+    entries: Vec<ZipEntry>,
+}
+impl<'a> ZipArchive<'a> {
+    pub fn new(data: &'a [u8]) -> Result<Self, ZipError> {
+        let lowest: usize = data.len().saturating_sub(END_SIZE + MAX_COMMENT);
+        let end: usize = (lowest..=data.len().saturating_sub(END_SIZE))
+            .rev()
+            .find(|pos| u32_at(data, *pos) == Some(END_MAGIC))
+            .ok_or(ZipError::NotAZip)?;
+        let count: usize = u16_at(data, end + 10).ok_or(ZipError::Truncated)?;
+        let mut pos: usize =
+            u32_at(data, end + 16).ok_or(ZipError::Truncated)? as usize;
+
+        let mut entries: Vec<ZipEntry> = Vec::with_capacity(count);
+        for _ in 0..count {
+            if u32_at(data, pos) != Some(CENTRAL_HEADER_MAGIC) {
+                return Err(ZipError::Truncated);
+            }
+            let field = |offset: usize| {
+                u16_at(data, pos + offset).ok_or(ZipError::Truncated)
+            };
+            let long = |offset: usize| {
+                u32_at(data, pos + offset)
+                    .map(|n| n as usize)
+                    .ok_or(ZipError::Truncated)
+            };
+            let name_len: usize = field(28)?;
+            let name: &[u8] = data
+                .get(
+                    pos + CENTRAL_HEADER_SIZE
+                        ..pos + CENTRAL_HEADER_SIZE + name_len,
+                )
+                .ok_or(ZipError::Truncated)?;
+            entries.push(ZipEntry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                size: long(24)?,
+                crc32: long(16)? as u32,
+                method: field(10)? as u16,
+                flags: field(8)? as u16,
+                compressed_size: long(20)?,
+                local_header: long(42)?,
+            });
+            pos += CENTRAL_HEADER_SIZE + name_len + field(30)? + field(32)?;
+        }
+        Ok(Self { data, entries })
+    }
+
+    pub fn entries(&self) -> &[ZipEntry] {
+        &self.entries
+    }
+
+    /// Decompresses an entry and checks its CRC-32.
+    pub fn read(&self, entry: &ZipEntry) -> Result<Vec<u8>, ZipError> {
+        if entry.flags & FLAG_ENCRYPTED != 0 {
+            return Err(ZipError::Encrypted);
+        }
+        let pos: usize = entry.local_header;
+        if u32_at(self.data, pos) != Some(LOCAL_HEADER_MAGIC) {
+            return Err(ZipError::Truncated);
+        }
+        // the local header repeats the name but may carry its own extra field
+        let start: usize = pos
+            + LOCAL_HEADER_SIZE
+            + u16_at(self.data, pos + 26).ok_or(ZipError::Truncated)?
+            + u16_at(self.data, pos + 28).ok_or(ZipError::Truncated)?;
+        let compressed: &[u8] = self
+            .data
+            .get(start..start + entry.compressed_size)
+            .ok_or(ZipError::Truncated)?;
+
+        let contents: Vec<u8> = match entry.method {
+            METHOD_STORED => compressed.to_vec(),
+            METHOD_DEFLATED => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(
+                    compressed, entry.size,
+                )
+                .map_err(|_| ZipError::Corrupt)?
+            }
+            method => return Err(ZipError::UnsupportedMethod(method)),
+        };
+        if contents.len() != entry.size {
+            return Err(ZipError::Corrupt);
+        }
+        match hash::crc32(&contents) {
+            found if found == entry.crc32 => Ok(contents),
+            found => Err(ZipError::Checksum {
+                expected: entry.crc32,
+                found,
+            }),
+        }
+    }
+}
+
+/// Whether `data` starts like a zip archive (a local file header, or the
+/// end record of an empty archive).
+pub fn is_zip(data: &[u8]) -> bool {
+    matches!(u32_at(data, 0), Some(LOCAL_HEADER_MAGIC | END_MAGIC))
+}
+
+#[inline]
+fn u16_at(data: &[u8], pos: usize) -> Option<usize> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+#[inline]
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
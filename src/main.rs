@@ -3,6 +3,13 @@ mod components;
 #[cfg(test)]
 mod tests;
 
+use anyhow::Context;
+use components::{
+    bus::Bus,
+    cartridge::{Cartrige, LoadOptions},
+    dh_cpu::CPU,
+};
+
 #[macro_export]
 macro_rules! bs {
     ($($x:expr),*) => (
@@ -12,4 +19,35 @@ macro_rules! bs {
     );
 }
 
-fn main() {}
+fn main() -> anyhow::Result<()> {
+    const USAGE: &str = "usage: nes_emulator <path-to-rom> [--bios <path>] \
+                         [--patch <path>] [--entry <name-in-zip>]";
+    let mut args = std::env::args().skip(1);
+    let mut rom_path: Option<String> = None;
+    let mut options: LoadOptions = LoadOptions::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bios" => options.bios = Some(args.next().context(USAGE)?.into()),
+            "--patch" => {
+                options.patch = Some(args.next().context(USAGE)?.into())
+            }
+            "--entry" => options.entry = Some(args.next().context(USAGE)?),
+            _ => rom_path = Some(arg),
+        }
+    }
+    let rom_path: String = rom_path.context(USAGE)?;
+
+    let cart: Cartrige = Cartrige::open(&rom_path, &options)
+        .with_context(|| format!("failed to load {rom_path}"))?;
+
+    println!("{rom_path}: {}", cart.rom_info());
+
+    let mut bus: Bus = Bus::new();
+    let mut cpu: CPU = CPU::new();
+    bus.insert_cartridge(cart);
+    bus.reset(&mut cpu);
+    println!("reset vector: ${:04X}", cpu.pc());
+
+    bus.shutdown().context("failed to save the game")?;
+    Ok(())
+}
@@ -4,16 +4,26 @@ use crate::{
     components::{
         self,
         bus::Bus,
+        cartridge::{
+            self, CartridgeError, Cartrige, ConsoleType, HeaderFormat,
+            LoadOptions, Mirroring, PpuAccess, TimingRegion,
+        },
         dh_cpu::CPU,
+        hash,
+        mappers::opll::{Opll, EXP_TABLE, LOGSIN_TABLE, SAMPLE_RATE},
+        patch::{self, PatchError},
+        romdb::{RomDatabase, RomDbError, RomHash},
         types::{CpuFlags, CpuInstruction, M6502AddrModes, M6502Opcodes},
+        unif::{self, UnifError, UnifImage},
+        zip::{self, ZipArchive, ZipError},
     },
 };
 
 #[test]
 fn test_clock() {
     let mut cpu: CPU = CPU::new();
-    let mut bus: Bus = Bus::new();
-    CPU::reset(&mut cpu, &bus);
+    let mut bus: Bus = Bus::new_flat();
+    CPU::reset(&mut cpu, &mut bus);
     for _ in 0..8 {
         CPU::clock(&mut cpu, &mut bus);
     }
@@ -23,9 +33,9 @@ fn test_clock() {
 #[test]
 fn test_LDA() {
     let mut cpu = CPU::new();
-    let mut bus = Bus::new();
+    let mut bus = Bus::new_flat();
 
-    CPU::reset(&mut cpu, &bus);
+    CPU::reset(&mut cpu, &mut bus);
     cpu.set_cycles(0);
 
     cpu.set_pc(0xFFFC);
@@ -45,12 +55,12 @@ fn test_new() {
 #[test]
 fn test_disassemble() {
     let mut cpu = CPU::new();
-    let mut bus = Bus::new();
+    let mut bus = Bus::new_flat();
 
     const START: u16 = 0x0000;
     const STOP: u16 = 0x000f;
 
-    CPU::reset(&mut cpu, &bus);
+    CPU::reset(&mut cpu, &mut bus);
     cpu.set_cycles(0);
 
     for i in START..STOP {
@@ -78,7 +88,7 @@ fn test_disassemble() {
 #[test]
 fn test_mini_program() {
     let mut cpu = CPU::new();
-    let mut bus = Bus::new();
+    let mut bus = Bus::new_flat();
     const START: u16 = 0xC000;
     const STOP: u16 = 0xC00E;
 
@@ -101,7 +111,7 @@ fn test_mini_program() {
     ];
 
     // is there a better way to do this?
-    CPU::reset(&mut cpu, &bus);
+    CPU::reset(&mut cpu, &mut bus);
     bus.load_instruction_mem(ttape);
     cpu.set_cycles(0);
 
@@ -116,3 +126,3422 @@ fn test_gex_fmt() {
     let string_rep: String = format!("#${:x} {{imm}}", 100u8 as u32);
     dbg!(string_rep);
 }
+
+/// Builds an iNES image in memory with the PRG banks filled with their bank
+/// index and the CHR banks filled with `0x80 | index`.
+fn ines_image(prg_banks: u8, chr_banks: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+    let mut image: Vec<u8> =
+        vec![0x4E, 0x45, 0x53, 0x1A, prg_banks, chr_banks, flags6, flags7];
+    image.resize(cartridge::INES_HEADER_SIZE, 0);
+    if flags6 & 0x04 != 0 {
+        image.extend(std::iter::repeat_n(0xEE, cartridge::TRAINER_SIZE));
+    }
+    for bank in 0..prg_banks {
+        image.extend(std::iter::repeat_n(bank, cartridge::PRG_BANK_SIZE));
+    }
+    for bank in 0..chr_banks {
+        image
+            .extend(std::iter::repeat_n(0x80 | bank, cartridge::CHR_BANK_SIZE));
+    }
+    image
+}
+
+#[test]
+fn test_ines_header() {
+    // mapper 0, vertical mirroring, battery, trainer
+    let image = ines_image(2, 1, 0x07, 0x00);
+    let cart = Cartrige::from_bytes(&image).unwrap();
+
+    assert!(cart.image_valid);
+    assert_eq!(cart.mapper_id(), 0x00);
+    assert_eq!(cart.mirror, Mirroring::VERTICAL);
+    assert!(cart.battery());
+    assert_eq!(cart.trainer().map(|t| t.len()), Some(512));
+    assert_eq!(cart.prg_mem().len(), components::KB(32));
+    assert_eq!(cart.prg_mem()[components::KB(16)], 1);
+    assert_eq!(cart.chr_mem().len(), components::KB(8));
+    assert_eq!(cart.chr_mem()[0], 0x80);
+
+    // mapper number is split across flags 6 and 7
+    assert!(matches!(
+        Cartrige::from_bytes(&ines_image(1, 1, 0x20, 0x10)),
+        Err(CartridgeError::UnsupportedMapper(0x12))
+    ));
+}
+
+#[test]
+fn test_ines_chr_ram_and_dirty_header() {
+    let mut image = ines_image(1, 0, 0x08, 0x00);
+    image[7..16].copy_from_slice(b"DiskDude!");
+    let cart = Cartrige::from_bytes(&image).unwrap();
+
+    // everything past byte 6 is ignored when the padding is garbage
+    assert_eq!(cart.mapper_id(), 0x00);
+    assert_eq!(cart.mirror, Mirroring::FOUR_SCREEN);
+    assert_eq!(cart.chr_mem().len(), components::KB(8));
+    assert_eq!(cart.prg_ram_size(), components::KB(8));
+    assert_eq!(cart.timing(), TimingRegion::NTSC);
+}
+
+#[test]
+fn test_ines_bad_images() {
+    assert!(matches!(
+        Cartrige::from_bytes(&[0x4E, 0x45, 0x53]),
+        Err(CartridgeError::MissingHeader)
+    ));
+
+    let mut image = ines_image(1, 1, 0, 0);
+    image[3] = 0x00;
+    assert!(matches!(
+        Cartrige::from_bytes(&image),
+        Err(CartridgeError::BadMagic(_))
+    ));
+
+    let image = ines_image(2, 1, 0, 0);
+    assert!(matches!(
+        Cartrige::from_bytes(&image[..image.len() - 1]),
+        Err(CartridgeError::Truncated { .. })
+    ));
+}
+
+#[test]
+fn test_nes2_header() {
+    let mut image = ines_image(2, 0, 0x02, 0x08 | 0x01);
+    image[8] = 0x30; // submapper 3
+    image[10] = 0x70; // 8KB PRG NVRAM, no volatile PRG RAM
+    image[11] = 0x07; // 8KB CHR RAM
+    image[12] = 0x03; // Dendy
+    image[13] = 0x21; // Vs. hardware 2, PPU 1
+    image[15] = 0x01; // standard controllers
+    let cart = Cartrige::from_bytes(&image).unwrap();
+
+    assert_eq!(cart.format(), HeaderFormat::NES2);
+    assert_eq!(cart.mapper_id(), 0x000);
+    assert_eq!(cart.submapper(), 3);
+    assert_eq!(cart.prg_ram_size(), 0);
+    assert_eq!(cart.prg_nvram_size(), components::KB(8));
+    assert_eq!(cart.chr_ram_size(), components::KB(8));
+    assert_eq!(cart.chr_nvram_size(), 0);
+    assert_eq!(cart.chr_mem().len(), components::KB(8));
+    assert_eq!(cart.timing(), TimingRegion::DENDY);
+    assert_eq!(
+        cart.console(),
+        ConsoleType::VS_SYSTEM {
+            ppu_type: 1,
+            hardware_type: 2
+        }
+    );
+    assert_eq!(cart.expansion_device(), 1);
+
+    // 12-bit mapper numbers
+    image[6] = 0x10;
+    image[7] = 0x48;
+    image[8] = 0x01;
+    assert!(matches!(
+        Cartrige::from_bytes(&image),
+        Err(CartridgeError::UnsupportedMapper(0x141))
+    ));
+}
+
+#[test]
+fn test_nes2_rom_size_msb() {
+    // PRG size MSB nibble of 1 turns 0 banks into 256 banks
+    let mut image = ines_image(0, 1, 0x00, 0x08);
+    image[9] = 0x01;
+    let prg: usize = 256 * cartridge::PRG_BANK_SIZE;
+    image.splice(16..16, std::iter::repeat_n(0, prg));
+    let cart = Cartrige::from_bytes(&image).unwrap();
+    assert_eq!(cart.prg_banks(), 256);
+    assert_eq!(cart.chr_mem()[0], 0x80);
+
+    // exponent-multiplier notation: 2^14 * 3 bytes of PRG
+    let mut image = ines_image(0, 0, 0x00, 0x08);
+    image[4] = (14 << 2) | 0x01;
+    image[9] = 0x0F;
+    image.extend(std::iter::repeat_n(0, 3 * components::KB(16)));
+    let cart = Cartrige::from_bytes(&image).unwrap();
+    assert_eq!(cart.prg_mem().len(), 3 * components::KB(16));
+}
+
+#[test]
+fn test_bus_ram_mirroring() {
+    let mut bus = Bus::new();
+    bus.write(0x0001, 0x42);
+    assert_eq!(bus.read(0x0801, false), 0x42);
+    assert_eq!(bus.read(0x1001, false), 0x42);
+    bus.write(0x1FFF, 0x24);
+    assert_eq!(bus.read(0x07FF, false), 0x24);
+
+    // nothing is mapped in cartridge space without a cartridge
+    bus.write(0x8000, 0x99);
+    assert_eq!(bus.read(0x8000, false), 0x00);
+}
+
+#[test]
+fn test_bus_ppu_registers() {
+    let mut bus = Bus::new();
+
+    // $3F06 through the mirrored register at $3FFE/$3FFF
+    bus.write(0x3FFE, 0x3F);
+    bus.write(0x2006, 0x00);
+    bus.write(0x200F, 0x11);
+    bus.write(0x2007, 0x22);
+    assert_eq!(bus.ppu.vram_addr(), 0x3F02);
+
+    bus.write(0x2006, 0x3F);
+    bus.write(0x2006, 0x00);
+    // palette reads are not buffered
+    assert_eq!(bus.read(0x2007, false), 0x11);
+    assert_eq!(bus.read(0x2007, false), 0x22);
+
+    // nametable reads are delayed by one read
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0x5A);
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x00);
+    bus.read(0x2007, false);
+    assert_eq!(bus.read(0x2007, false), 0x5A);
+}
+
+#[test]
+fn test_bus_oam_dma() {
+    let mut bus = Bus::new();
+    for i in 0..=0xFFu16 {
+        bus.write(0x0200 + i, i as u8);
+    }
+    bus.write(0x2003, 0x00);
+    bus.write(0x4014, 0x02);
+    bus.write(0x2003, 0x10);
+    assert_eq!(bus.read(0x2004, false), 0x10);
+}
+
+#[test]
+fn test_nrom_reset_vector() {
+    // 16KB NROM: $C000-$FFFF mirrors $8000-$BFFF
+    let mut image = ines_image(1, 1, 0x00, 0x00);
+    let vector: usize = cartridge::INES_HEADER_SIZE + 0x3FFC;
+    image[vector] = 0x34;
+    image[vector + 1] = 0x82;
+
+    let mut cpu = CPU::new();
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    bus.reset(&mut cpu);
+
+    assert_eq!(cpu.pc(), 0x8234);
+    assert_eq!(bus.read(0xBFFD, false), 0x82);
+    assert_eq!(bus.read(0x8000, false), 0x00);
+}
+
+#[test]
+fn test_ppu_reads_chr_through_cartridge() {
+    let image = ines_image(1, 1, 0x01, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    bus.write(0x2006, 0x10);
+    bus.write(0x2006, 0x00);
+    bus.read(0x2007, false);
+    assert_eq!(bus.read(0x2007, false), 0x80);
+
+    // vertical mirroring: $2800 is the same table as $2000
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x05);
+    bus.write(0x2007, 0x77);
+    bus.write(0x2006, 0x28);
+    bus.write(0x2006, 0x05);
+    bus.read(0x2007, false);
+    assert_eq!(bus.read(0x2007, false), 0x77);
+}
+
+#[test]
+fn test_chr_ram_is_writable() {
+    let image = ines_image(1, 0, 0x00, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    bus.write(0x2006, 0x00);
+    bus.write(0x2006, 0x10);
+    bus.write(0x2007, 0xAB);
+    assert_eq!(bus.cartridge().unwrap().chr_mem()[0x10], 0xAB);
+}
+
+#[test]
+fn test_mapper_registry() {
+    use crate::components::mappers::{self, mapper::MapperData};
+
+    assert!(mappers::new_mapper(0, MapperData::new(1, 1)).is_some());
+    assert!(mappers::new_mapper(0xFFF, MapperData::new(1, 1)).is_none());
+}
+
+#[test]
+fn test_nrom_work_ram_and_rom_protection() {
+    let image = ines_image(2, 1, 0x00, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    bus.write(0x6000, 0x12);
+    bus.write(0x7FFF, 0x34);
+    assert_eq!(bus.read(0x6000, false), 0x12);
+    assert_eq!(bus.read(0x7FFF, false), 0x34);
+    assert_eq!(bus.cartridge().unwrap().prg_ram().len(), components::KB(8));
+
+    // PRG ROM ignores writes
+    bus.write(0xC000, 0xFF);
+    assert_eq!(bus.read(0xC000, false), 0x01);
+}
+
+/// Loads an MMC1 register through the serial port, clocking the cartridge
+/// between writes so they do not look like a read-modify-write.
+fn mmc1_write(bus: &mut Bus, addr: u16, value: u8) {
+    for bit in 0..5 {
+        bus.write(addr, (value >> bit) & 0x01);
+        bus.cartridge_mut().unwrap().cpu_clock();
+        bus.cartridge_mut().unwrap().cpu_clock();
+    }
+}
+
+#[test]
+fn test_mmc1_prg_banking() {
+    let image = ines_image(8, 2, 0x10, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    // power on: switch $8000, last bank fixed at $C000
+    assert_eq!(bus.read(0xC000, false), 7);
+    mmc1_write(&mut bus, 0xE000, 0x02);
+    assert_eq!(bus.read(0x8000, false), 2);
+    assert_eq!(bus.read(0xFFFF, false), 7);
+
+    // first bank fixed at $8000, switch $C000
+    mmc1_write(&mut bus, 0x8000, 0x08);
+    assert_eq!(bus.read(0x8000, false), 0);
+    assert_eq!(bus.read(0xC000, false), 2);
+
+    // 32KB mode ignores the low bit
+    mmc1_write(&mut bus, 0x8000, 0x00);
+    mmc1_write(&mut bus, 0xE000, 0x05);
+    assert_eq!(bus.read(0x8000, false), 4);
+    assert_eq!(bus.read(0xC000, false), 5);
+
+    // bit 7 resets the shift register and restores the fixed last bank
+    bus.write(0x8000, 0x01);
+    bus.cartridge_mut().unwrap().cpu_clock();
+    bus.cartridge_mut().unwrap().cpu_clock();
+    bus.write(0x8000, 0x80);
+    assert_eq!(bus.read(0xC000, false), 7);
+}
+
+#[test]
+fn test_mmc1_chr_mirroring_and_ram() {
+    let image = ines_image(2, 2, 0x10, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    // 4KB CHR mode, vertical mirroring
+    mmc1_write(&mut bus, 0x8000, 0x1E);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::VERTICAL);
+    mmc1_write(&mut bus, 0xA000, 0x03);
+    mmc1_write(&mut bus, 0xC000, 0x00);
+    let cart = bus.cartridge_mut().unwrap();
+    let mut data: u8 = 0;
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x81);
+    cart.ppu_read(0x1000, &mut data);
+    assert_eq!(data, 0x80);
+
+    mmc1_write(&mut bus, 0x8000, 0x01);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::ONESCREAN_HI);
+
+    // PRG RAM can be switched off through the PRG register
+    bus.write(0x6000, 0x55);
+    assert_eq!(bus.read(0x6000, false), 0x55);
+    mmc1_write(&mut bus, 0xE000, 0x10);
+    assert_eq!(bus.read(0x6000, false), 0x00);
+    mmc1_write(&mut bus, 0xE000, 0x00);
+    assert_eq!(bus.read(0x6000, false), 0x55);
+}
+
+#[test]
+fn test_mmc1_ignores_consecutive_writes() {
+    let image = ines_image(8, 2, 0x10, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    // the second write of each pair lands on the next cycle and is dropped
+    for bit in 0..5 {
+        bus.write(0xE000, (0x03 >> bit) & 0x01);
+        bus.cartridge_mut().unwrap().cpu_clock();
+        bus.write(0xE000, 0x00);
+        bus.cartridge_mut().unwrap().cpu_clock();
+        bus.cartridge_mut().unwrap().cpu_clock();
+    }
+    assert_eq!(bus.read(0x8000, false), 3);
+}
+
+#[test]
+fn test_mmc1_read_modify_write_reset() {
+    let mut image = ines_image(8, 2, 0x10, 0x00);
+    let bank7: usize =
+        cartridge::INES_HEADER_SIZE + 7 * cartridge::PRG_BANK_SIZE;
+    #[rustfmt::skip]
+    let program: [u8; 30] = [
+        0xA9, 0x01,       // LDA #$01
+        0x8D, 0x00, 0xE0, // STA $E000, a stray bit the reset must drop
+        0xEE, 0x00, 0xE0, // INC $E000, writes $FF then $00
+        0xA9, 0x03,       // LDA #$03
+        0x8D, 0x00, 0xE0, // STA $E000
+        0x4A,             // LSR A
+        0x8D, 0x00, 0xE0, // STA $E000
+        0x4A,             // LSR A
+        0x8D, 0x00, 0xE0, // STA $E000
+        0x8D, 0x00, 0xE0, // STA $E000
+        0x8D, 0x00, 0xE0, // STA $E000
+        0x4C, 0x1B, 0xC0, // JMP $C01B
+    ];
+    image[bank7..bank7 + program.len()].copy_from_slice(&program);
+    image[bank7 + 0x2000] = 0xFF;
+    image[bank7 + 0x3FFC..bank7 + 0x3FFE].copy_from_slice(&[0x00, 0xC0]);
+
+    let mut cpu = CPU::new();
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    CPU::reset(&mut cpu, &mut bus);
+    for _ in 0..3 * 100 {
+        bus.clock(&mut cpu);
+    }
+
+    // only the dummy write of the unmodified $FF reaches the shift register,
+    // the incremented value lands on the next cycle and is ignored
+    assert_eq!(cpu.pc(), 0xC01B);
+    assert_eq!(bus.read(0x8000, false), 3);
+}
+
+#[test]
+fn test_uxrom_banking_and_bus_conflicts() {
+    let image = ines_image(8, 0, 0x20, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    assert_eq!(bus.read(0x8000, false), 0);
+    assert_eq!(bus.read(0xFFFF, false), 7);
+    bus.write(0x8000, 0x03);
+    assert_eq!(bus.read(0xBFFF, false), 3);
+    assert_eq!(bus.read(0xC000, false), 7);
+
+    // submapper 2: the ROM byte under the write is ANDed in
+    let mut image = ines_image(8, 0, 0x20, 0x08);
+    image[8] = 0x20;
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    bus.write(0x8000, 0x06); // ROM reads 0x00 here
+    assert_eq!(bus.read(0x8000, false), 0);
+    bus.write(0xC000, 0x06); // ROM reads 0x07 here
+    assert_eq!(bus.read(0x8000, false), 6);
+}
+
+#[test]
+fn test_cnrom_chr_banking() {
+    let image = ines_image(2, 4, 0x30, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    let mut data: u8 = 0;
+    bus.write(0x8000, 0x02);
+    bus.cartridge_mut().unwrap().ppu_read(0x1FFF, &mut data);
+    assert_eq!(data, 0x82);
+    assert_eq!(bus.read(0xC000, false), 1);
+
+    // submapper 2: ROM byte 0x01 at $C000 masks the bank number
+    let mut image = ines_image(2, 4, 0x30, 0x08);
+    image[8] = 0x20;
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    bus.write(0xC000, 0x03);
+    bus.cartridge_mut().unwrap().ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x81);
+}
+
+/// Image for boards with finer banking: every 8KB PRG bank is filled with
+/// its own index and every 1KB CHR bank with `0x80 | index`, the last bank
+/// is a NOP sled that the reset vector points into.
+fn banked_image(prg_banks: u8, chr_banks: u8, flags6: u8) -> Vec<u8> {
+    let mut image = ines_image(prg_banks, chr_banks, flags6, 0x00);
+    let prg_start: usize = cartridge::INES_HEADER_SIZE;
+    let prg_len: usize = prg_banks as usize * cartridge::PRG_BANK_SIZE;
+    for (bank, chunk) in image[prg_start..prg_start + prg_len]
+        .chunks_mut(components::KB(8))
+        .enumerate()
+    {
+        chunk.fill(bank as u8);
+    }
+    for (bank, chunk) in image[prg_start + prg_len..]
+        .chunks_mut(components::KB(1))
+        .enumerate()
+    {
+        chunk.fill(0x80 | bank as u8);
+    }
+    let last: usize = prg_start + prg_len - components::KB(8);
+    image[last..prg_start + prg_len].fill(0xEA);
+    image[prg_start + prg_len - 4] = 0x00;
+    image[prg_start + prg_len - 3] = 0xE0;
+    image
+}
+
+#[test]
+fn test_mmc3_prg_and_chr_banking() {
+    let image = banked_image(4, 2, 0x40);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    // R6 = 2, R7 = 5 with PRG mode 0: R6, R7, second last, last
+    bus.write(0x8000, 0x06);
+    bus.write(0x8001, 0x02);
+    bus.write(0x8000, 0x07);
+    bus.write(0x8001, 0x05);
+    assert_eq!(bus.read(0x8000, false), 2);
+    assert_eq!(bus.read(0xA000, false), 5);
+    assert_eq!(bus.read(0xC000, false), 6);
+    assert_eq!(bus.read(0xFFF0, false), 0xEA);
+
+    // PRG mode 1 swaps R6 and the second last bank
+    bus.write(0x8000, 0x40);
+    assert_eq!(bus.read(0x8000, false), 6);
+    assert_eq!(bus.read(0xC000, false), 2);
+
+    // R0 = 4 (2KB, low bit ignored), R2 = 9 (1KB)
+    bus.write(0x8000, 0x00);
+    bus.write(0x8001, 0x05);
+    bus.write(0x8000, 0x02);
+    bus.write(0x8001, 0x09);
+    let mut data: u8 = 0;
+    let cart = bus.cartridge_mut().unwrap();
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x84);
+    cart.ppu_read(0x0400, &mut data);
+    assert_eq!(data, 0x85);
+    cart.ppu_read(0x1000, &mut data);
+    assert_eq!(data, 0x89);
+
+    // CHR inversion moves the 2KB banks to $1000
+    bus.write(0x8000, 0x80);
+    let cart = bus.cartridge_mut().unwrap();
+    cart.ppu_read(0x1400, &mut data);
+    assert_eq!(data, 0x85);
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x89);
+
+    // mirroring and PRG RAM write protection
+    bus.write(0xA000, 0x01);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::HORIZONTAL);
+    bus.write(0x6000, 0x42);
+    assert_eq!(bus.read(0x6000, false), 0x42);
+    bus.write(0xA001, 0xC0);
+    bus.write(0x6000, 0x24);
+    assert_eq!(bus.read(0x6000, false), 0x42);
+}
+
+#[test]
+fn test_mmc3_a12_filter() {
+    let image = banked_image(2, 1, 0x40);
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    let mut data: u8 = 0;
+    cart.cpu_write(0xC000, 0x01);
+    cart.cpu_write(0xC001, 0x00);
+    cart.cpu_write(0xE001, 0x00);
+
+    // A12 low for three cpu cycles, then high: reloads the counter with 1
+    for _ in 0..3 {
+        cart.cpu_clock();
+    }
+    cart.ppu_read(0x1000, &mut data);
+    assert!(!cart.irq_state());
+
+    // a rising edge right after A12 fell is filtered out
+    cart.ppu_read(0x0000, &mut data);
+    cart.ppu_read(0x1000, &mut data);
+    assert!(!cart.irq_state());
+
+    // a properly spaced edge decrements to 0 and raises the IRQ
+    cart.ppu_read(0x0000, &mut data);
+    for _ in 0..3 {
+        cart.cpu_clock();
+    }
+    cart.ppu_address(0x1000);
+    assert!(cart.irq_state());
+
+    // $E000 acknowledges and disables
+    cart.cpu_write(0xE000, 0x00);
+    assert!(!cart.irq_state());
+}
+
+#[test]
+fn test_mmc3_scanline_irq() {
+    let image = banked_image(2, 1, 0x40);
+    let mut bus = Bus::new();
+    let mut cpu = CPU::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    bus.reset(&mut cpu);
+
+    // IRQ after 4 scanlines, sprites at $1000, rendering on
+    bus.write(0xC000, 0x03);
+    bus.write(0xC001, 0x00);
+    bus.write(0xE001, 0x00);
+    bus.write(0x2000, 0x08);
+    bus.write(0x2001, 0x18);
+
+    while !cpu.irq_line() {
+        bus.clock(&mut cpu);
+        assert!(bus.ppu.scan_line() < 240, "IRQ never fired");
+    }
+    assert_eq!(bus.ppu.scan_line(), 3);
+
+    bus.write(0xE000, 0x00);
+    bus.clock(&mut cpu);
+    assert!(!cpu.irq_line());
+}
+
+#[test]
+fn test_axrom_banking_and_one_screen() {
+    let image = ines_image(8, 0, 0x70, 0x00);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::ONESCREAN_LO);
+
+    bus.write(0x8000, 0x12);
+    assert_eq!(bus.read(0x8000, false), 4);
+    assert_eq!(bus.read(0xFFFF, false), 5);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::ONESCREAN_HI);
+
+    // every nametable lands on the same 1KB
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x10);
+    bus.write(0x2007, 0x5A);
+    bus.write(0x2006, 0x2C);
+    bus.write(0x2006, 0x10);
+    bus.read(0x2007, false);
+    assert_eq!(bus.read(0x2007, false), 0x5A);
+}
+
+#[test]
+fn test_gxrom_and_color_dreams_banking() {
+    // GxROM: xxPP xxCC, $8000 of bank 0 reads 0xFF so the first write
+    // goes through untouched
+    let mut image = ines_image(8, 4, 0x20, 0x40);
+    image[cartridge::INES_HEADER_SIZE] = 0xFF;
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mapper_id(), 66);
+
+    let mut data: u8 = 0;
+    bus.write(0x8000, 0x13);
+    assert_eq!(bus.read(0x8000, false), 2);
+    assert_eq!(bus.read(0xC000, false), 3);
+    bus.cartridge_mut().unwrap().ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x83);
+
+    // bus conflict with the ROM byte 0x02 now at $8000
+    bus.write(0x8000, 0x12);
+    assert_eq!(bus.read(0xC000, false), 1);
+    bus.cartridge_mut().unwrap().ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x82);
+
+    // Color Dreams: CCCC xxPP
+    let mut image = ines_image(8, 4, 0xB0, 0x00);
+    image[cartridge::INES_HEADER_SIZE] = 0xFF;
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    bus.write(0x8000, 0x31);
+    assert_eq!(bus.read(0x8000, false), 2);
+    bus.cartridge_mut().unwrap().ppu_read(0x1FFF, &mut data);
+    assert_eq!(data, 0x83);
+}
+
+#[test]
+fn test_mmc5_prg_banking_ram_and_multiplier() {
+    let mut image = banked_image(8, 2, 0x50);
+    image[8] = 2; // 16KB of PRG RAM
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    // mode 3 at power on, $5117 = $FF
+    assert_eq!(bus.read(0xFFF0, false), 0xEA);
+    bus.write(0x5114, 0x83);
+    bus.write(0x5115, 0x85);
+    bus.write(0x5116, 0x86);
+    assert_eq!(bus.read(0x8000, false), 3);
+    assert_eq!(bus.read(0xA000, false), 5);
+    assert_eq!(bus.read(0xC000, false), 6);
+
+    // mode 0: 32KB from $5117
+    bus.write(0x5100, 0x00);
+    bus.write(0x5117, 0x06);
+    assert_eq!(bus.read(0x8000, false), 4);
+    assert_eq!(bus.read(0xE000, false), 7);
+
+    // mode 1: 16KB from $5115 and $5117
+    bus.write(0x5100, 0x01);
+    bus.write(0x5115, 0x8B);
+    assert_eq!(bus.read(0x8000, false), 10);
+    assert_eq!(bus.read(0xA000, false), 11);
+    assert_eq!(bus.read(0xC000, false), 6);
+
+    // mode 2: 16KB + 8KB + 8KB
+    bus.write(0x5100, 0x02);
+    bus.write(0x5116, 0x8D);
+    bus.write(0x5117, 0x0E);
+    assert_eq!(bus.read(0xC000, false), 13);
+    assert_eq!(bus.read(0xE000, false), 14);
+
+    // PRG RAM is write protected until $5102/$5103 hold $02/$01
+    bus.write(0x6000, 0x42);
+    assert_eq!(bus.read(0x6000, false), 0x00);
+    bus.write(0x5102, 0x02);
+    bus.write(0x5103, 0x01);
+    bus.write(0x6000, 0x42);
+    bus.write(0x5113, 0x01);
+    assert_eq!(bus.read(0x6000, false), 0x00);
+    bus.write(0x6000, 0x24);
+    bus.write(0x5113, 0x00);
+    assert_eq!(bus.read(0x6000, false), 0x42);
+
+    // RAM banks can be mapped into $8000-$DFFF too
+    bus.write(0x5100, 0x03);
+    bus.write(0x5114, 0x01);
+    assert_eq!(bus.read(0x8000, false), 0x24);
+
+    bus.write(0x5205, 200);
+    bus.write(0x5206, 100);
+    assert_eq!(bus.read(0x5205, false), 0x20);
+    assert_eq!(bus.read(0x5206, false), 0x4E);
+}
+
+#[test]
+fn test_mmc5_chr_banking() {
+    let image = banked_image(2, 2, 0x50);
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    let mut data: u8 = 0;
+
+    // 1KB mode, set A
+    cart.cpu_write(0x5101, 0x03);
+    for bank in 0..8u16 {
+        cart.cpu_write(0x5120 + bank, 8 + bank as u8);
+    }
+    for bank in 0..8u16 {
+        cart.ppu_read(bank * 0x0400, &mut data);
+        assert_eq!(data, 0x88 + bank as u8);
+    }
+
+    // writing set B makes it the active one, repeated over both halves
+    for bank in 0..4u16 {
+        cart.cpu_write(0x5128 + bank, 1 + bank as u8);
+    }
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x81);
+    cart.ppu_read(0x1C00, &mut data);
+    assert_eq!(data, 0x84);
+
+    // 2KB and 8KB modes
+    cart.cpu_write(0x5101, 0x02);
+    cart.cpu_write(0x5123, 0x03);
+    cart.ppu_read(0x0C00, &mut data);
+    assert_eq!(data, 0x87);
+    cart.cpu_write(0x5101, 0x00);
+    cart.cpu_write(0x5127, 0x01);
+    cart.ppu_read(0x0400, &mut data);
+    assert_eq!(data, 0x89);
+}
+
+#[test]
+fn test_mmc5_nametables_fill_and_exram() {
+    let image = banked_image(2, 2, 0x50);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    fn ppu_peek(bus: &mut Bus, addr: u16) -> u8 {
+        bus.write(0x2006, (addr >> 8) as u8);
+        bus.write(0x2006, addr as u8);
+        bus.read(0x2007, false);
+        bus.read(0x2007, false)
+    }
+
+    // ExRAM as plain ram
+    bus.write(0x5104, 0x02);
+    bus.write(0x5C10, 0x77);
+    assert_eq!(bus.read(0x5C10, false), 0x77);
+
+    // CIRAM page 0, CIRAM page 1, ExRAM, fill mode
+    bus.write(0x5104, 0x00);
+    bus.write(0x5105, 0xE4);
+    bus.write(0x5106, 0x33);
+    bus.write(0x5107, 0x02);
+    bus.write(0x2006, 0x20);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0x11);
+    bus.write(0x2006, 0x24);
+    bus.write(0x2006, 0x00);
+    bus.write(0x2007, 0x22);
+    assert_eq!(ppu_peek(&mut bus, 0x2000), 0x11);
+    assert_eq!(ppu_peek(&mut bus, 0x2400), 0x22);
+    assert_eq!(ppu_peek(&mut bus, 0x2810), 0x77);
+    assert_eq!(ppu_peek(&mut bus, 0x2C05), 0x33);
+    assert_eq!(ppu_peek(&mut bus, 0x2FC0), 0xAA);
+
+    // outside of rendering the cpu can only clear ExRAM in mode 0
+    bus.write(0x5C10, 0x55);
+    assert_eq!(ppu_peek(&mut bus, 0x2810), 0x00);
+
+    // with rendering enabled but outside the frame $2007 still sees CHR
+    bus.write(0x5101, 0x03);
+    bus.write(0x5120, 0x05);
+    bus.write(0x2001, 0x18);
+    assert_eq!(ppu_peek(&mut bus, 0x0000), 0x85);
+    assert_eq!(ppu_peek(&mut bus, 0x2000), 0x11);
+}
+
+#[test]
+fn test_mmc5_extended_attributes_and_split() {
+    let image = banked_image(2, 2, 0x50);
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    let mut data: u8 = 0;
+
+    cart.cpu_write(0x5104, 0x02);
+    cart.cpu_write(0x5C02, 0x07); // split tile at column 2
+    cart.cpu_write(0x5FC0, 0x04); // split palette 1 for columns 2-3
+    cart.cpu_write(0x5C06, 0x42); // palette 1, 4KB bank 2
+    cart.cpu_write(0x5104, 0x01);
+    cart.cpu_write(0x5200, 0x83); // split left of column 3
+    cart.cpu_write(0x5202, 0x01);
+    cart.ppu_register_write(0x2001, 0x18);
+
+    // the third identical nametable read starts the line and is the first
+    // background slot, column 2, inside the split: tile, palette and CHR
+    // bank all come from the split registers
+    cart.ppu_read(0x2005, &mut data);
+    cart.ppu_read(0x2005, &mut data);
+    cart.ppu_read(0x2005, &mut data);
+    assert_eq!(data, 0x07);
+    cart.ppu_read(0x23C1, &mut data);
+    assert_eq!(data, 0x55);
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x84);
+    cart.ppu_read(0x0008, &mut data);
+    assert_eq!(data, 0x84);
+
+    // column 3 uses the extended attributes
+    assert_eq!(cart.ppu_read(0x2006, &mut data), PpuAccess::CIRAM(6));
+    cart.ppu_read(0x23C1, &mut data);
+    assert_eq!(data, 0x55);
+    cart.ppu_read(0x0013, &mut data);
+    assert_eq!(data, 0x88);
+    cart.ppu_read(0x001B, &mut data);
+    assert_eq!(data, 0x88);
+
+    // and with extended attributes off, column 4 is a regular tile
+    cart.cpu_write(0x5104, 0x00);
+    assert_eq!(cart.ppu_read(0x2007, &mut data), PpuAccess::CIRAM(7));
+    cart.ppu_read(0x23C1, &mut data);
+    cart.ppu_read(0x0010, &mut data);
+    assert_eq!(data, 0x80);
+}
+
+#[test]
+fn test_mmc5_scanline_irq() {
+    // a 32KB NOP sled that keeps the cpu busy for two frames
+    let mut image = banked_image(8, 2, 0x50);
+    let prg_end: usize =
+        cartridge::INES_HEADER_SIZE + 8 * cartridge::PRG_BANK_SIZE;
+    image[cartridge::INES_HEADER_SIZE..prg_end].fill(0xEA);
+    image[prg_end - 4] = 0x00;
+    image[prg_end - 3] = 0x80;
+    let mut bus = Bus::new();
+    let mut cpu = CPU::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    bus.reset(&mut cpu);
+    bus.write(0x5100, 0x00);
+    bus.write(0x2001, 0x18);
+
+    // start from the bottom of the first frame
+    while bus.ppu.scan_line() != 250 {
+        bus.clock(&mut cpu);
+    }
+    assert_eq!(bus.read(0x5204, false) & 0x40, 0x00);
+
+    bus.write(0x5203, 0x05);
+    bus.write(0x5204, 0x80);
+    let mut ticks: u32 = 0;
+    while !cpu.irq_line() {
+        bus.clock(&mut cpu);
+        ticks += 1;
+        assert!(ticks < 341 * 262, "IRQ never fired");
+    }
+    assert_eq!(bus.ppu.scan_line(), 5);
+
+    // reading the status acknowledges, in-frame stays set
+    assert_eq!(bus.read(0x5204, false), 0xC0);
+    bus.clock(&mut cpu);
+    assert!(!cpu.irq_line());
+}
+
+#[test]
+fn test_mmc5_pulse_audio() {
+    let image = banked_image(2, 2, 0x50);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.audio_sample(), 0.0);
+
+    // pulse 1: 50% duty, constant volume 15
+    bus.write(0x5015, 0x01);
+    bus.write(0x5000, 0xBF);
+    bus.write(0x5002, 0x10);
+    bus.write(0x5003, 0x08);
+    assert_eq!(bus.read(0x5015, false), 0x01);
+
+    let mut peak: f32 = 0.0;
+    for _ in 0..256 {
+        bus.cartridge_mut().unwrap().cpu_clock();
+        peak = peak.max(bus.audio_sample());
+    }
+    assert!(peak > 0.1);
+
+    // disabling the channel clears its length counter
+    bus.write(0x5015, 0x00);
+    assert_eq!(bus.read(0x5015, false), 0x00);
+    assert_eq!(bus.audio_sample(), 0.0);
+}
+
+#[test]
+fn test_mmc2_prg_and_chr_latches() {
+    let image = banked_image(8, 16, 0x90);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    // switchable 8KB at $8000, the last three banks fixed behind it
+    bus.write(0xA000, 0x02);
+    assert_eq!(bus.read(0x8000, false), 2);
+    assert_eq!(bus.read(0xA000, false), 13);
+    assert_eq!(bus.read(0xC000, false), 14);
+    assert_eq!(bus.read(0xE000, false), 0xEA);
+
+    // 4KB bank n starts at 1KB bank 4n
+    bus.write(0xB000, 0x01);
+    bus.write(0xC000, 0x02);
+    bus.write(0xD000, 0x03);
+    bus.write(0xE000, 0x04);
+    let mut data: u8 = 0;
+    let cart = bus.cartridge_mut().unwrap();
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x88);
+    cart.ppu_read(0x1000, &mut data);
+    assert_eq!(data, 0x90);
+
+    // only $0FD8 itself flips the left latch, after the fetch
+    cart.ppu_read(0x0FD9, &mut data);
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x88);
+    cart.ppu_read(0x0FD8, &mut data);
+    assert_eq!(data, 0x8B);
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x84);
+    cart.ppu_read(0x0FE8, &mut data);
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x88);
+
+    // the right latch reacts to any row of the high plane
+    cart.ppu_read(0x1FDD, &mut data);
+    cart.ppu_read(0x1000, &mut data);
+    assert_eq!(data, 0x8C);
+
+    bus.write(0xF000, 0x01);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::HORIZONTAL);
+}
+
+#[test]
+fn test_mmc4_prg_ram_and_chr_latches() {
+    let image = banked_image(8, 16, 0xA0);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+
+    bus.write(0xA000, 0x03);
+    assert_eq!(bus.read(0x8000, false), 6);
+    assert_eq!(bus.read(0xBFFF, false), 7);
+    assert_eq!(bus.read(0xC000, false), 14);
+
+    bus.write(0x6000, 0x5A);
+    assert_eq!(bus.read(0x6000, false), 0x5A);
+
+    // unlike MMC2, any row of tile $FD flips the left latch
+    bus.write(0xB000, 0x01);
+    bus.write(0xC000, 0x02);
+    let mut data: u8 = 0;
+    let cart = bus.cartridge_mut().unwrap();
+    cart.ppu_read(0x0FDC, &mut data);
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x84);
+}
+
+/// Turns an iNES image into NES 2.0 with the given mapper and submapper.
+fn with_submapper(mut image: Vec<u8>, mapper: u16, submapper: u8) -> Vec<u8> {
+    image[6] = (image[6] & 0x0F) | ((mapper & 0x0F) << 4) as u8;
+    image[7] = 0x08 | (mapper & 0xF0) as u8;
+    image[8] = (submapper << 4) | ((mapper >> 8) & 0x0F) as u8;
+    image
+}
+
+#[test]
+fn test_vrc4_address_lines_and_banking() {
+    // VRC4c: register select on A6/A7
+    let image = with_submapper(banked_image(8, 4, 0x00), 21, 2);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mapper_id(), 21);
+
+    bus.write(0x8000, 0x03);
+    bus.write(0xA000, 0x05);
+    assert_eq!(bus.read(0x8000, false), 3);
+    assert_eq!(bus.read(0xA000, false), 5);
+    assert_eq!(bus.read(0xC000, false), 14);
+
+    // $9080 is register 2: swap $8000 and $C000
+    bus.write(0x9080, 0x02);
+    assert_eq!(bus.read(0x8000, false), 14);
+    assert_eq!(bus.read(0xC000, false), 3);
+
+    // 9-bit CHR bank: low nibble at $B000, high bits at $B040
+    bus.write(0xB000, 0x05);
+    bus.write(0xB040, 0x01);
+    let mut data: u8 = 0;
+    bus.cartridge_mut().unwrap().ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x80 | 0x15);
+
+    bus.write(0x9000, 0x03);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::ONESCREAN_HI);
+
+    // VRC2a: A0/A1 swapped and CHR bank numbers halved
+    let image = with_submapper(banked_image(8, 4, 0x00), 22, 0);
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mapper_id(), 22);
+    bus.write(0xB000, 0x06);
+    bus.write(0xB002, 0x01);
+    bus.cartridge_mut().unwrap().ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x80 | 0x0B);
+
+    // no work ram in the NES 2.0 header: the one bit latch at $6000
+    bus.write(0x6000, 0xFF);
+    assert_eq!(bus.read(0x6000, false), 0x01);
+}
+
+#[test]
+fn test_vrc4_irq_prescaler() {
+    let image = with_submapper(banked_image(2, 1, 0x00), 25, 1);
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+
+    // VRC4b: A0 = A1, A1 = A0, so $F002 is the latch high nibble and
+    // $F001 the control register
+    cart.cpu_write(0xF000, 0x0D);
+    cart.cpu_write(0xF002, 0x0F);
+    cart.cpu_write(0xF001, 0x06); // enabled, cpu cycle mode
+    cart.cpu_clock();
+    cart.cpu_clock();
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+
+    // acknowledge copies A (clear) into E, the counter stops
+    cart.cpu_write(0xF003, 0x00);
+    assert!(!cart.irq_state());
+    for _ in 0..1000 {
+        cart.cpu_clock();
+    }
+    assert!(!cart.irq_state());
+
+    // scanline mode: one tick every 341/3 cpu cycles
+    cart.cpu_write(0xF000, 0x0F);
+    cart.cpu_write(0xF001, 0x02);
+    for _ in 0..113 {
+        cart.cpu_clock();
+    }
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+}
+
+#[test]
+fn test_vrc6_banking_and_audio() {
+    let mut image = banked_image(8, 4, 0x80);
+    image[7] = 0x10;
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mapper_id(), 24);
+
+    bus.write(0x8000, 0x02);
+    bus.write(0xC000, 0x07);
+    assert_eq!(bus.read(0x8000, false), 4);
+    assert_eq!(bus.read(0xA000, false), 5);
+    assert_eq!(bus.read(0xC000, false), 7);
+    assert_eq!(bus.read(0xE000, false), 0xEA);
+
+    // 1KB CHR banks, horizontal mirroring
+    bus.write(0xB003, 0x24);
+    bus.write(0xD000, 0x09);
+    bus.write(0xE003, 0x1F);
+    let mut data: u8 = 0;
+    let cart = bus.cartridge_mut().unwrap();
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x89);
+    cart.ppu_read(0x1C00, &mut data);
+    assert_eq!(data, 0x9F);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::HORIZONTAL);
+
+    // pulse 1 at 50% duty and the sawtooth at full rate
+    assert_eq!(bus.audio_sample(), 0.0);
+    bus.write(0x9000, 0x7F);
+    bus.write(0x9001, 0x20);
+    bus.write(0x9002, 0x80);
+    bus.write(0xB000, 0x3F);
+    bus.write(0xB001, 0x20);
+    bus.write(0xB002, 0x80);
+    let mut peak: f32 = 0.0;
+    for _ in 0..2000 {
+        bus.cartridge_mut().unwrap().cpu_clock();
+        peak = peak.max(bus.audio_sample());
+    }
+    assert!(peak > 15.0 * 0.00752);
+
+    // disabling both channels silences them
+    bus.write(0x9002, 0x00);
+    bus.write(0xB002, 0x00);
+    assert_eq!(bus.audio_sample(), 0.0);
+
+    // mapper 26 swaps A0 and A1
+    let mut image = banked_image(8, 4, 0xA0);
+    image[7] = 0x10;
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    bus.write(0xB003, 0x20);
+    bus.write(0xD002, 0x05);
+    bus.cartridge_mut().unwrap().ppu_read(0x0400, &mut data);
+    assert_eq!(data, 0x85);
+}
+
+#[test]
+fn test_vrc7_banking_and_irq() {
+    // VRC7a: register select on A4
+    let image = with_submapper(banked_image(8, 8, 0x00), 85, 1);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mapper_id(), 85);
+
+    bus.write(0x8000, 0x03);
+    bus.write(0x8010, 0x05);
+    bus.write(0x9000, 0x06);
+    assert_eq!(bus.read(0x8000, false), 3);
+    assert_eq!(bus.read(0xA000, false), 5);
+    assert_eq!(bus.read(0xC000, false), 6);
+    assert_eq!(bus.read(0xE000, false), 0xEA);
+
+    bus.write(0xA000, 0x09);
+    bus.write(0xD010, 0x1F);
+    bus.write(0xE000, 0x01);
+    let mut data: u8 = 0;
+    let cart = bus.cartridge_mut().unwrap();
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x89);
+    cart.ppu_read(0x1C00, &mut data);
+    assert_eq!(data, 0x9F);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::HORIZONTAL);
+
+    // VRC7b: A3 instead, $8008 is the second PRG register
+    let image = with_submapper(banked_image(8, 8, 0x00), 85, 2);
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    cart.cpu_write(0x8008, 0x02);
+    cart.cpu_write(0xA008, 0x04);
+    cart.cpu_read(0xA000, &mut data);
+    assert_eq!(data, 2);
+    cart.ppu_read(0x0400, &mut data);
+    assert_eq!(data, 0x84);
+
+    // the VRC4's IRQ counter, latch $E008, control $F000, ack $F008
+    cart.cpu_write(0xE008, 0xFD);
+    cart.cpu_write(0xF000, 0x06);
+    cart.cpu_clock();
+    cart.cpu_clock();
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+    cart.cpu_write(0xF008, 0x00);
+    assert!(!cart.irq_state());
+}
+
+#[test]
+fn test_vrc7_fm_audio() {
+    let image = with_submapper(banked_image(8, 8, 0x00), 85, 1);
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.audio_sample(), 0.0);
+
+    // channel 0, built-in instrument 3 at full volume, keyed on
+    let opll = |bus: &mut Bus, reg: u8, data: u8| {
+        bus.write(0x9010, reg);
+        bus.write(0x9030, data);
+    };
+    opll(&mut bus, 0x10, 0x22);
+    opll(&mut bus, 0x30, 0x30);
+    opll(&mut bus, 0x20, 0x19);
+    let mut peak: f32 = 0.0;
+    for _ in 0..36 * 500 {
+        bus.cartridge_mut().unwrap().cpu_clock();
+        peak = peak.max(bus.audio_sample().abs());
+    }
+    assert!(peak > 0.05);
+
+    // the sound reset bit silences and clears the synthesizer
+    bus.write(0xE000, 0x40);
+    assert_eq!(bus.audio_sample(), 0.0);
+    bus.write(0xE000, 0x00);
+    for _ in 0..36 * 100 {
+        bus.cartridge_mut().unwrap().cpu_clock();
+    }
+    assert_eq!(bus.audio_sample(), 0.0);
+}
+
+/// Custom instrument with a silent modulator and a carrier that holds at
+/// full level, a plain sine.
+fn opll_sine(volume: u8) -> Opll {
+    let mut opll = Opll::new();
+    let patch: [u8; 8] = [0x01, 0x21, 0x3F, 0x00, 0xF0, 0xF0, 0x0F, 0x0F];
+    for (reg, data) in patch.into_iter().enumerate() {
+        opll.write(reg as u8, data);
+    }
+    // 440Hz: fnum = 440 * 2^19 / (rate * 2^block)
+    let fnum: u16 = (440.0 * (1 << 19) as f32 / (SAMPLE_RATE * 16.0)) as u16;
+    opll.write(0x10, fnum as u8);
+    opll.write(0x30, volume);
+    opll.write(0x20, 0x10 | (4 << 1) | (fnum >> 8) as u8);
+    opll
+}
+
+#[test]
+fn test_opll_silent_until_keyed() {
+    let mut opll = Opll::new();
+    for reg in 0..6 {
+        opll.write(0x10 + reg, 0xFF);
+        opll.write(0x30 + reg, 0x10);
+    }
+    for _ in 0..1000 {
+        assert_eq!(opll.clock(), 0.0);
+    }
+}
+
+#[test]
+fn test_opll_pitch_volume_and_release() {
+    let mut opll = opll_sine(0x00);
+    let mut crossings: u32 = 0;
+    let mut peak: f32 = 0.0;
+    let mut last: f32 = 0.0;
+    for _ in 0..SAMPLE_RATE as u32 {
+        let sample: f32 = opll.clock();
+        if last < 0.0 && sample >= 0.0 {
+            crossings += 1;
+        }
+        peak = peak.max(sample.abs());
+        last = sample;
+    }
+    assert!((435..=445).contains(&crossings), "{crossings} Hz");
+    assert!(peak > 0.95);
+
+    // each volume step attenuates by 3dB
+    let mut quiet = opll_sine(0x0F);
+    let quiet_peak: f32 =
+        (0..2000).map(|_| quiet.clock().abs()).fold(0.0, f32::max);
+    let db: f32 = 20.0 * (peak / quiet_peak).log10();
+    assert!((44.0..=46.0).contains(&db), "{db} dB");
+
+    // key off, RR 15 fades out quickly
+    opll.write(0x20, 0x08);
+    for _ in 0..2000 {
+        opll.clock();
+    }
+    assert_eq!(opll.output(), 0.0);
+}
+
+#[test]
+fn test_opll_rom_tables() {
+    // the first sixteen words of the log-sine and exponent ROMs as read
+    // off the decapped YM3812 die, the YM2413 carries the same contents
+    const LOGSIN_ROM: [u16; 16] = [
+        0x859, 0x6C3, 0x607, 0x58B, 0x52E, 0x4E4, 0x4A6, 0x471, 0x443, 0x41A,
+        0x3F5, 0x3D3, 0x3B5, 0x398, 0x37E, 0x365,
+    ];
+    const EXP_ROM: [u16; 16] = [
+        0x000, 0x003, 0x006, 0x008, 0x00B, 0x00E, 0x011, 0x014, 0x016, 0x019,
+        0x01C, 0x01F, 0x022, 0x025, 0x028, 0x02A,
+    ];
+    assert_eq!(LOGSIN_TABLE[..16], LOGSIN_ROM);
+    assert_eq!(LOGSIN_TABLE[255], 0x000);
+    assert_eq!(EXP_TABLE[..16], EXP_ROM);
+    assert_eq!(EXP_TABLE[255], 0x3FA);
+
+    // a full level sine peaks at (0x3FA | 0x400) << 1 out of 4096
+    let mut opll = opll_sine(0x00);
+    let peak: f32 = (0..2000).map(|_| opll.clock().abs()).fold(0.0, f32::max);
+    assert_eq!(peak, 4084.0 / 4096.0);
+}
+
+#[test]
+fn test_fme7_banking_and_wram() {
+    let mut image = banked_image(16, 8, 0x50);
+    image[7] = 0x40;
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mapper_id(), 69);
+
+    let fme7 = |bus: &mut Bus, command: u8, data: u8| {
+        bus.write(0x8000, command);
+        bus.write(0xA000, data);
+    };
+    fme7(&mut bus, 0x09, 0x03);
+    fme7(&mut bus, 0x0A, 0x05);
+    fme7(&mut bus, 0x0B, 0x0C);
+    assert_eq!(bus.read(0x8000, false), 3);
+    assert_eq!(bus.read(0xA000, false), 5);
+    assert_eq!(bus.read(0xC000, false), 12);
+    assert_eq!(bus.read(0xE000, false), 0xEA);
+
+    // ROM at $6000, then RAM that only answers while enabled
+    fme7(&mut bus, 0x08, 0x07);
+    assert_eq!(bus.read(0x6000, false), 7);
+    fme7(&mut bus, 0x08, 0xC0);
+    bus.write(0x6010, 0x42);
+    assert_eq!(bus.read(0x6010, false), 0x42);
+    fme7(&mut bus, 0x08, 0x40);
+    bus.write(0x6010, 0x13);
+    fme7(&mut bus, 0x08, 0xC0);
+    assert_eq!(bus.read(0x6010, false), 0x42);
+
+    fme7(&mut bus, 0x00, 0x11);
+    fme7(&mut bus, 0x07, 0x3E);
+    fme7(&mut bus, 0x0C, 0x03);
+    let mut data: u8 = 0;
+    let cart = bus.cartridge_mut().unwrap();
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x91);
+    cart.ppu_read(0x1C00, &mut data);
+    assert_eq!(data, 0xBE);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::ONESCREAN_HI);
+}
+
+#[test]
+fn test_fme7_cycle_irq() {
+    let mut image = banked_image(4, 2, 0x50);
+    image[7] = 0x40;
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    let fme7 = |cart: &mut Cartrige, command: u8, data: u8| {
+        cart.cpu_write(0x8000, command);
+        cart.cpu_write(0xA000, data);
+    };
+    fme7(&mut cart, 0x0E, 0x02);
+    fme7(&mut cart, 0x0F, 0x00);
+    fme7(&mut cart, 0x0D, 0x81);
+    for _ in 0..2 {
+        cart.cpu_clock();
+    }
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+
+    // writing the control register acknowledges, the counter keeps going
+    fme7(&mut cart, 0x0D, 0x81);
+    assert!(!cart.irq_state());
+    for _ in 0..0xFFFF {
+        cart.cpu_clock();
+    }
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+
+    // counting stopped
+    fme7(&mut cart, 0x0D, 0x01);
+    for _ in 0..0x20000 {
+        cart.cpu_clock();
+    }
+    assert!(!cart.irq_state());
+}
+
+#[test]
+fn test_sunsoft5b_audio() {
+    let mut image = banked_image(4, 2, 0x50);
+    image[7] = 0x40;
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    let ay = |bus: &mut Bus, reg: u8, data: u8| {
+        bus.write(0xC000, reg);
+        bus.write(0xE000, data);
+    };
+    let run = |bus: &mut Bus, cycles: u32| -> (f32, f32) {
+        let (mut low, mut high) = (f32::MAX, 0.0f32);
+        for _ in 0..cycles {
+            bus.cartridge_mut().unwrap().cpu_clock();
+            low = low.min(bus.audio_sample());
+            high = high.max(bus.audio_sample());
+        }
+        (low, high)
+    };
+
+    // channel A tone only, full volume: a square wave
+    ay(&mut bus, 0x07, 0x3E);
+    ay(&mut bus, 0x00, 0x10);
+    ay(&mut bus, 0x08, 0x0F);
+    let (low, high) = run(&mut bus, 2000);
+    assert_eq!(low, 0.0);
+    assert!(high > 0.1);
+
+    // volume 1 is 14 steps of 3dB below volume 15
+    ay(&mut bus, 0x08, 0x01);
+    let (_, quiet) = run(&mut bus, 2000);
+    let db: f32 = 20.0 * (high / quiet).log10();
+    assert!((41.0..=43.0).contains(&db), "{db} dB");
+
+    // tone off: the channel sits at its envelope level, a single decay
+    // that ends in silence
+    ay(&mut bus, 0x07, 0x3F);
+    ay(&mut bus, 0x08, 0x10);
+    ay(&mut bus, 0x0B, 0x01);
+    ay(&mut bus, 0x0C, 0x00);
+    ay(&mut bus, 0x0D, 0x00);
+    assert!((bus.audio_sample() - high).abs() < 0.001);
+    run(&mut bus, 16 * 16);
+    assert_eq!(bus.audio_sample(), 0.0);
+    run(&mut bus, 1000);
+    assert_eq!(bus.audio_sample(), 0.0);
+}
+
+#[test]
+fn test_n163_banking_and_ciram() {
+    let mut image = banked_image(16, 8, 0x30);
+    image[7] = 0x10;
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mapper_id(), 19);
+
+    bus.write(0xE000, 0x03);
+    bus.write(0xE800, 0x05);
+    bus.write(0xF000, 0x0C);
+    assert_eq!(bus.read(0x8000, false), 3);
+    assert_eq!(bus.read(0xA000, false), 5);
+    assert_eq!(bus.read(0xC000, false), 12);
+    assert_eq!(bus.read(0xE000, false), 0xEA);
+
+    // $E0 and up is CIRAM, unless $E800 turns that off for the half
+    bus.write(0x8000, 0x11);
+    bus.write(0x8800, 0xE1);
+    bus.write(0xB800, 0xE0);
+    let mut data: u8 = 0;
+    let cart = bus.cartridge_mut().unwrap();
+    assert_eq!(cart.ppu_read(0x0000, &mut data), PpuAccess::CARTRIDGE);
+    assert_eq!(data, 0x91);
+    assert_eq!(cart.ppu_read(0x0410, &mut data), PpuAccess::CIRAM(0x0410));
+    assert_eq!(cart.ppu_read(0x1C20, &mut data), PpuAccess::CIRAM(0x0020));
+    cart.cpu_write(0xE800, 0x85);
+    assert_eq!(cart.ppu_read(0x1C20, &mut data), PpuAccess::CARTRIDGE);
+    assert_eq!(data, 0x80 | (0xE0 % 64)); // wraps within the 64KB of CHR
+    assert_eq!(cart.ppu_read(0x0410, &mut data), PpuAccess::CIRAM(0x0410));
+
+    // nametables: CIRAM pages or CHR ROM, which ignores writes
+    cart.cpu_write(0xC000, 0xE1);
+    cart.cpu_write(0xC800, 0x02);
+    assert_eq!(cart.ppu_read(0x2005, &mut data), PpuAccess::CIRAM(0x0405));
+    assert_eq!(cart.ppu_read(0x2405, &mut data), PpuAccess::CARTRIDGE);
+    assert_eq!(data, 0x82);
+    assert_eq!(cart.ppu_write(0x2405, 0x00), PpuAccess::CARTRIDGE);
+    cart.ppu_read(0x2405, &mut data);
+    assert_eq!(data, 0x82);
+    assert_eq!(cart.ppu_read(0x3F00, &mut data), PpuAccess::CONSOLE);
+
+    // PRG RAM writes need the $4x key and an unprotected 2KB section
+    bus.write(0x6000, 0x11);
+    assert_eq!(bus.read(0x6000, false), 0x00);
+    bus.write(0xF800, 0x41);
+    bus.write(0x6000, 0x22);
+    bus.write(0x6800, 0x33);
+    assert_eq!(bus.read(0x6000, false), 0x00);
+    assert_eq!(bus.read(0x6800, false), 0x33);
+}
+
+#[test]
+fn test_n163_irq_and_sound_ram() {
+    let mut image = banked_image(4, 2, 0x32);
+    image[7] = 0x10;
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+
+    // counts up to $7FFF and stays there
+    cart.cpu_write(0x5000, 0xFD);
+    cart.cpu_write(0x5800, 0xFF);
+    cart.cpu_clock();
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+    let mut data: u8 = 0;
+    cart.cpu_read(0x5000, &mut data);
+    assert_eq!(data, 0xFF);
+    cart.cpu_write(0x5800, 0x00);
+    assert!(!cart.irq_state());
+
+    // the data port with auto increment
+    cart.cpu_write(0xF800, 0x80 | 0x10);
+    for value in [0x12, 0x34, 0x56] {
+        cart.cpu_write(0x4800, value);
+    }
+    cart.cpu_write(0xF800, 0x11);
+    cart.cpu_read(0x4800, &mut data);
+    assert_eq!(data, 0x34);
+    cart.cpu_read(0x4800, &mut data);
+    assert_eq!(data, 0x34);
+
+    // battery backed: the sound RAM follows the PRG RAM in the save
+    let save = cart.battery_data().unwrap();
+    assert_eq!(save.len(), 0x2000 + 0x80);
+    assert_eq!(save[0x2000 + 0x11], 0x34);
+    let mut copy = Cartrige::from_bytes(&image).unwrap();
+    copy.load_battery_data(&save);
+    copy.cpu_write(0xF800, 0x12);
+    copy.cpu_read(0x4800, &mut data);
+    assert_eq!(data, 0x56);
+
+    // no battery, nothing to save
+    let mut image = banked_image(4, 2, 0x30);
+    image[7] = 0x10;
+    assert!(Cartrige::from_bytes(&image)
+        .unwrap()
+        .battery_data()
+        .is_none());
+}
+
+#[test]
+fn test_n163_wavetable_audio() {
+    let mut image = banked_image(4, 2, 0x30);
+    image[7] = 0x10;
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    let poke = |cart: &mut Cartrige, addr: u8, bytes: &[u8]| {
+        cart.cpu_write(0xF800, 0x80 | addr);
+        for &b in bytes {
+            cart.cpu_write(0x4800, b);
+        }
+    };
+    // an 8 sample square at the start of RAM: 4 × $F then 4 × $0
+    poke(&mut cart, 0x00, &[0xFF, 0xFF, 0x00, 0x00]);
+    // channel 7 only: one sample per update, length 8, full volume
+    poke(
+        &mut cart,
+        0x78,
+        &[0x00, 0x00, 0x00, 0x00, 0xF9, 0x00, 0x00, 0x0F],
+    );
+    assert_eq!(cart.audio_output(), 0.0);
+
+    let mut levels: Vec<f32> = vec![];
+    for _ in 0..15 * 8 {
+        cart.cpu_clock();
+        levels.push(cart.audio_output());
+    }
+    let high: f32 = 7.0 * 15.0 * 0.0012;
+    let low: f32 = -8.0 * 15.0 * 0.0012;
+    assert!(levels.iter().any(|&l| (l - high).abs() < 1e-6));
+    assert!(levels.iter().any(|&l| (l - low).abs() < 1e-6));
+
+    // the sound disable bit in $E000
+    cart.cpu_write(0xE000, 0x40);
+    assert_eq!(cart.audio_output(), 0.0);
+}
+
+#[test]
+fn test_bandai_fcg_banking_and_irq() {
+    // LZ93D50: registers at $8000-$FFFF, IRQ reloaded from a latch
+    let mut image = banked_image(16, 8, 0x00);
+    image[7] = 0x10;
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    assert_eq!(bus.cartridge().unwrap().mapper_id(), 16);
+
+    bus.write(0x8008, 0x03);
+    assert_eq!(bus.read(0x8000, false), 6);
+    assert_eq!(bus.read(0xA000, false), 7);
+    assert_eq!(bus.read(0xC000, false), 30);
+    assert_eq!(bus.read(0xE000, false), 0xEA);
+    bus.write(0x8000, 0x11);
+    bus.write(0xFFF7, 0x3E); // registers repeat every 16 bytes
+    bus.write(0x8009, 0x01);
+    let mut data: u8 = 0;
+    let cart = bus.cartridge_mut().unwrap();
+    cart.ppu_read(0x0000, &mut data);
+    assert_eq!(data, 0x91);
+    cart.ppu_read(0x1C00, &mut data);
+    assert_eq!(data, 0xBE);
+    assert_eq!(bus.cartridge().unwrap().mirror(), Mirroring::HORIZONTAL);
+
+    let cart = bus.cartridge_mut().unwrap();
+    cart.cpu_write(0x800B, 0x02);
+    cart.cpu_write(0x800C, 0x00);
+    cart.cpu_write(0x800A, 0x01);
+    cart.cpu_clock();
+    cart.cpu_clock();
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+    cart.cpu_write(0x800A, 0x00);
+    assert!(!cart.irq_state());
+
+    // FCG-1/2: registers at $6000-$7FFF, the counter is written directly
+    let image = with_submapper(banked_image(16, 8, 0x00), 16, 4);
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    cart.cpu_write(0x8008, 0x03);
+    cart.cpu_read(0x8000, &mut data);
+    assert_eq!(data, 0);
+    cart.cpu_write(0x6008, 0x03);
+    cart.cpu_read(0x8000, &mut data);
+    assert_eq!(data, 6);
+    cart.cpu_write(0x600B, 0x01);
+    cart.cpu_write(0x600C, 0x00);
+    cart.cpu_write(0x600A, 0x01);
+    cart.cpu_clock();
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+}
+
+/// Bit-bangs the Bandai EEPROM lines through $800D.
+struct I2c<'a> {
+    cart: &'a mut Cartrige,
+    lsb_first: bool,
+}
+impl I2c<'_> {
+    fn lines(&mut self, scl: bool, sda: bool) {
+        self.cart
+            .cpu_write(0x800D, 0x80 | (sda as u8) << 6 | (scl as u8) << 5);
+    }
+
+    fn sda(&mut self) -> bool {
+        let mut data: u8 = 0;
+        self.cart.cpu_read(0x6000, &mut data);
+        data & 0x10 != 0
+    }
+
+    fn start(&mut self) {
+        self.lines(false, true);
+        self.lines(true, true);
+        self.lines(true, false);
+        self.lines(false, false);
+    }
+
+    fn stop(&mut self) {
+        self.lines(false, false);
+        self.lines(true, false);
+        self.lines(true, true);
+    }
+
+    /// Sends a byte, returns whether the chip acknowledged it.
+    fn send(&mut self, byte: u8) -> bool {
+        for i in 0..8 {
+            let shift: u8 = if self.lsb_first { i } else { 7 - i };
+            let bit: bool = (byte >> shift) & 0x01 != 0;
+            self.lines(false, bit);
+            self.lines(true, bit);
+            self.lines(false, bit);
+        }
+        self.lines(false, true);
+        self.lines(true, true);
+        let ack: bool = !self.sda();
+        self.lines(false, true);
+        ack
+    }
+
+    fn receive(&mut self, ack: bool) -> u8 {
+        let mut byte: u8 = 0;
+        for i in 0..8 {
+            self.lines(true, true);
+            let shift: u8 = if self.lsb_first { i } else { 7 - i };
+            byte |= (self.sda() as u8) << shift;
+            self.lines(false, true);
+        }
+        self.lines(false, !ack);
+        self.lines(true, !ack);
+        self.lines(false, !ack);
+        byte
+    }
+}
+
+#[test]
+fn test_bandai_24c02_eeprom() {
+    let image = with_submapper(banked_image(4, 2, 0x00), 16, 5);
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+
+    let mut i2c = I2c {
+        cart: &mut cart,
+        lsb_first: false,
+    };
+    // write two bytes from $10
+    i2c.start();
+    assert!(i2c.send(0xA0));
+    assert!(i2c.send(0x10));
+    assert!(i2c.send(0x12));
+    assert!(i2c.send(0x34));
+    i2c.stop();
+    // other device addresses are not answered
+    i2c.start();
+    assert!(!i2c.send(0x50));
+    i2c.stop();
+    // random read: set the address, repeated start, then read
+    i2c.start();
+    assert!(i2c.send(0xA0));
+    assert!(i2c.send(0x10));
+    i2c.start();
+    assert!(i2c.send(0xA1));
+    assert_eq!(i2c.receive(true), 0x12);
+    assert_eq!(i2c.receive(false), 0x34);
+    i2c.stop();
+
+    // the EEPROM is what gets saved, and restored into a fresh board
+    let save = cart.battery_data().unwrap();
+    assert_eq!(save.len(), 0x100);
+    assert_eq!(&save[0x10..0x12], &[0x12, 0x34]);
+    let mut copy = Cartrige::from_bytes(&image).unwrap();
+    copy.load_battery_data(&save);
+    let mut i2c = I2c {
+        cart: &mut copy,
+        lsb_first: false,
+    };
+    i2c.start();
+    i2c.send(0xA0);
+    i2c.send(0x11);
+    i2c.start();
+    i2c.send(0xA1);
+    assert_eq!(i2c.receive(false), 0x34);
+    i2c.stop();
+}
+
+#[test]
+fn test_bandai_24c01_eeprom() {
+    // mapper 159, 7-bit address and direction in one byte, LSB first
+    let mut image = banked_image(4, 2, 0xF0);
+    image[7] = 0x90;
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    assert_eq!(cart.mapper_id(), 159);
+    let mut i2c = I2c {
+        cart: &mut cart,
+        lsb_first: true,
+    };
+    i2c.start();
+    assert!(i2c.send(0x05));
+    assert!(i2c.send(0xC3));
+    assert!(i2c.send(0x5A));
+    i2c.stop();
+    i2c.start();
+    assert!(i2c.send(0x80 | 0x05));
+    assert_eq!(i2c.receive(true), 0xC3);
+    assert_eq!(i2c.receive(false), 0x5A);
+    i2c.stop();
+    assert_eq!(cart.battery_data().unwrap().len(), 0x80);
+}
+
+#[test]
+fn test_ips_round_trip() {
+    let original: Vec<u8> = (0..=255u8).cycle().take(0x1000).collect();
+    let mut modified: Vec<u8> = original.clone();
+    modified[0x10] = 0xAA;
+    modified[0x14] = 0xBB; // close enough to share a record
+    modified[0x800..0x900].fill(0x00);
+    modified.extend_from_slice(&[1, 2, 3]);
+    let ips: Vec<u8> = patch::create_ips(&original, &modified);
+    assert_eq!(patch::apply_ips(&original, &ips), Ok(modified.clone()));
+
+    // shrinking uses the truncate extension
+    let ips: Vec<u8> = patch::create_ips(&original, &original[..0x100]);
+    assert_eq!(
+        patch::apply_ips(&original, &ips),
+        Ok(original[..0x100].to_vec())
+    );
+
+    // run length records
+    let rle: &[u8] = b"PATCH\x00\x00\x02\x00\x00\x00\x04\x7FEOF";
+    assert_eq!(
+        patch::apply_ips(&[0; 8], rle),
+        Ok(vec![0, 0, 0x7F, 0x7F, 0x7F, 0x7F, 0, 0])
+    );
+    assert_eq!(
+        patch::apply_ips(&[0; 8], b"PTCH"),
+        Err(PatchError::BadMagic)
+    );
+    assert_eq!(
+        patch::apply_ips(&[0; 8], b"PATCH\x00\x00\x02\x00\x04\x01"),
+        Err(PatchError::Truncated)
+    );
+}
+
+/// A BPS/UPS number: 7 bits per byte with bit 7 ending it.
+fn patch_number(mut n: usize) -> Vec<u8> {
+    let mut bytes: Vec<u8> = vec![];
+    loop {
+        let low: u8 = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            bytes.push(0x80 | low);
+            return bytes;
+        }
+        bytes.push(low);
+        n -= 1;
+    }
+}
+
+/// Appends the source, target and patch CRC-32 footer.
+fn patch_footer(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+    patch.extend_from_slice(&hash::crc32(source).to_le_bytes());
+    patch.extend_from_slice(&hash::crc32(target).to_le_bytes());
+    let crc: u32 = hash::crc32(&patch);
+    patch.extend_from_slice(&crc.to_le_bytes());
+    patch
+}
+
+fn bps_patch(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+    let mut patch: Vec<u8> = b"BPS1".to_vec();
+    patch.extend(patch_number(source.len()));
+    patch.extend(patch_number(target.len()));
+    patch.extend(patch_number(0)); // no metadata
+    patch.extend_from_slice(actions);
+    patch_footer(patch, source, target)
+}
+
+/// A BPS patch writing all of `target` with one target read action.
+fn bps_target_read(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut actions: Vec<u8> = patch_number((target.len() - 1) << 2 | 1);
+    actions.extend_from_slice(target);
+    bps_patch(source, target, &actions)
+}
+
+fn ups_patch(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch: Vec<u8> = b"UPS1".to_vec();
+    patch.extend(patch_number(source.len()));
+    patch.extend(patch_number(target.len()));
+    let xor = |i: usize| source.get(i).copied().unwrap_or(0) ^ target[i];
+    let mut skipped: usize = 0;
+    let mut i: usize = 0;
+    while i < target.len() {
+        if xor(i) == 0 {
+            skipped += 1;
+            i += 1;
+            continue;
+        }
+        patch.extend(patch_number(skipped));
+        while i < target.len() && xor(i) != 0 {
+            patch.push(xor(i));
+            i += 1;
+        }
+        patch.push(0x00); // also steps over the equal byte after the hunk
+        skipped = 0;
+        i += 1;
+    }
+    patch_footer(patch, source, target)
+}
+
+#[test]
+fn test_bps_patch() {
+    let source: Vec<u8> = (0..64u8).collect();
+    let mut actions: Vec<u8> = vec![];
+    // source read: the first 16 bytes stay
+    actions.extend(patch_number((16 - 1) << 2));
+    // target read: 5 new bytes
+    actions.extend(patch_number((5 - 1) << 2 | 1));
+    actions.extend_from_slice(b"HELLO");
+    // source copy: 16 bytes from source offset 32
+    actions.extend(patch_number((16 - 1) << 2 | 2));
+    actions.extend(patch_number(32 << 1));
+    // target copy: 8 bytes from output offset 16, reading its own output
+    actions.extend(patch_number((8 - 1) << 2 | 3));
+    actions.extend(patch_number(16 << 1));
+
+    let mut target: Vec<u8> = source[..16].to_vec();
+    target.extend_from_slice(b"HELLO");
+    target.extend_from_slice(&source[32..48]);
+    target.extend_from_within(16..24);
+    let bps: Vec<u8> = bps_patch(&source, &target, &actions);
+    assert_eq!(patch::apply(&source, &bps), Ok(target.clone()));
+
+    // wrong rom
+    let other: Vec<u8> = vec![0u8; 64];
+    assert_eq!(
+        patch::apply_bps(&other, &bps),
+        Err(PatchError::Checksum {
+            which: patch::Checksum::Source,
+            expected: hash::crc32(&source),
+            found: hash::crc32(&other),
+        })
+    );
+    // damaged patch
+    let mut damaged: Vec<u8> = bps.clone();
+    damaged[10] ^= 0x01;
+    assert!(matches!(
+        patch::apply_bps(&source, &damaged),
+        Err(PatchError::Checksum {
+            which: patch::Checksum::Patch,
+            ..
+        })
+    ));
+    // a footer promising some other result
+    let mut promised: Vec<u8> = target.clone();
+    promised[0] ^= 0x01;
+    let wrong: Vec<u8> = bps_patch(&source, &promised, &actions);
+    let error: PatchError = patch::apply_bps(&source, &wrong).unwrap_err();
+    assert_eq!(
+        error,
+        PatchError::Checksum {
+            which: patch::Checksum::Target,
+            expected: hash::crc32(&promised),
+            found: hash::crc32(&target),
+        }
+    );
+    assert!(error.to_string().starts_with("the patched rom is wrong"));
+}
+
+#[test]
+fn test_ups_patch() {
+    let source: Vec<u8> = (0..64u8).collect();
+    let mut target: Vec<u8> = source.clone();
+    target[3] = 0xFF;
+    target[10..14].fill(0xAA);
+    target.extend_from_slice(&[1, 2, 3, 0, 5]); // grows, including a zero
+    let ups: Vec<u8> = ups_patch(&source, &target);
+    assert_eq!(patch::apply(&source, &ups), Ok(target.clone()));
+
+    // shrinking keeps only the target size
+    let ups: Vec<u8> = ups_patch(&target, &source);
+    assert_eq!(patch::apply_ups(&target, &ups), Ok(source.clone()));
+    let error: PatchError = patch::apply_ups(&source, &ups).unwrap_err();
+    assert!(matches!(
+        error,
+        PatchError::Checksum {
+            which: patch::Checksum::Source,
+            ..
+        }
+    ));
+    assert!(error
+        .to_string()
+        .starts_with("the rom does not match the patch: expected CRC32"));
+    assert_eq!(
+        patch::apply_ups(&source, b"UPS1\x80"),
+        Err(PatchError::Truncated)
+    );
+
+    // a skip past the end of the output is rejected instead of wrapping
+    let rom: Vec<u8> = vec![1, 2, 3, 4];
+    let mut ups: Vec<u8> = b"UPS1".to_vec();
+    ups.extend(patch_number(rom.len()));
+    ups.extend(patch_number(rom.len()));
+    ups.extend(patch_number(usize::MAX));
+    ups.push(0x00);
+    let ups: Vec<u8> = patch_footer(ups, &rom, &rom);
+    assert_eq!(patch::apply_ups(&rom, &ups), Err(PatchError::OutOfBounds));
+
+    // and so is a number with more digits than a usize holds
+    let mut ups: Vec<u8> = b"UPS1".to_vec();
+    ups.extend(patch_number(rom.len()));
+    ups.extend_from_slice(&[0x00; 10]);
+    ups.push(0x80);
+    let ups: Vec<u8> = patch_footer(ups, &rom, &rom);
+    assert_eq!(patch::apply_ups(&rom, &ups), Err(PatchError::OutOfBounds));
+}
+
+/// Scratch directory under the system temp dir, removed again on drop so
+/// a failing test does not leave files behind.
+struct TempDir(std::path::PathBuf);
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let dir =
+            std::env::temp_dir().join(format!("{name}_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        Self(dir)
+    }
+}
+impl std::ops::Deref for TempDir {
+    type Target = std::path::Path;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn test_patches_applied_on_load() {
+    let dir = TempDir::new("patch");
+    let rom_path = dir.join("game.nes");
+    let image: Vec<u8> = ines_image(2, 1, 0x00, 0x00);
+    std::fs::write(&rom_path, &image).unwrap();
+    let prg = cartridge::INES_HEADER_SIZE;
+
+    // a sibling IPS patch is picked up by name
+    let mut hacked: Vec<u8> = image.clone();
+    hacked[prg + 0x10] = 0x42;
+    std::fs::write(dir.join("game.ips"), patch::create_ips(&image, &hacked))
+        .unwrap();
+    assert_eq!(
+        cartridge::sibling_patch(&rom_path),
+        Some(dir.join("game.ips"))
+    );
+    let cart = Cartrige::from_file(&rom_path).unwrap();
+    assert_eq!(cart.prg_mem()[0x10], 0x42);
+
+    // an explicit BPS made for the headerless rom takes precedence
+    let mut translated: Vec<u8> = image[prg..].to_vec();
+    translated[0x20] = 0x99;
+    let bps_path = dir.join("translation.bps");
+    std::fs::write(&bps_path, bps_target_read(&image[prg..], &translated))
+        .unwrap();
+    let options = LoadOptions {
+        patch: Some(bps_path),
+        ..LoadOptions::default()
+    };
+    let cart = Cartrige::open(&rom_path, &options).unwrap();
+    assert_eq!(cart.prg_mem()[0x10], 0x00);
+    assert_eq!(cart.prg_mem()[0x20], 0x99);
+
+    // a patch for another rom is refused, naming the patch
+    let ups_path = dir.join("other.ups");
+    std::fs::write(&ups_path, ups_patch(&[0u8; 16], &[1u8; 16])).unwrap();
+    let options = LoadOptions {
+        patch: Some(ups_path.clone()),
+        ..LoadOptions::default()
+    };
+    let error = Cartrige::open(&rom_path, &options).unwrap_err();
+    assert!(matches!(
+        &error,
+        CartridgeError::RomPatch(path, PatchError::Checksum {
+            which: patch::Checksum::Source,
+            ..
+        }) if *path == ups_path
+    ));
+    assert!(error.to_string().contains("other.ups"));
+}
+
+/// A zip archive holding `files`, deflated or stored.
+fn zip_archive(files: &[(&str, &[u8])], deflate: bool) -> Vec<u8> {
+    let mut archive: Vec<u8> = vec![];
+    let mut directory: Vec<u8> = vec![];
+    for (name, contents) in files {
+        let (method, data): (u16, Vec<u8>) = match deflate {
+            true => (8, miniz_oxide::deflate::compress_to_vec(contents, 6)),
+            false => (0, contents.to_vec()),
+        };
+        let mut fields: Vec<u8> = vec![];
+        fields.extend_from_slice(&method.to_le_bytes());
+        fields.extend_from_slice(&[0; 4]); // time and date
+        fields.extend_from_slice(&hash::crc32(contents).to_le_bytes());
+        fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+        fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        fields.extend_from_slice(&[0; 2]); // extra field length
+
+        directory.extend_from_slice(b"PK\x01\x02\x14\x00\x14\x00\x00\x00");
+        directory.extend_from_slice(&fields);
+        directory.extend_from_slice(&[0; 6]); // comment, disk, attributes
+        directory.extend_from_slice(&[0; 4]); // external attributes
+        directory.extend_from_slice(&(archive.len() as u32).to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+
+        archive.extend_from_slice(b"PK\x03\x04\x14\x00\x00\x00");
+        archive.extend_from_slice(&fields);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&data);
+    }
+    let offset: u32 = archive.len() as u32;
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(b"PK\x05\x06\x00\x00\x00\x00");
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(files.len() as u16).to_le_bytes());
+    archive.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    archive.extend_from_slice(&offset.to_le_bytes());
+    archive.extend_from_slice(&[0; 2]); // comment length
+    archive
+}
+
+#[test]
+fn test_zip_archive() {
+    let text: &[u8] = b"not a rom, not a rom, not a rom";
+    let rom: Vec<u8> = banked_image(2, 1, 0x00);
+    let archive: Vec<u8> = zip_archive(
+        &[
+            ("docs/", b""),
+            ("docs/readme.txt", text),
+            ("game.nes", &rom),
+        ],
+        true,
+    );
+    assert!(zip::is_zip(&archive));
+    assert!(!zip::is_zip(&rom));
+
+    let zip = ZipArchive::new(&archive).unwrap();
+    let names: Vec<&str> = zip
+        .entries()
+        .iter()
+        .map(|entry| entry.name.as_str())
+        .collect();
+    assert_eq!(names, ["docs/", "docs/readme.txt", "game.nes"]);
+    assert!(zip.entries()[0].is_dir());
+    assert_eq!(zip.entries()[1].file_name(), "readme.txt");
+    assert_eq!(zip.read(&zip.entries()[1]), Ok(text.to_vec()));
+    assert_eq!(zip.read(&zip.entries()[2]), Ok(rom.clone()));
+
+    // a damaged stored entry fails its CRC-32
+    let mut archive: Vec<u8> = zip_archive(&[("game.nes", &rom)], false);
+    let data: usize = 30 + "game.nes".len();
+    archive[data + 100] ^= 0xFF;
+    let zip = ZipArchive::new(&archive).unwrap();
+    assert!(matches!(
+        zip.read(&zip.entries()[0]),
+        Err(ZipError::Checksum { .. })
+    ));
+    assert_eq!(ZipArchive::new(&rom).unwrap_err(), ZipError::NotAZip);
+}
+
+#[test]
+fn test_zip_loading() {
+    let dir = TempDir::new("zip");
+    let rom: Vec<u8> = banked_image(2, 1, 0x01);
+    let other: Vec<u8> = banked_image(4, 2, 0x00);
+
+    // the only rom in the archive is picked, and checked like a plain file
+    let zip_path = dir.join("game.zip");
+    std::fs::write(
+        &zip_path,
+        zip_archive(&[("readme.txt", b"hi"), ("Game.NES", &rom)], true),
+    )
+    .unwrap();
+    let cart = Cartrige::from_file(&zip_path).unwrap();
+    let plain = Cartrige::from_bytes(&rom).unwrap();
+    assert_eq!(cart.prg_mem(), plain.prg_mem());
+    assert_eq!(cart.mirror, Mirroring::VERTICAL);
+    assert_eq!(cart.hash(), plain.hash());
+
+    // several roms need a name, with or without the directory
+    std::fs::write(
+        &zip_path,
+        zip_archive(&[("a/first.nes", &rom), ("b/second.nes", &other)], true),
+    )
+    .unwrap();
+    let error = Cartrige::from_file(&zip_path).unwrap_err();
+    assert!(matches!(
+        &error,
+        CartridgeError::AmbiguousArchive(names)
+            if names == &["a/first.nes", "b/second.nes"]
+    ));
+    assert!(error.to_string().ends_with("a/first.nes, b/second.nes"));
+    for name in ["second.nes", "b/second.nes"] {
+        let options = LoadOptions {
+            entry: Some(name.to_string()),
+            ..LoadOptions::default()
+        };
+        let cart = Cartrige::open(&zip_path, &options).unwrap();
+        assert_eq!(cart.prg_banks(), 4);
+    }
+    let options = LoadOptions {
+        entry: Some("third.nes".to_string()),
+        ..LoadOptions::default()
+    };
+    assert!(matches!(
+        Cartrige::open(&zip_path, &options),
+        Err(CartridgeError::NotInArchive(name)) if name == "third.nes"
+    ));
+
+    std::fs::write(&zip_path, zip_archive(&[("readme.txt", b"hi")], false))
+        .unwrap();
+    assert!(matches!(
+        Cartrige::from_file(&zip_path),
+        Err(CartridgeError::NoRomInArchive)
+    ));
+
+    // disks still need the BIOS, music rips are recognised
+    let (disk, bios) = fds_image();
+    std::fs::write(&zip_path, zip_archive(&[("disk.fds", &disk)], true))
+        .unwrap();
+    assert!(matches!(
+        Cartrige::from_file(&zip_path),
+        Err(CartridgeError::MissingBios)
+    ));
+    let bios_path = dir.join("disksys.rom");
+    std::fs::write(&bios_path, &bios).unwrap();
+    let cart = Cartrige::from_fds_file(&zip_path, &bios_path).unwrap();
+    assert_eq!(cart.disk_sides(), 2);
+    let mut nsf: Vec<u8> = b"NESM\x1A\x01".to_vec();
+    nsf.resize(0x100, 0x00);
+    std::fs::write(&zip_path, zip_archive(&[("music.nsf", &nsf)], true))
+        .unwrap();
+    assert!(matches!(
+        Cartrige::from_file(&zip_path),
+        Err(CartridgeError::NsfNotSupported)
+    ));
+}
+
+/// A disk side holding one file of `file` bytes, byte 22 of the disk info
+/// block is the side number.
+fn fds_side(side: u8, file: &[u8]) -> Vec<u8> {
+    let mut info: Vec<u8> = vec![0x00; 56];
+    info[..15].copy_from_slice(b"\x01*NINTENDO-HVC*");
+    info[22] = side;
+    let mut header: Vec<u8> = vec![0x00; 16];
+    header[0] = 0x03;
+    header[13..15].copy_from_slice(&(file.len() as u16).to_le_bytes());
+    let mut image: Vec<u8> = info;
+    image.extend_from_slice(&[0x02, 0x01]);
+    image.extend_from_slice(&header);
+    image.push(0x04);
+    image.extend_from_slice(file);
+    image.resize(65500, 0x00);
+    image
+}
+
+/// A headered image with two sides and a BIOS that resets to $E123.
+fn fds_image() -> (Vec<u8>, Vec<u8>) {
+    let mut image: Vec<u8> = b"FDS\x1A\x02".to_vec();
+    image.resize(16, 0x00);
+    image.extend_from_slice(&fds_side(0, &[0x11, 0x22, 0x33]));
+    image.extend_from_slice(&fds_side(1, &[0x44, 0x55]));
+    let mut bios: Vec<u8> = vec![0xEA; 0x2000];
+    bios[0x1FFC..0x1FFE].copy_from_slice(&[0x23, 0xE1]);
+    (image, bios)
+}
+
+/// Clocks the drive until it raises its IRQ and reads the byte it
+/// transferred.
+fn fds_byte(cart: &mut Cartrige) -> u8 {
+    // the lead-in gap alone takes over half a million cycles
+    for _ in 0..1_000_000 {
+        cart.cpu_clock();
+        if cart.irq_state() {
+            let mut data: u8 = 0x00;
+            cart.cpu_read(0x4031, &mut data);
+            assert!(!cart.irq_state());
+            return data;
+        }
+    }
+    panic!("the drive never transferred a byte");
+}
+
+/// Reads the next block the way the BIOS does: transfers off to skip the
+/// CRC and find the gap, then on again to wait for the start mark.
+fn fds_block(cart: &mut Cartrige, len: usize) -> Vec<u8> {
+    cart.cpu_write(0x4025, 0x85);
+    for _ in 0..300 {
+        cart.cpu_clock();
+    }
+    cart.cpu_write(0x4025, 0xC5);
+    (0..len).map(|_| fds_byte(cart)).collect()
+}
+
+/// Stops the motor so the head goes back to the start, then rewrites the
+/// first byte of the file on the disk.
+fn fds_overwrite_file(cart: &mut Cartrige, data: u8) {
+    cart.cpu_write(0x4025, 0x00);
+    cart.cpu_clock();
+    fds_block(cart, 56);
+    fds_block(cart, 2);
+    fds_block(cart, 16);
+    fds_block(cart, 1);
+    cart.cpu_write(0x4024, data);
+    cart.cpu_write(0x4025, 0xC1);
+    fds_byte(cart);
+    cart.cpu_write(0x4025, 0x00);
+}
+
+#[test]
+fn test_fds_loading_and_memory() {
+    let (image, bios) = fds_image();
+    let mut bus = Bus::new();
+    let mut cpu = CPU::new();
+    bus.insert_cartridge(Cartrige::from_fds(&image, &bios).unwrap());
+    bus.reset(&mut cpu);
+    assert_eq!(cpu.pc(), 0xE123);
+
+    let cart: &Cartrige = bus.cartridge().unwrap();
+    assert_eq!(cart.mapper_id(), 20);
+    assert_eq!(cart.disk_sides(), 2);
+    assert_eq!(cart.disk_side(), Some(0));
+    assert_eq!(cart.prg_ram_size(), 0x8000);
+    assert_eq!(cart.chr_ram_size(), 0x2000);
+
+    // 32KB of RAM at $6000-$DFFF, the BIOS above it
+    bus.write(0x6000, 0x12);
+    bus.write(0xDFFF, 0x34);
+    bus.write(0xE000, 0x56);
+    assert_eq!(bus.read(0x6000, false), 0x12);
+    assert_eq!(bus.read(0xDFFF, false), 0x34);
+    assert_eq!(bus.read(0xE000, false), 0xEA);
+
+    // CHR RAM and mirroring from $4025
+    let cart: &mut Cartrige = bus.cartridge_mut().unwrap();
+    cart.ppu_write(0x1FFF, 0x77);
+    let mut data: u8 = 0x00;
+    cart.ppu_read(0x1FFF, &mut data);
+    assert_eq!(data, 0x77);
+    assert_eq!(cart.mirror(), Mirroring::VERTICAL);
+    cart.cpu_write(0x4025, 0x2E);
+    assert_eq!(cart.mirror(), Mirroring::HORIZONTAL);
+
+    // headerless images and what gets rejected
+    let raw: &[u8] = &image[16..];
+    assert_eq!(Cartrige::from_fds(raw, &bios).unwrap().disk_sides(), 2);
+    assert!(cartridge::is_fds_image(raw));
+    assert!(cartridge::is_fds_image(&image));
+    assert!(matches!(
+        Cartrige::from_fds(&image, &bios[..0x1000]),
+        Err(CartridgeError::BadBios(0x1000))
+    ));
+    assert!(matches!(
+        Cartrige::from_fds(&image[..1000], &bios),
+        Err(CartridgeError::NoDiskSides)
+    ));
+}
+
+#[test]
+fn test_fds_timer_irq() {
+    let (image, bios) = fds_image();
+    let mut cart = Cartrige::from_fds(&image, &bios).unwrap();
+    cart.cpu_write(0x4020, 0x10);
+    cart.cpu_write(0x4021, 0x00);
+    cart.cpu_write(0x4022, 0x03);
+    for _ in 0..0x10 {
+        cart.cpu_clock();
+    }
+    assert!(!cart.irq_state());
+    cart.cpu_clock();
+    assert!(cart.irq_state());
+    // $4030 reports and acknowledges it
+    let mut status: u8 = 0x00;
+    cart.cpu_read(0x4030, &mut status);
+    assert_eq!(status & 0x01, 0x01);
+    assert!(!cart.irq_state());
+    // repeating, it comes back after another 17 cycles
+    for _ in 0..0x11 {
+        cart.cpu_clock();
+    }
+    assert!(cart.irq_state());
+
+    // disabling disk I/O in $4023 stops the timer
+    cart.cpu_write(0x4023, 0x00);
+    assert!(!cart.irq_state());
+    for _ in 0..0x100 {
+        cart.cpu_clock();
+    }
+    assert!(!cart.irq_state());
+}
+
+#[test]
+fn test_fds_disk_read_write_and_sides() {
+    let (image, bios) = fds_image();
+    let mut cart = Cartrige::from_fds(&image, &bios).unwrap();
+    let mut status: u8 = 0xFF;
+    cart.cpu_read(0x4032, &mut status);
+    assert_eq!(status & 0x03, 0x02); // inserted, not spinning yet
+
+    // the first block comes off the disk after the head rewinds
+    let info: Vec<u8> = fds_block(&mut cart, 56);
+    assert_eq!(&info[..15], b"\x01*NINTENDO-HVC*");
+    cart.cpu_read(0x4032, &mut status);
+    assert_eq!(status & 0x03, 0x00);
+    assert_eq!(fds_block(&mut cart, 2), [0x02, 0x01]);
+    assert_eq!(fds_block(&mut cart, 16)[13], 3);
+    assert_eq!(fds_block(&mut cart, 4), [0x04, 0x11, 0x22, 0x33]);
+
+    fds_overwrite_file(&mut cart, 0x99);
+    let disk: Vec<u8> = cart.disk_image().unwrap();
+    assert_eq!(disk.len(), image.len());
+    assert_eq!(disk[16 + 75], 0x99);
+    assert_eq!(disk[16 + 76..], image[16 + 76..]);
+
+    // switching sides leaves the drive empty for a while
+    cart.insert_disk(Some(1));
+    assert_eq!(cart.disk_side(), None);
+    cart.cpu_read(0x4032, &mut status);
+    assert_eq!(status & 0x07, 0x07);
+    for _ in 0..1_000_000 {
+        cart.cpu_clock();
+    }
+    assert_eq!(cart.disk_side(), Some(1));
+    assert_eq!(fds_block(&mut cart, 56)[22], 1);
+
+    cart.insert_disk(None);
+    assert_eq!(cart.disk_side(), None);
+}
+
+#[test]
+fn test_fds_disk_saved_as_ips() {
+    let (image, bios) = fds_image();
+    let dir = TempDir::new("fds");
+    let disk_path = dir.join("game.fds");
+    let bios_path = dir.join("disksys.rom");
+    std::fs::write(&disk_path, &image).unwrap();
+    std::fs::write(&bios_path, &bios).unwrap();
+
+    let mut cart = Cartrige::from_fds_file(&disk_path, &bios_path).unwrap();
+    assert_eq!(cart.path(), Some(disk_path.as_path()));
+    // nothing written, nothing saved
+    cart.save_writes().unwrap();
+    assert!(!cartridge::write_save_path(&disk_path).exists());
+
+    fds_overwrite_file(&mut cart, 0x5A);
+    cart.save_writes().unwrap();
+    assert_eq!(std::fs::read(&disk_path).unwrap(), image);
+    assert!(dir.join("game.fds.ips").exists());
+
+    let cart = Cartrige::from_fds_file(&disk_path, &bios_path).unwrap();
+    let disk: Vec<u8> = cart.disk_image().unwrap();
+    assert_ne!(disk, image);
+    assert_eq!(disk[16 + 75], 0x5A);
+}
+
+#[test]
+fn test_fds_wavetable_audio() {
+    let (image, bios) = fds_image();
+    let mut cart = Cartrige::from_fds(&image, &bios).unwrap();
+    let run = |cart: &mut Cartrige, cycles: u32| -> (f32, f32) {
+        let (mut low, mut high) = (f32::MAX, 0.0f32);
+        for _ in 0..cycles {
+            cart.cpu_clock();
+            low = low.min(cart.audio_output());
+            high = high.max(cart.audio_output());
+        }
+        (low, high)
+    };
+
+    // a square wave, only writable while $4089 bit 7 is set
+    cart.cpu_write(0x4040, 0x3F);
+    let mut data: u8 = 0xFF;
+    cart.cpu_read(0x4040, &mut data);
+    assert_eq!(data, 0x00);
+    cart.cpu_write(0x4089, 0x80);
+    for i in 0..64u16 {
+        cart.cpu_write(0x4040 + i, if i < 32 { 0x3F } else { 0x00 });
+    }
+    cart.cpu_write(0x4089, 0x00);
+    cart.cpu_write(0x4080, 0xA0); // manual gain of 32
+    cart.cpu_write(0x4082, 0x00);
+    cart.cpu_write(0x4083, 0x04);
+    cart.cpu_read(0x4090, &mut data);
+    assert_eq!(data, 32);
+
+    let (low, high) = run(&mut cart, 8192);
+    assert_eq!(low, 0.0);
+    assert!(high > 0.3, "{high}");
+
+    // master volume 2/5
+    cart.cpu_write(0x4089, 0x03);
+    let (_, quiet) = run(&mut cart, 8192);
+    assert!((quiet / high - 0.4).abs() < 0.01);
+
+    // halting the wave holds the output
+    cart.cpu_write(0x4083, 0x84);
+    let (low, high) = run(&mut cart, 8192);
+    assert_eq!(low, high);
+
+    // the volume envelope ramps down from the manual gain
+    cart.cpu_write(0x4083, 0x04);
+    cart.cpu_write(0x408A, 0x01);
+    cart.cpu_write(0x4080, 0x00);
+    run(&mut cart, 8 * 32);
+    cart.cpu_read(0x4090, &mut data);
+    assert_eq!(data, 0);
+}
+
+#[test]
+fn test_unrom512_banking_and_mirroring() {
+    // four screen bit alone: one-screen mirroring from the register
+    let mut image = banked_image(32, 0, 0xE8);
+    image[7] = 0x10;
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    assert_eq!(cart.chr_ram_size(), 0x8000);
+    assert_eq!(cart.prg_nvram_size(), 0);
+    let read = |cart: &mut Cartrige, addr: u16| -> u8 {
+        let mut data: u8 = 0x00;
+        cart.cpu_read(addr, &mut data);
+        data
+    };
+
+    // bus conflicts with the $EA filling the last bank
+    cart.cpu_write(0xE000, 0xFF);
+    assert_eq!(read(&mut cart, 0x8000), 20);
+    assert_eq!(read(&mut cart, 0xC000), 62);
+    assert_eq!(cart.mirror(), Mirroring::ONESCREAN_HI);
+    cart.ppu_write(0x0010, 0x33);
+    cart.cpu_write(0xE000, 0x05);
+    assert_eq!(read(&mut cart, 0x8000), 0);
+    assert_eq!(cart.mirror(), Mirroring::ONESCREAN_LO);
+    let mut data: u8 = 0x00;
+    cart.ppu_read(0x0010, &mut data);
+    assert_eq!(data, 0x00);
+    cart.cpu_write(0xE000, 0x60);
+    cart.ppu_read(0x0010, &mut data);
+    assert_eq!(data, 0x33);
+
+    // plain mirroring bits are left alone
+    let mut image = banked_image(32, 0, 0xE1);
+    image[7] = 0x10;
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    cart.cpu_write(0xE000, 0xFF);
+    assert_eq!(cart.mirror(), Mirroring::VERTICAL);
+}
+
+/// The SST39SF040 unlock: $AA to $5555 and $55 to $2AAA, switching banks to
+/// reach those flash addresses.
+fn flash_unlock(cart: &mut Cartrige) {
+    cart.cpu_write(0xC000, 0x01);
+    cart.cpu_write(0x9555, 0xAA);
+    cart.cpu_write(0xC000, 0x00);
+    cart.cpu_write(0xAAAA, 0x55);
+}
+
+/// Unlocks the flash and writes `command` to $5555.
+fn flash_command(cart: &mut Cartrige, command: u8) {
+    flash_unlock(cart);
+    cart.cpu_write(0xC000, 0x01);
+    cart.cpu_write(0x9555, command);
+}
+
+/// Erases the sector at $8000 in bank 2 and programs `data` at $8010.
+fn flash_program(cart: &mut Cartrige, data: u8) {
+    flash_command(cart, 0x80);
+    flash_unlock(cart);
+    cart.cpu_write(0xC000, 0x02);
+    cart.cpu_write(0x8000, 0x30);
+    flash_command(cart, 0xA0);
+    cart.cpu_write(0xC000, 0x02);
+    cart.cpu_write(0x8010, data);
+}
+
+#[test]
+fn test_unrom512_flash() {
+    let mut image = banked_image(32, 0, 0xE2);
+    image[7] = 0x10;
+    let mut cart = Cartrige::from_bytes(&image).unwrap();
+    let read = |cart: &mut Cartrige, addr: u16| -> u8 {
+        let mut data: u8 = 0x00;
+        cart.cpu_read(addr, &mut data);
+        data
+    };
+
+    // writes to $8000-$BFFF no longer reach the register
+    cart.cpu_write(0x8000, 0x03);
+    assert_eq!(read(&mut cart, 0x8000), 0);
+
+    // sector erase, then program
+    flash_program(&mut cart, 0x12);
+    assert_eq!(read(&mut cart, 0x8010), 0x12);
+    assert_eq!(read(&mut cart, 0x8011), 0xFF);
+    assert_eq!(read(&mut cart, 0x9000), 4); // the next sector
+                                            // programming only clears bits
+    flash_command(&mut cart, 0xA0);
+    cart.cpu_write(0xC000, 0x02);
+    cart.cpu_write(0x8010, 0xF0);
+    assert_eq!(read(&mut cart, 0x8010), 0x10);
+    // a write outside a command sequence does nothing
+    cart.cpu_write(0x8011, 0x00);
+    assert_eq!(read(&mut cart, 0x8011), 0xFF);
+
+    // software ID
+    flash_command(&mut cart, 0x90);
+    assert_eq!(read(&mut cart, 0x8000), 0xBF);
+    assert_eq!(read(&mut cart, 0x8001), 0xB7);
+    cart.cpu_write(0x8000, 0xF0);
+    cart.cpu_write(0xC000, 0x02);
+    assert_eq!(read(&mut cart, 0x8010), 0x10);
+
+    // chip erase
+    flash_command(&mut cart, 0x80);
+    flash_command(&mut cart, 0x10);
+    cart.cpu_write(0xC000, 0x02);
+    assert_eq!(read(&mut cart, 0x8010), 0xFF);
+    assert_eq!(read(&mut cart, 0xC000), 0xFF);
+}
+
+#[test]
+fn test_unrom512_flash_saved_as_ips() {
+    let mut image = banked_image(32, 0, 0xE2);
+    image[7] = 0x10;
+    let dir = TempDir::new("unrom512");
+    let path = dir.join("homebrew.nes");
+    std::fs::write(&path, &image).unwrap();
+
+    let mut cart = Cartrige::from_file(&path).unwrap();
+    cart.save_writes().unwrap();
+    assert!(!cartridge::write_save_path(&path).exists());
+    flash_program(&mut cart, 0x42);
+    cart.save_writes().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), image);
+    assert!(dir.join("homebrew.nes.ips").exists());
+
+    let mut cart = Cartrige::from_file(&path).unwrap();
+    cart.cpu_write(0xC000, 0x02);
+    let mut data: u8 = 0x00;
+    cart.cpu_read(0x8010, &mut data);
+    assert_eq!(data, 0x42);
+    cart.cpu_read(0x8011, &mut data);
+    assert_eq!(data, 0xFF);
+}
+
+#[test]
+fn test_battery_save_round_trip() {
+    let dir = TempDir::new("sav");
+    let path = dir.join("rpg.nes");
+    std::fs::write(&path, ines_image(2, 1, 0x02, 0x00)).unwrap();
+
+    let mut cart = Cartrige::from_file(&path).unwrap();
+    assert_eq!(cart.save_path(), Some(dir.join("rpg.sav")));
+    // nothing written yet, still a first save
+    assert!(cart.flush_save().unwrap());
+    assert!(!cart.flush_save().unwrap());
+    cart.cpu_write(0x6000, 0x5A);
+    cart.cpu_write(0x7FFF, 0xA5);
+    assert!(cart.flush_save().unwrap());
+    assert!(!cart.flush_save().unwrap());
+    let save: Vec<u8> = std::fs::read(dir.join("rpg.sav")).unwrap();
+    assert_eq!(save.len(), 0x2000);
+    assert_eq!((save[0], save[0x1FFF]), (0x5A, 0xA5));
+    assert!(!dir.join("rpg.sav.tmp").exists());
+
+    // loaded again when the cartridge comes back
+    let mut cart = Cartrige::from_file(&path).unwrap();
+    let mut data: u8 = 0x00;
+    cart.cpu_read(0x6000, &mut data);
+    assert_eq!(data, 0x5A);
+    assert!(!cart.flush_save().unwrap());
+
+    // no battery, no save
+    let path = dir.join("action.nes");
+    std::fs::write(&path, ines_image(2, 1, 0x00, 0x00)).unwrap();
+    let mut cart = Cartrige::from_file(&path).unwrap();
+    cart.cpu_write(0x6000, 0x5A);
+    assert!(!cart.flush_save().unwrap());
+    assert!(!dir.join("action.sav").exists());
+}
+
+#[test]
+fn test_bus_marks_save_due_periodically() {
+    let dir = TempDir::new("flush");
+    let path = dir.join("rpg.nes");
+    std::fs::write(&path, banked_image(2, 1, 0x02)).unwrap();
+
+    // short enough for the cpu to stay on the NOP sled
+    let mut bus = Bus::new();
+    let mut cpu = CPU::new();
+    bus.save_interval = 1000;
+    bus.insert_cartridge(Cartrige::from_file(&path).unwrap());
+    bus.reset(&mut cpu);
+    bus.write(0x6000, 0x42);
+    for _ in 0..3 * 1000 - 3 {
+        bus.clock(&mut cpu);
+    }
+    assert!(!bus.save_due());
+    for _ in 0..3 {
+        bus.clock(&mut cpu);
+    }
+    // clocking never touches the disk, the frontend flushes when due
+    assert!(bus.save_due());
+    assert!(!dir.join("rpg.sav").exists());
+    assert!(bus.flush_save().unwrap());
+    assert!(!bus.save_due());
+    assert_eq!(std::fs::read(dir.join("rpg.sav")).unwrap()[0], 0x42);
+
+    // and once more when the console is switched off
+    bus.write(0x6001, 0x43);
+    bus.shutdown().unwrap();
+    assert_eq!(std::fs::read(dir.join("rpg.sav")).unwrap()[1], 0x43);
+}
+
+#[test]
+fn test_crc32_and_sha1() {
+    assert_eq!(hash::crc32(b""), 0x00000000);
+    assert_eq!(hash::crc32(b"123456789"), 0xCBF43926);
+
+    let hex = |data: &[u8]| -> String {
+        hash::sha1(data)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect()
+    };
+    assert_eq!(hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    // 56 bytes, the length no longer fits in the first block
+    assert_eq!(
+        hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+}
+
+#[test]
+fn test_rom_database_parsing() {
+    let embedded: &RomDatabase = RomDatabase::embedded();
+    assert!(!embedded.is_empty());
+    assert!(embedded.find(&RomHash::of(b"")).is_none());
+    let mut smb = RomHash {
+        crc32: 0x3337EC46,
+        sha1: [
+            0xEA, 0x34, 0x3F, 0x4E, 0x44, 0x5A, 0x90, 0x50, 0xD4, 0xB4, 0xFB,
+            0xAC, 0x2C, 0x77, 0xD0, 0x69, 0x3B, 0x1D, 0x09, 0x22,
+        ],
+    };
+    let entry = embedded.find(&smb).unwrap();
+    assert_eq!(entry.title, "Super Mario Bros. (World)");
+    assert_eq!(entry.mapper, Some(0));
+    assert_eq!(entry.mirror, Some(Mirroring::VERTICAL));
+    smb.sha1[0] ^= 0x01;
+    assert!(embedded.find(&smb).is_none());
+
+    let database = RomDatabase::parse(
+        "# comment\n\
+         \n\
+         CBF43926 | - | 4 | 1 | V | yes | PAL | Game (Europe)\n\
+         DEADBEEF | - | - | - | - | - | - | Title | with a bar\n",
+    )
+    .unwrap();
+    assert_eq!(database.len(), 2);
+    let entry = database.find(&RomHash::of(b"123456789")).unwrap();
+    assert_eq!(entry.mapper, Some(4));
+    assert_eq!(entry.submapper, Some(1));
+    assert_eq!(entry.mirror, Some(Mirroring::VERTICAL));
+    assert_eq!(entry.battery, Some(true));
+    assert_eq!(entry.timing, Some(TimingRegion::PAL));
+    assert_eq!(entry.title, "Game (Europe)");
+    let other = RomHash {
+        crc32: 0xDEADBEEF,
+        sha1: [0u8; 20],
+    };
+    let entry = database.find(&other).unwrap();
+    assert_eq!((entry.mapper, entry.mirror), (None, None));
+    assert_eq!(entry.title, "Title | with a bar");
+
+    // a SHA-1 that does not match hides the entry
+    let database = RomDatabase::parse(&format!(
+        "CBF43926 | {} | 4 | - | - | - | - | Game",
+        "00".repeat(20)
+    ))
+    .unwrap();
+    assert!(database.find(&RomHash::of(b"123456789")).is_none());
+
+    assert_eq!(
+        RomDatabase::parse("# header\nCBF43926 | - | 4").unwrap_err(),
+        RomDbError {
+            line: 2,
+            message: "expected 8 fields separated by '|'"
+        }
+    );
+    assert_eq!(
+        RomDatabase::parse("CBF43926 | - | 4 | - | X | - | - | Game")
+            .unwrap_err()
+            .line,
+        1
+    );
+}
+
+#[test]
+fn test_rom_database_corrects_header() {
+    // header says mapper 0, horizontal, no battery, NTSC
+    let image: Vec<u8> = ines_image(2, 1, 0x00, 0x00);
+    let hash: RomHash = RomHash::of(&image[cartridge::INES_HEADER_SIZE..]);
+    let database = RomDatabase::parse(&format!(
+        "{:08X} | - | 2 | - | V | yes | PAL | Test Game (World)",
+        hash.crc32
+    ))
+    .unwrap();
+
+    let cart = Cartrige::from_bytes_with_database(&image, &database).unwrap();
+    assert_eq!(cart.mapper_id(), 2);
+    assert_eq!(cart.mirror, Mirroring::VERTICAL);
+    assert!(cart.battery());
+    assert_eq!(cart.prg_ram_size(), 0);
+    assert_eq!(cart.prg_nvram_size(), components::KB(8));
+    assert_eq!(cart.timing(), TimingRegion::PAL);
+    assert_eq!(cart.title(), Some("Test Game (World)"));
+    assert_eq!(cart.hash(), hash);
+
+    let info = cart.rom_info();
+    assert!(info.corrected);
+    assert_eq!(info.prg_rom_size, components::KB(32));
+    assert_eq!(info.chr_rom_size, components::KB(8));
+    let text: String = info.to_string();
+    assert!(text.starts_with("Test Game (World)\n"));
+    assert!(text.contains(&format!("CRC32 {:08X}", hash.crc32)));
+    assert!(text.contains("mapper 2.0 | PRG 32KB | CHR 8KB"));
+    assert!(text.ends_with("PAL | battery | header corrected"));
+
+    // a matching header only picks up the title
+    let database = RomDatabase::parse(&format!(
+        "{:08X} | - | 0 | 0 | H | no | NTSC | Test Game (World)",
+        hash.crc32
+    ))
+    .unwrap();
+    let cart = Cartrige::from_bytes_with_database(&image, &database).unwrap();
+    assert_eq!(cart.title(), Some("Test Game (World)"));
+    assert!(!cart.rom_info().corrected);
+
+    // NES 2.0 battery corrections move the RAM between the two pools
+    let mut image: Vec<u8> = ines_image(2, 1, 0x02, 0x08);
+    image[10] = 0x70; // 8KB PRG NVRAM
+    let database = RomDatabase::parse(&format!(
+        "{:08X} | - | - | - | - | no | - | Test Game (World)",
+        hash.crc32
+    ))
+    .unwrap();
+    let cart = Cartrige::from_bytes_with_database(&image, &database).unwrap();
+    assert!(!cart.battery());
+    assert_eq!(cart.prg_ram_size(), components::KB(8));
+    assert_eq!(cart.prg_nvram_size(), 0);
+    assert_eq!(cart.battery_data(), None);
+
+    // unknown dumps keep the header
+    let image: Vec<u8> = ines_image(2, 1, 0x00, 0x00);
+    let cart = Cartrige::from_bytes(&image).unwrap();
+    assert_eq!(cart.mapper_id(), 0);
+    assert_eq!(cart.title(), None);
+    assert!(cart.rom_info().to_string().starts_with("unknown dump\n"));
+}
+
+/// A UNIF image made of `chunks`.
+fn unif_image(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut image: Vec<u8> = b"UNIF\x07\x00\x00\x00".to_vec();
+    image.resize(unif::UNIF_HEADER_SIZE, 0x00);
+    for (id, data) in chunks {
+        image.extend_from_slice(*id);
+        image.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        image.extend_from_slice(data);
+    }
+    image
+}
+
+#[test]
+fn test_unif_board_names() {
+    assert_eq!(
+        unif::board_mapper("NES-SNROM"),
+        Some((1, 0, components::KB(8)))
+    );
+    assert_eq!(unif::board_mapper("SNROM"), Some((1, 0, components::KB(8))));
+    assert_eq!(unif::board_mapper("HVC-TLROM"), Some((4, 0, 0)));
+    assert_eq!(
+        unif::board_mapper("NES-EWROM"),
+        Some((5, 0, components::KB(32)))
+    );
+    assert_eq!(unif::board_mapper("UNL-UNROM-512-32"), Some((30, 0, 0)));
+    assert_eq!(unif::board_mapper("NES-SOMETHING"), None);
+}
+
+#[test]
+fn test_unif_loading() {
+    // an MMC1 game split over two PRG chunks, with a checksum on the first
+    let prg0: Vec<u8> = vec![0x11; components::KB(64)];
+    let prg1: Vec<u8> = vec![0x22; components::KB(64)];
+    let chr0: Vec<u8> = vec![0x33; components::KB(8)];
+    let image: Vec<u8> = unif_image(&[
+        (b"MAPR", b"NES-SNROM\0"),
+        (b"NAME", b"Some Game\0"),
+        (b"PRG1", &prg1),
+        (b"PRG0", &prg0),
+        (b"PCK0", &hash::crc32(&prg0).to_le_bytes()),
+        (b"CHR0", &chr0),
+        (b"MIRR", &[1]),
+        (b"BATR", &[1]),
+        (b"TVCI", &[1]),
+    ]);
+    let unif = UnifImage::parse(&image).unwrap();
+    assert_eq!(unif.board, "NES-SNROM");
+    assert_eq!(unif.prg.len(), components::KB(128));
+    assert_eq!((unif.prg[0], unif.prg[components::KB(64)]), (0x11, 0x22));
+
+    let cart = Cartrige::from_unif(&image).unwrap();
+    assert_eq!(cart.format(), HeaderFormat::NES2);
+    assert_eq!((cart.mapper_id(), cart.submapper()), (1, 0));
+    assert_eq!((cart.prg_banks(), cart.chr_banks()), (8, 1));
+    assert_eq!(cart.mirror, Mirroring::VERTICAL);
+    assert!(cart.battery());
+    assert_eq!(cart.prg_nvram_size(), components::KB(8));
+    assert_eq!(cart.prg_ram_size(), 0);
+    assert_eq!(cart.timing(), TimingRegion::PAL);
+    assert_eq!(cart.title(), Some("Some Game"));
+    assert_eq!(cart.chr_mem(), &chr0[..]);
+
+    // 8KB of PRG repeats to fill the 16KB bank, no CHR means CHR RAM
+    let prg: Vec<u8> = (0..components::KB(8)).map(|i| i as u8).collect();
+    let image: Vec<u8> =
+        unif_image(&[(b"MAPR", b"NES-UNROM\0"), (b"PRG0", &prg)]);
+    let cart = Cartrige::from_unif(&image).unwrap();
+    assert_eq!(cart.mapper_id(), 2);
+    assert_eq!(cart.prg_mem()[..components::KB(8)], prg[..]);
+    assert_eq!(cart.prg_mem()[components::KB(8)..], prg[..]);
+    assert_eq!(cart.chr_mem().len(), components::KB(8));
+    assert_eq!(cart.mirror, Mirroring::HORIZONTAL);
+    assert_eq!(cart.title(), None);
+
+    // UNROM 512 with a battery saves its flash, there is no work RAM
+    let unrom512: Vec<u8> = unif_image(&[
+        (b"MAPR", b"UNL-UNROM-512-32\0"),
+        (b"PRG0", &prg),
+        (b"BATR", &[1]),
+    ]);
+    let cart = Cartrige::from_unif(&unrom512).unwrap();
+    assert_eq!(cart.mapper_id(), 30);
+    assert!(cart.battery());
+    assert_eq!(cart.prg_nvram_size(), 0);
+    assert_eq!(cart.prg_ram_size(), 0);
+    assert_eq!(cart.battery_data(), None);
+
+    // loading by path, plain or zipped, goes through the same parsing
+    let dir = TempDir::new("unif");
+    let unf_path = dir.join("game.unf");
+    std::fs::write(&unf_path, &image).unwrap();
+    assert_eq!(Cartrige::from_file(&unf_path).unwrap().mapper_id(), 2);
+    let zip_path = dir.join("game.zip");
+    std::fs::write(&zip_path, zip_archive(&[("game.unf", &image)], true))
+        .unwrap();
+    assert_eq!(Cartrige::from_file(&zip_path).unwrap().mapper_id(), 2);
+
+    // errors
+    let image: Vec<u8> =
+        unif_image(&[(b"MAPR", b"UNL-MYSTERY\0"), (b"PRG0", &prg)]);
+    let error = Cartrige::from_unif(&image).unwrap_err();
+    assert!(matches!(
+        &error,
+        CartridgeError::Unif(UnifError::UnknownBoard(board))
+            if board == "UNL-MYSTERY"
+    ));
+    assert_eq!(error.to_string(), "UNIF board UNL-MYSTERY is not supported");
+    let image: Vec<u8> = unif_image(&[
+        (b"MAPR", b"NES-NROM-256\0"),
+        (b"PRG0", &prg),
+        (b"PCK0", &[0, 0, 0, 0]),
+    ]);
+    assert!(matches!(
+        UnifImage::parse(&image),
+        Err(UnifError::Checksum { chunk, .. }) if chunk == "PRG0"
+    ));
+    let mut image: Vec<u8> = unif_image(&[(b"PRG0", &prg)]);
+    assert_eq!(UnifImage::parse(&image), Err(UnifError::NoBoard));
+    image.truncate(image.len() - 1);
+    assert_eq!(UnifImage::parse(&image), Err(UnifError::Truncated));
+}
+
+/// Clocks the cpu through whatever it is doing and the whole next
+/// instruction (or interrupt sequence). Returns the cycles it took.
+fn step(cpu: &mut CPU, bus: &mut Bus) -> u32 {
+    let start: u32 = cpu.clock_count();
+    CPU::clock(cpu, bus);
+    while !cpu.complete() {
+        CPU::clock(cpu, bus);
+    }
+    cpu.clock_count() - start
+}
+
+#[test]
+fn test_stack_instructions() {
+    let mut cpu = CPU::new();
+    let mut bus = Bus::new_flat();
+    // JSR $9000
+    for (addr, byte) in (0x8000..).zip([0x20, 0x00, 0x90]) {
+        bus.write(addr, byte);
+    }
+    // PHP, PLA, PHA, PLP, RTS
+    for (addr, byte) in (0x9000..).zip([0x08, 0x68, 0x48, 0x28, 0x60]) {
+        bus.write(addr, byte);
+    }
+    bus.write(0xFFFC, 0x00);
+    bus.write(0xFFFD, 0x80);
+    CPU::reset(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    cpu.set_status(CpuFlags::U as u8);
+
+    assert_eq!(step(&mut cpu, &mut bus), 6);
+    assert_eq!(cpu.pc(), 0x9000);
+    assert_eq!(cpu.sp(), 0xFB);
+    assert_eq!(bus.read(0x01FD, true), 0x80);
+    assert_eq!(bus.read(0x01FC, true), 0x02);
+
+    // PHP pushes B and U set, PLA sees them, PLP drops B again
+    step(&mut cpu, &mut bus);
+    assert_eq!(
+        bus.read(0x01FB, true),
+        CpuFlags::B as u8 | CpuFlags::U as u8
+    );
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), CpuFlags::B as u8 | CpuFlags::U as u8);
+    assert_eq!(cpu.get_flag(CpuFlags::N), 0);
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.status(), CpuFlags::U as u8);
+
+    assert_eq!(step(&mut cpu, &mut bus), 6);
+    assert_eq!(cpu.pc(), 0x8003);
+    assert_eq!(cpu.sp(), 0xFD);
+
+    // clearing a flag leaves the others alone
+    cpu.set_flag(CpuFlags::C, true);
+    cpu.set_flag(CpuFlags::C, false);
+    assert_eq!(cpu.status(), CpuFlags::U as u8);
+
+    // the stack pointer wraps around the page
+    cpu.set_sp(0x00);
+    bus.write(0x8003, 0x48); // PHA
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.sp(), 0xFF);
+}
+
+/// A flat bus with a NOP sled at $8000, the reset vector pointing at it,
+/// IRQ/BRK handlers at $9000 and the NMI handler at $A000. Both handlers
+/// are a NOP followed by RTI. The reset sequence is already stepped over.
+fn interrupt_test_bus(cpu: &mut CPU, program: &[u8]) -> Bus {
+    let mut bus = Bus::new_flat();
+    for addr in 0x8000..0x8100 {
+        bus.write(addr, 0xEA);
+    }
+    for (addr, byte) in (0x8000..).zip(program) {
+        bus.write(addr, *byte);
+    }
+    for handler in [0x9000, 0xA000] {
+        bus.write(handler, 0xEA);
+        bus.write(handler + 1, 0x40);
+    }
+    bus.write(0xFFFA, 0x00);
+    bus.write(0xFFFB, 0xA0);
+    bus.write(0xFFFC, 0x00);
+    bus.write(0xFFFD, 0x80);
+    bus.write(0xFFFE, 0x00);
+    bus.write(0xFFFF, 0x90);
+    CPU::reset(cpu, &mut bus);
+    step(cpu, &mut bus);
+    bus
+}
+
+#[test]
+fn test_irq_is_level_triggered_and_masked() {
+    let mut cpu = CPU::new();
+    // CLI
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x58]);
+    assert_eq!(cpu.get_flag(CpuFlags::I), 1);
+    cpu.set_irq(true);
+
+    // the IRQ is only taken after the instruction following CLI
+    assert_eq!(step(&mut cpu, &mut bus), 2);
+    assert_eq!(cpu.pc(), 0x8001);
+    assert_eq!(step(&mut cpu, &mut bus), 2);
+    assert_eq!(cpu.pc(), 0x8002);
+
+    assert_eq!(step(&mut cpu, &mut bus), 7);
+    assert_eq!(cpu.pc(), 0x9000);
+    assert_eq!(cpu.sp(), 0xFA);
+    assert_eq!(bus.read(0x01FD, true), 0x80);
+    assert_eq!(bus.read(0x01FC, true), 0x02);
+    // B clear, U set, I as it was before the interrupt
+    assert_eq!(bus.read(0x01FB, true), CpuFlags::U as u8);
+    assert_eq!(cpu.get_flag(CpuFlags::I), 1);
+
+    // the line is still asserted but I masks it until RTI restores I
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0x8002);
+    assert_eq!(cpu.sp(), 0xFD);
+    assert_eq!(cpu.get_flag(CpuFlags::I), 0);
+    assert_eq!(step(&mut cpu, &mut bus), 7);
+    assert_eq!(cpu.pc(), 0x9000);
+
+    // released, the handler returns to the sled for good
+    cpu.set_irq(false);
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    for _ in 0..4 {
+        step(&mut cpu, &mut bus);
+    }
+    assert_eq!(cpu.pc(), 0x8006);
+
+    // SEI still lets an IRQ through right after it, pushed with I set
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x58, 0xEA, 0x78]);
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    cpu.set_irq(true);
+    step(&mut cpu, &mut bus);
+    assert_eq!(step(&mut cpu, &mut bus), 7);
+    assert_eq!(bus.read(0x01FB, true), 0x24);
+
+    // CPU::irq respects I, CPU::nmi does not
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[]);
+    CPU::irq(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0x8000);
+    CPU::nmi(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0xA000);
+    assert_eq!(cpu.cycles(), 7);
+}
+
+#[test]
+fn test_nmi_is_edge_triggered() {
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[]);
+
+    cpu.set_nmi(true);
+    assert!(cpu.nmi_pending());
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0x8001);
+    // taken with I set
+    assert_eq!(step(&mut cpu, &mut bus), 7);
+    assert_eq!(cpu.pc(), 0xA000);
+    assert!(!cpu.nmi_pending());
+    assert_eq!(bus.read(0x01FD, true), 0x80);
+    assert_eq!(bus.read(0x01FC, true), 0x01);
+    assert_eq!(
+        bus.read(0x01FB, true),
+        CpuFlags::U as u8 | CpuFlags::I as u8
+    );
+
+    // holding the line does not retrigger
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0x8001);
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0x8003);
+
+    // a new edge does, even when released again before it is serviced
+    cpu.set_nmi(false);
+    cpu.set_nmi(true);
+    cpu.set_nmi(false);
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0xA000);
+
+    // NMI wins over an IRQ asserted at the same time
+    let mut cpu = CPU::new();
+    // CLI, NOP
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x58]);
+    step(&mut cpu, &mut bus);
+    cpu.set_irq(true);
+    cpu.set_nmi(true);
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0xA000);
+}
+
+#[test]
+fn test_brk() {
+    let mut cpu = CPU::new();
+    // CLI, BRK, padding
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x58, 0x00, 0xFF]);
+    step(&mut cpu, &mut bus);
+
+    assert_eq!(step(&mut cpu, &mut bus), 7);
+    assert_eq!(cpu.pc(), 0x9000);
+    // the return address skips the padding byte, B is set on the stack
+    assert_eq!(bus.read(0x01FD, true), 0x80);
+    assert_eq!(bus.read(0x01FC, true), 0x03);
+    assert_eq!(
+        bus.read(0x01FB, true),
+        CpuFlags::B as u8 | CpuFlags::U as u8
+    );
+    assert_eq!(cpu.get_flag(CpuFlags::B), 0);
+    assert_eq!(cpu.get_flag(CpuFlags::I), 1);
+
+    step(&mut cpu, &mut bus);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0x8003);
+    assert_eq!(cpu.get_flag(CpuFlags::I), 0);
+    assert_eq!(cpu.get_flag(CpuFlags::B), 0);
+}
+
+#[test]
+fn test_ppu_vblank_nmi_reaches_the_cpu() {
+    // JMP $E000 so the cpu never runs off the sled into the vectors
+    let mut image = banked_image(2, 1, 0x00);
+    let sled: usize = cartridge::INES_HEADER_SIZE
+        + 2 * cartridge::PRG_BANK_SIZE
+        - components::KB(8);
+    image[sled..sled + 3].copy_from_slice(&[0x4C, 0x00, 0xE0]);
+    let mut cpu = CPU::new();
+    let mut bus = Bus::new();
+    bus.insert_cartridge(Cartrige::from_bytes(&image).unwrap());
+    bus.reset(&mut cpu);
+
+    // without PPUCTRL bit 7 the vblank flag alone does nothing
+    while bus.ppu.scan_line() != 242 {
+        bus.clock(&mut cpu);
+    }
+    assert!(!cpu.nmi_line());
+    assert_eq!(cpu.sp(), 0xFD);
+
+    // enabling NMIs in the middle of vblank raises the line right away
+    bus.write(0x2000, 0x80);
+    bus.clock(&mut cpu);
+    assert!(cpu.nmi_line());
+    for _ in 0..60 {
+        bus.clock(&mut cpu);
+    }
+    assert!(!cpu.nmi_pending());
+    assert_eq!(cpu.sp(), 0xFA);
+    assert_eq!(cpu.get_flag(CpuFlags::I), 1);
+
+    // the pre-render line clears vblank and releases /NMI
+    while bus.ppu.scan_line() != 0 {
+        bus.clock(&mut cpu);
+    }
+    assert!(!cpu.nmi_line());
+}
+
+#[test]
+fn test_alu_flags() {
+    let mut cpu = CPU::new();
+    // ADC #$01, SBC #$01, CMP #$40, ASL A, ROR A, EOR #$FF, DEX
+    let program = [
+        0x69, 0x01, 0xE9, 0x01, 0xC9, 0x40, 0x0A, 0x6A, 0x49, 0xFF, 0xCA,
+    ];
+    let mut bus = interrupt_test_bus(&mut cpu, &program);
+    cpu.set_status(CpuFlags::U as u8);
+
+    // signed overflow into the sign bit
+    cpu.set_a(0x7F);
+    assert_eq!(step(&mut cpu, &mut bus), 2);
+    assert_eq!(cpu.a(), 0x80);
+    assert_eq!(cpu.status(), 0xE0); // N V U
+
+    // 0 - 1 borrows
+    cpu.set_a(0x00);
+    cpu.set_status(CpuFlags::U as u8 | CpuFlags::C as u8);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), 0xFF);
+    assert_eq!(cpu.get_flag(CpuFlags::C), 0);
+    assert_eq!(cpu.get_flag(CpuFlags::N), 1);
+
+    cpu.set_a(0x40);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.get_flag(CpuFlags::Z), 1);
+    assert_eq!(cpu.get_flag(CpuFlags::C), 1);
+
+    // the bit shifted out lands in C, ROR shifts the old C in
+    cpu.set_a(0x81);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), 0x02);
+    assert_eq!(cpu.get_flag(CpuFlags::C), 1);
+    cpu.set_a(0x01);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), 0x80);
+    assert_eq!(cpu.get_flag(CpuFlags::C), 1);
+    assert_eq!(cpu.get_flag(CpuFlags::N), 1);
+
+    // EOR sets the flags from A
+    cpu.set_a(0x0F);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), 0xF0);
+    assert_eq!(cpu.get_flag(CpuFlags::N), 1);
+    assert_eq!(cpu.get_flag(CpuFlags::Z), 0);
+
+    cpu.set_x(0x00);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.x(), 0xFF);
+    assert_eq!(cpu.pc(), 0x800B);
+}
+
+#[test]
+fn test_addressing_modes() {
+    let mut cpu = CPU::new();
+    // LDA $FF,X / LDA ($FF),Y / LDA $12FF,X / JMP ($10FF)
+    let program = [0xB5, 0xFF, 0xB1, 0xFF, 0xBD, 0xFF, 0x12, 0x6C, 0xFF, 0x10];
+    let mut bus = interrupt_test_bus(&mut cpu, &program);
+    bus.write(0x0001, 0x11);
+    // the pointer at $FF takes its high byte from $00
+    bus.write(0x00FF, 0xF0);
+    bus.write(0x0000, 0x20);
+    bus.write(0x2100, 0x22);
+    bus.write(0x1301, 0x33);
+    bus.write(0x10FF, 0x34);
+    bus.write(0x1000, 0x90);
+    bus.write(0x1100, 0xA0);
+
+    // zero page indexing wraps inside the zero page
+    cpu.set_x(0x02);
+    assert_eq!(step(&mut cpu, &mut bus), 4);
+    assert_eq!(cpu.a(), 0x11);
+
+    // crossing a page costs a cycle
+    cpu.set_y(0x10);
+    assert_eq!(step(&mut cpu, &mut bus), 6);
+    assert_eq!(cpu.a(), 0x22);
+    assert_eq!(step(&mut cpu, &mut bus), 5);
+    assert_eq!(cpu.a(), 0x33);
+
+    // the indirect pointer never carries into its high byte
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.pc(), 0x9034);
+
+    // branches go backwards, a taken branch costs a cycle
+    let mut cpu = CPU::new();
+    // NOP, BNE -3
+    let mut bus = interrupt_test_bus(&mut cpu, &[0xEA, 0xD0, 0xFD]);
+    step(&mut cpu, &mut bus);
+    assert_eq!(step(&mut cpu, &mut bus), 3);
+    assert_eq!(cpu.pc(), 0x8000);
+}
+#[test]
+fn test_unofficial_read_modify_write() {
+    let c: u8 = CpuFlags::C as u8;
+    let z: u8 = CpuFlags::Z as u8;
+    let n: u8 = CpuFlags::N as u8;
+    let v: u8 = CpuFlags::V as u8;
+    // (opcode on $10, memory, A, C in) -> (memory, A, flags out)
+    let cases: [(u8, u8, u8, bool, u8, u8, u8); 6] = [
+        (0x07, 0x81, 0x01, false, 0x02, 0x03, c), // SLO
+        (0x27, 0x81, 0x03, true, 0x03, 0x03, c),  // RLA
+        (0x47, 0x03, 0x01, false, 0x01, 0x00, c | z), // SRE
+        (0x67, 0x02, 0x7F, true, 0x81, 0x00, c | z), // RRA
+        (0xC7, 0x41, 0x40, false, 0x40, 0x40, c | z), // DCP
+        (0xE7, 0x7F, 0x70, true, 0x80, 0xF0, n | v), // ISC
+    ];
+    for (opcode, memory, a, carry, memory_out, a_out, flags) in cases {
+        let mut cpu = CPU::new();
+        let mut bus = interrupt_test_bus(&mut cpu, &[opcode, 0x10]);
+        bus.write(0x0010, memory);
+        cpu.set_a(a);
+        cpu.set_status(CpuFlags::U as u8 | if carry { c } else { 0 });
+
+        assert_eq!(step(&mut cpu, &mut bus), 5, "{opcode:02X}");
+        assert_eq!(bus.read(0x0010, true), memory_out, "{opcode:02X}");
+        assert_eq!(cpu.a(), a_out, "{opcode:02X}");
+        assert_eq!(cpu.status() & (c | z | n | v), flags, "{opcode:02X}");
+        assert_eq!(cpu.pc(), 0x8002);
+    }
+
+    // the indexed forms never take the shortcut on a page cross
+    let mut cpu = CPU::new();
+    // DCP $12FF,X
+    let mut bus = interrupt_test_bus(&mut cpu, &[0xDF, 0xFF, 0x12]);
+    cpu.set_x(0x01);
+    assert_eq!(step(&mut cpu, &mut bus), 7);
+    assert_eq!(bus.read(0x1300, true), 0xFF);
+}
+
+#[test]
+fn test_unofficial_loads_stores_and_immediates() {
+    // LAX $10 / LAX ($20),Y across a page
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[0xA7, 0x10, 0xB3, 0x20]);
+    bus.write(0x0010, 0x80);
+    bus.write(0x0020, 0xFF);
+    bus.write(0x0021, 0x12);
+    bus.write(0x1301, 0x00);
+    assert_eq!(step(&mut cpu, &mut bus), 3);
+    assert_eq!((cpu.a(), cpu.x()), (0x80, 0x80));
+    assert_eq!(cpu.get_flag(CpuFlags::N), 1);
+    cpu.set_y(0x02);
+    assert_eq!(step(&mut cpu, &mut bus), 6);
+    assert_eq!((cpu.a(), cpu.x()), (0x00, 0x00));
+    assert_eq!(cpu.get_flag(CpuFlags::Z), 1);
+
+    // SAX $10,Y leaves the flags alone
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x97, 0x10]);
+    cpu.set_a(0xF0);
+    cpu.set_x(0x3C);
+    cpu.set_y(0x01);
+    let status: u8 = cpu.status();
+    assert_eq!(step(&mut cpu, &mut bus), 4);
+    assert_eq!(bus.read(0x0011, true), 0x30);
+    assert_eq!(cpu.status(), status);
+
+    // ANC #$80, ALR #$03, ARR #$FF, AXS #$02, SBC #$01 ($EB)
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(
+        &mut cpu,
+        &[0x0B, 0x80, 0x4B, 0x03, 0x6B, 0xFF, 0xCB, 0x02, 0xEB, 0x01],
+    );
+    cpu.set_a(0xFF);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), 0x80);
+    assert_eq!(cpu.get_flag(CpuFlags::C), 1);
+    cpu.set_a(0xFF);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), 0x01);
+    assert_eq!(cpu.get_flag(CpuFlags::C), 1);
+    cpu.set_a(0xC0);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), 0xE0);
+    assert_eq!(cpu.get_flag(CpuFlags::C), 1);
+    assert_eq!(cpu.get_flag(CpuFlags::V), 0);
+    cpu.set_a(0x0F);
+    cpu.set_x(0x07);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.x(), 0x05);
+    assert_eq!(cpu.get_flag(CpuFlags::C), 1);
+    cpu.set_a(0x10);
+    assert_eq!(step(&mut cpu, &mut bus), 2);
+    assert_eq!(cpu.a(), 0x0F);
+    assert_eq!(cpu.pc(), 0x800A);
+
+    // LAS $1200,Y
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[0xBB, 0x00, 0x12]);
+    bus.write(0x1200, 0x0F);
+    assert_eq!(step(&mut cpu, &mut bus), 4);
+    assert_eq!((cpu.a(), cpu.x(), cpu.sp()), (0x0D, 0x0D, 0x0D));
+}
+
+#[test]
+fn test_unofficial_unstable_stores() {
+    // SHX $1200,Y stores X & $13
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x9E, 0x00, 0x12]);
+    cpu.set_x(0xFF);
+    cpu.set_y(0x01);
+    assert_eq!(step(&mut cpu, &mut bus), 5);
+    assert_eq!(bus.read(0x1201, true), 0x13);
+
+    // across a page the value also becomes the high byte of the address
+    let mut cpu = CPU::new();
+    // SHY $12FF,X
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x9C, 0xFF, 0x12]);
+    cpu.set_x(0x01);
+    cpu.set_y(0x05);
+    step(&mut cpu, &mut bus);
+    assert_eq!(bus.read(0x0100, true), 0x01);
+    assert_eq!(bus.read(0x1300, true), 0x00);
+
+    // TAS $1200,Y sets SP = A & X as well
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x9B, 0x00, 0x12]);
+    cpu.set_a(0xF3);
+    cpu.set_x(0x3F);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.sp(), 0x33);
+    assert_eq!(bus.read(0x1200, true), 0x13);
+
+    // XAA #$FF
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &[0x8B, 0xFF]);
+    cpu.set_a(0x01);
+    cpu.set_x(0x0F);
+    step(&mut cpu, &mut bus);
+    assert_eq!(cpu.a(), 0x0F & 0xEF);
+}
+
+#[test]
+fn test_unofficial_nops_and_disassembly() {
+    // NOP #$xx, NOP $xx, NOP $xx,X, NOP $xxxx, NOP $xxxx,X across a page
+    let program: [u8; 11] = [
+        0x80, 0xFF, 0x04, 0x10, 0x14, 0x10, 0x0C, 0x00, 0x20, 0x1C, 0xFF,
+    ];
+    let mut cpu = CPU::new();
+    let mut bus = interrupt_test_bus(&mut cpu, &program);
+    bus.write(0x800B, 0x20);
+    cpu.set_x(0x01);
+    let status: u8 = cpu.status();
+    let cycles: Vec<u32> = (0..5).map(|_| step(&mut cpu, &mut bus)).collect();
+    assert_eq!(cycles, [2, 3, 4, 4, 5]);
+    assert_eq!(cpu.pc(), 0x800C);
+    assert_eq!(cpu.a(), 0x00);
+    assert_eq!(cpu.status(), status);
+
+    let mut bus = Bus::new_flat();
+    for (addr, byte) in (0x0000..).zip([0xA7, 0x10, 0xDB, 0x34, 0x12]) {
+        bus.write(addr, byte);
+    }
+    let disasm = CPU::disassemble(&mut bus, 0x0000, 0x0004);
+    assert_eq!(disasm[&0x0000], "$0: LAX $10 {zp0}");
+    assert_eq!(disasm[&0x0002], "$2: DCP $1234 {aby}");
+}